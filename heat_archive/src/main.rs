@@ -1,7 +1,8 @@
 
-use std::fs::{File};
+use std::fs::{File, read};
 use std::path::Path;
 use clap::Parser;
+use lib_heat_spec::module::Module;
 
 /// Heat archive is an utility to pack heat byte code
 #[derive(Parser, Debug)]
@@ -21,10 +22,19 @@ fn main() {
     let build_location = Path::new(&args.archive_location);
     let binary_location = Path::new(&args.binary);
 
+    let bin = read(binary_location).unwrap();
+    if let Err(err) = Module::from_bytes(&bin) {
+        eprintln!("{}: {}", binary_location.display(), err);
+        std::process::exit(1);
+    }
+
     let file = File::create(build_location.join("build.har")).unwrap();
     let mut f = tar::Builder::new(file);
 
-    let mut bin = File::open(binary_location).unwrap();
-
-    f.append_file("bin",&mut bin).unwrap();
-}
\ No newline at end of file
+    let mut header = tar::Header::new_gnu();
+    header.set_size(bin.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    f.append_data(&mut header, "bin", bin.as_slice()).unwrap();
+    f.finish().unwrap();
+}
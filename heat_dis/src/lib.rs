@@ -0,0 +1,351 @@
+use std::collections::HashSet;
+use heatc::compiler::is_label;
+use lib_heat_spec::h_type::{self, immediate_value, HType};
+use lib_heat_spec::instruction::SIZE;
+use lib_heat_spec::module::{Constant, FunctionEntry, Module};
+use lib_heat_spec::opcode;
+use libvirt::instruction::Instruction;
+
+/// A disassembled instruction
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line {
+    /// byte offset of the instruction in the module's code section
+    pub offset: usize,
+    pub instruction: Instruction,
+    /// HeatASM source of the instruction
+    pub text: String,
+    /// false if the opcode is not in `opcode::OPCODES`, `text` is not valid HeatASM then
+    pub known: bool,
+}
+
+/// A disassembled function of a module
+#[derive(Clone, Debug, PartialEq)]
+pub struct FunctionListing {
+    /// the `.func` declaration followed by the function's `.const` and `.local` declarations
+    pub declarations: Vec<String>,
+    /// the function's code, offsets are relative to the module's code section
+    pub lines: Vec<Line>,
+}
+
+/// Disassemble a single instruction into HeatASM
+///
+/// Trailing arguments that are 0 are left out, heatc fills them back in.
+///
+/// ## Examples
+/// ```
+/// use libvirt::instruction::Instruction;
+/// use lib_heat_spec::opcode;
+///
+/// let instruction = Instruction { opcode: opcode::LOAD_U8, arg1: 7, arg2: 0, arg3: 0 };
+/// assert_eq!(heat_dis::disassemble_instruction(&instruction), Some("LOAD_U8 7".to_string()));
+/// ```
+pub fn disassemble_instruction(instruction: &Instruction) -> Option<String> {
+    let mnemonic = opcode::to_mnemonic(instruction.opcode)?;
+    Some(format_instruction(mnemonic, instruction))
+}
+
+/// Disassemble the encoded instructions of a code section
+pub fn disassemble(code: &[u8]) -> Vec<Line> {
+    code.chunks_exact(SIZE as usize)
+        .enumerate()
+        .map(|(index, bytes)| {
+            let instruction = Instruction::from(bytes);
+            let (text, known) = match disassemble_instruction(&instruction) {
+                Some(text) => (text, true),
+                None => (format_instruction(&format!("??? {:#x}", instruction.opcode), &instruction), false),
+            };
+            Line { offset: index * SIZE as usize, instruction, text, known }
+        })
+        .collect()
+}
+
+/// Disassemble the constants of a module into `.const` declarations named after their index
+///
+/// LOAD_CONST instructions are disassembled with the index of their constant,
+/// which heatc takes in place of the name.
+///
+/// ## Examples
+/// ```
+/// use lib_heat_spec::h_type::HType;
+/// use lib_heat_spec::module::Constant;
+///
+/// let constants = [Constant::from_immediate(HType::I8, -1i64 as u64)];
+/// assert_eq!(heat_dis::disassemble_constants(&constants), vec![".const c0 I8 -1".to_string()]);
+/// ```
+pub fn disassemble_constants(constants: &[Constant]) -> Vec<String> {
+    constants.iter()
+        .enumerate()
+        .map(|(index, constant)| {
+            format!(".const c{} {:?} {}", index, constant.h_type, format_immediate(constant.h_type, constant.immediate()))
+        })
+        .collect()
+}
+
+/// Disassemble the locals a function declares into `.local` declarations named after their slot
+///
+/// Declared locals follow the parameters, so the first one is named after the
+/// parameter count. LOCAL_GET, LOCAL_SET and LOCAL_TEE are disassembled with
+/// the slot index, which heatc takes in place of the name.
+pub fn disassemble_locals(function: &FunctionEntry) -> Vec<String> {
+    function.locals.iter()
+        .enumerate()
+        .map(|(index, h_type)| format!(".local l{} {:?}", function.param_count as usize + index, h_type))
+        .collect()
+}
+
+/// Disassemble the declaration starting a function
+pub fn disassemble_function(function: &FunctionEntry) -> String {
+    format!(".func {} {}", function.name, function.param_count)
+}
+
+/// Disassemble every function of a module in the order of the function table
+///
+/// Each function gets its own constants and locals, like heatc scopes them
+/// to the `.func` they are declared in.
+///
+/// ## Examples
+/// ```
+/// use lib_heat_spec::module::{FunctionEntry, Module};
+///
+/// let function = FunctionEntry {
+///     name: "main".to_string(),
+///     param_count: 0,
+///     code_start: 0,
+///     code_length: 1,
+///     constant_start: 0,
+///     constant_length: 0,
+///     locals: Vec::new(),
+/// };
+/// let module = Module { code: vec![0u8; 32], functions: vec![function], ..Default::default() };
+///
+/// let listing = heat_dis::disassemble_module(&module);
+/// assert_eq!(listing[0].declarations, vec![".func main 0".to_string()]);
+/// assert_eq!(listing[0].lines[0].text, "NONE");
+/// ```
+pub fn disassemble_module(module: &Module) -> Vec<FunctionListing> {
+    module.functions.iter()
+        .map(|function| {
+            let mut declarations = vec![disassemble_function(function)];
+            declarations.extend(disassemble_constants(module.function_constants(function)));
+            declarations.extend(disassemble_locals(function));
+
+            let start = function.code_start as usize * SIZE as usize;
+            let mut lines = disassemble(module.function_code(function));
+            for line in &mut lines {
+                line.offset += start;
+            }
+            FunctionListing { declarations, lines }
+        })
+        .collect()
+}
+
+/// Check that heatc assembles the disassembly of a module back into the same bytes
+///
+/// heatc lays functions out back to back in the order they are declared and
+/// merges equal constants of a function, so the function table has to cover
+/// the code and constants sections in order, and names have to be valid
+/// HeatASM. Returns why the module does not round trip otherwise.
+pub fn check_round_trip(module: &Module) -> Result<(), String> {
+    if module.functions.is_empty() {
+        return Err("the module has no functions".to_string());
+    }
+
+    let mut names = HashSet::new();
+    let (mut code, mut constants) = (0u64, 0u32);
+    for function in &module.functions {
+        if !is_label(&function.name) {
+            return Err(format!("function name `{}` is not valid HeatASM", function.name));
+        }
+        if !names.insert(function.name.as_str()) {
+            return Err(format!("function `{}` is defined twice", function.name));
+        }
+        if function.code_start != code || function.constant_start != constants {
+            return Err(format!("function `{}` does not directly follow the function before it", function.name));
+        }
+        let pool = module.function_constants(function);
+        if pool.iter().enumerate().any(|(index, constant)| pool[..index].contains(constant)) {
+            return Err(format!("function `{}` has equal constants", function.name));
+        }
+        code += function.code_length;
+        constants += function.constant_length;
+    }
+
+    if code * SIZE as u64 != module.code.len() as u64 {
+        return Err("code follows the last function".to_string());
+    }
+    if constants as usize != module.constants.len() {
+        return Err("constants follow the last function".to_string());
+    }
+    Ok(())
+}
+
+fn format_instruction(mnemonic: &str, instruction: &Instruction) -> String {
+    let args = [instruction.arg1, instruction.arg2, instruction.arg3];
+    let used = args.iter().rposition(|arg| *arg != 0).map_or(0, |last| last + 1);
+
+    let mut text = mnemonic.to_string();
+    for (index, arg) in args[..used].iter().enumerate() {
+        let type_arg = u8::try_from(*arg).ok().and_then(h_type::from_id).filter(|_| opcode::takes_type(instruction.opcode));
+        match (opcode::immediate_type(instruction.opcode), type_arg) {
+            (_, Some(h_type)) if index == 0 => text.push_str(&format!(" {:?}", h_type)),
+            (Some(h_type), _) if index == 0 => text.push_str(&format!(" {}", format_immediate(h_type, *arg))),
+            _ => text.push_str(&format!(" {}", arg)),
+        }
+    }
+    text
+}
+
+/// Format an immediate so heatc assembles it to the same bits
+///
+/// Floats use the shortest literal that parses back exactly, NaNs keep their
+/// payload by being printed as bit patterns.
+fn format_immediate(h_type: HType, arg: u64) -> String {
+    match h_type {
+        HType::F32 if f32::from_bits(arg as u32).is_nan() => format!("{:#x}", arg),
+        HType::F32 => format!("{:?}", f32::from_bits(arg as u32)),
+        HType::F64 if f64::from_bits(arg).is_nan() => format!("{:#x}", arg),
+        HType::F64 => format!("{:?}", f64::from_bits(arg)),
+        _ => immediate_value(h_type, arg).to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lib_heat_spec::module::Module;
+    use libvirt::instruction::Instruction;
+    use crate::{check_round_trip, disassemble, disassemble_constants, disassemble_locals, disassemble_module};
+
+    #[test]
+    /// Disassembles heatc output and assembles it again
+    fn disassemble_round_trip() {
+        let source = "NEW_U8\nLOAD_U8 255\nJMP_IF_TRUE 0 0 3\nCALL 1\nADD_U64\nLOAD_I8 -128\nLOAD_I64 -1\nLOAD_F32 -0.0\nLOAD_F64 1e-300\nLOAD_F64 -inf\nLOAD_F32 0x7fc00001\nCAST_WRAP U16\nCAST_CHECKED F64\nRET 1";
+        let module = heatc::compiler::compile(source).unwrap();
+
+        let lines = disassemble(&module.code);
+        assert!(lines.iter().all(|line| line.known));
+        assert_eq!(lines[2].offset, 64);
+
+        let text: Vec<String> = lines.into_iter().map(|line| line.text).collect();
+        assert_eq!(text.join("\n"), source);
+
+        let reassembled = heatc::compiler::compile(&text.join("\n")).unwrap();
+        assert_eq!(reassembled.to_bytes().unwrap(), module.to_bytes().unwrap());
+    }
+
+    #[test]
+    /// Disassembles the constant pool of heatc output and assembles it again
+    fn disassemble_constants_round_trip() {
+        let source = ".const big U64 18446744073709551615\n.const half F32 0.5\n.const nan F64 0x7ff8000000000001\n.const yes Bool true\n.const min I64 -9223372036854775808\nLOAD_CONST half";
+        let module = heatc::compiler::compile(source).unwrap();
+
+        let mut text = disassemble_constants(&module.constants);
+        assert_eq!(text, vec![
+            ".const c0 U64 18446744073709551615",
+            ".const c1 F32 0.5",
+            ".const c2 F64 0x7ff8000000000001",
+            ".const c3 Bool 1",
+            ".const c4 I64 -9223372036854775808",
+        ]);
+        text.extend(disassemble(&module.code).into_iter().map(|line| line.text));
+        assert_eq!(text.last().unwrap(), "LOAD_CONST 1");
+
+        let reassembled = heatc::compiler::compile(&text.join("\n")).unwrap();
+        assert_eq!(reassembled.to_bytes().unwrap(), module.to_bytes().unwrap());
+    }
+
+    #[test]
+    fn disassemble_locals_round_trip() {
+        let source = ".local count U32\n.local done Bool\nLOCAL_GET done\nLOCAL_SET count";
+        let mut module = heatc::compiler::compile(source).unwrap();
+
+        let mut text = disassemble_locals(&module.functions[0]);
+        assert_eq!(text, vec![".local l0 U32", ".local l1 Bool"]);
+        text.extend(disassemble(&module.code).into_iter().map(|line| line.text));
+        assert_eq!(text[2..], ["LOCAL_GET 1", "LOCAL_SET"]);
+
+        let reassembled = heatc::compiler::compile(&text.join("\n")).unwrap();
+        assert_eq!(reassembled.to_bytes().unwrap(), module.to_bytes().unwrap());
+
+        // declared locals are named after their slot, which follows the parameters
+        module.functions[0].param_count = 2;
+        assert_eq!(disassemble_locals(&module.functions[0]), vec![".local l2 U32", ".local l3 Bool"]);
+    }
+
+    #[test]
+    /// Disassembles heatc output with several functions and assembles it again
+    fn disassemble_module_round_trip() {
+        let source = "
+            .const one U8 1
+            CALL add 0 0
+            RET 1
+        .func add 2
+            .const one U8 1
+            .const two U16 2
+            .local sum U16
+            LOCAL_GET sum
+            LOAD_CONST two
+            CALL 2
+        .func empty 0
+        .func last 1
+            .local flag Bool
+            LOCAL_TEE flag
+        ";
+        let module = heatc::compiler::compile(source).unwrap();
+        assert!(check_round_trip(&module).is_ok());
+
+        let listing = disassemble_module(&module);
+        assert_eq!(listing[1].declarations, vec![".func add 2", ".const c0 U8 1", ".const c1 U16 2", ".local l2 U16"]);
+        assert_eq!(listing[1].lines[0].offset, 64, "offsets are relative to the code section");
+        assert!(listing[2].lines.is_empty());
+
+        let text: Vec<String> = listing.into_iter()
+            .flat_map(|function| function.declarations.into_iter().chain(function.lines.into_iter().map(|line| line.text)))
+            .collect();
+        assert_eq!(text[..4], [".func main 0", ".const c0 U8 1", "CALL 1", "RET 1"]);
+
+        let reassembled = heatc::compiler::compile(&text.join("\n")).unwrap();
+        assert_eq!(reassembled.to_bytes().unwrap(), module.to_bytes().unwrap());
+    }
+
+    #[test]
+    fn check_round_trip_layout() {
+        let module = heatc::compiler::compile("NEW_U8\n.func f 0\n.const a U8 1\nLOAD_CONST a").unwrap();
+        assert!(check_round_trip(&module).is_ok());
+
+        let mut swapped = module.clone();
+        swapped.functions.swap(0, 1);
+        assert_eq!(check_round_trip(&swapped), Err("function `f` does not directly follow the function before it".to_string()));
+
+        let mut shared = module.clone();
+        shared.functions[1].code_start = 0;
+        assert!(check_round_trip(&shared).is_err(), "overlapping code");
+
+        let mut dropped = module.clone();
+        dropped.functions.pop();
+        assert_eq!(check_round_trip(&dropped), Err("code follows the last function".to_string()));
+        dropped.code.clear();
+        dropped.functions[0].code_length = 0;
+        assert_eq!(check_round_trip(&dropped), Err("constants follow the last function".to_string()));
+
+        let mut renamed = module.clone();
+        renamed.functions[1].name = "main".to_string();
+        assert_eq!(check_round_trip(&renamed), Err("function `main` is defined twice".to_string()));
+        renamed.functions[1].name = "not a name".to_string();
+        assert!(check_round_trip(&renamed).is_err());
+
+        let mut merged = module.clone();
+        merged.constants.push(merged.constants[0].clone());
+        merged.functions[1].constant_length = 2;
+        assert_eq!(check_round_trip(&merged), Err("function `f` has equal constants".to_string()));
+
+        assert!(check_round_trip(&Module::default()).is_err());
+    }
+
+    #[test]
+    fn disassemble_unknown_opcode() {
+        let code = Instruction { opcode: 0xBEEF, arg1: 1, arg2: 0, arg3: 0 }.to_bytes();
+        let lines = disassemble(&code);
+        assert!(!lines[0].known);
+        assert_eq!(lines[0].text, "??? 0xbeef 1");
+    }
+}
@@ -0,0 +1,70 @@
+use std::fs::read;
+use std::path::Path;
+use clap::Parser;
+use lib_heat_spec::module::{self, Module};
+use heat_dis::{check_round_trip, disassemble_module};
+use libvirt::loader::read_archive;
+
+/// Heat disassembler prints the HeatASM source of heat modules and archives
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// Location of the module or build.har archive to disassemble
+    #[clap(short, long)]
+    file: String,
+
+    /// Print byte offsets as comments
+    #[clap(short, long)]
+    offsets: bool,
+}
+
+fn main() {
+    let args: Args = Args::parse();
+    let path = Path::new(&args.file);
+
+    let module = match read_module(path) {
+        Ok(module) => module,
+        Err(err) => {
+            eprintln!("{}: {}", path.display(), err);
+            std::process::exit(1);
+        }
+    };
+
+    let mut failed = false;
+    if let Err(reason) = check_round_trip(&module) {
+        eprintln!("{}: the disassembly does not assemble back into the same module: {}", path.display(), reason);
+        failed = true;
+    }
+
+    for function in disassemble_module(&module) {
+        for declaration in function.declarations {
+            println!("{}", declaration);
+        }
+
+        for line in function.lines {
+            if args.offsets {
+                println!("{:<32}; {:08x}", line.text, line.offset);
+            } else {
+                println!("{}", line.text);
+            }
+
+            if !line.known {
+                eprintln!("{}: unknown opcode {:#x} at byte {:#x}", path.display(), line.instruction.opcode, line.offset);
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        std::process::exit(2);
+    }
+}
+
+/// Read a module file, or the module in a heat archive
+fn read_module(path: &Path) -> Result<Module, Box<dyn std::error::Error>> {
+    let bytes = read(path)?;
+    if bytes.starts_with(&module::MAGIC) {
+        return Ok(Module::from_bytes(&bytes)?);
+    }
+    Ok(read_archive(bytes.as_slice())?)
+}
@@ -1,11 +1,9 @@
 
-use std::fs::{File, read};
+use std::fs::File;
 use clap::Parser;
-use uuid::Uuid;
 use libvirt::constraints::Constraints;
-use libvirt::frame::Frame;
-use libvirt::instruction::Instruction;
 use libvirt::interpreter::Interpreter;
+use libvirt::loader::{load_module, read_archive};
 
 /// The heat runtime is an program to execute heat bin package files
 #[derive(Parser, Debug)]
@@ -15,37 +13,58 @@ struct Args {
     #[clap(short, long)]
     file: String,
 
-    /// Maximum allocations per stack (bits) NOTE: set to 0 to turn off limit
+    /// Maximum allocations per stack (objects) NOTE: set to 0 to turn off limit
     #[clap(short, long, default_value_t = 0)]
     max_stack_allocation: u64,
+
+    /// Maximum memory used by all frames (bytes) NOTE: set to 0 to turn off limit
+    #[clap(long, default_value_t = 0)]
+    max_memory: u64,
+
+    /// Number of instructions the program may execute NOTE: set to 0 to turn off limit
+    #[clap(long, default_value_t = 0)]
+    fuel: u64,
 }
 
 fn main() {
     let args: Args = Args::parse();
 
-    let f = File::open(args.file);
-    if f.is_err() {
-        eprintln!("{}", f.unwrap_err());
-        return;
-    }
-
-    let f = f.unwrap();
-    let mut archive = tar::Archive::new(f);
-
-    let extract_location = std::env::temp_dir().join(Uuid::new_v4().to_simple().to_string());
-    archive.unpack(&extract_location).unwrap();
+    let f = match File::open(&args.file) {
+        Ok(f) => f,
+        Err(err) => {
+            eprintln!("{}", err);
+            return;
+        }
+    };
 
-    let bin_file = read(extract_location.join("/bin")).unwrap();
+    let mut i = Interpreter::new(Constraints::new(args.max_memory, args.max_stack_allocation));
+    i.functions = match read_archive(f).and_then(|module| load_module(&module)) {
+        Ok(functions) => functions,
+        Err(err) => {
+            eprintln!("{}: {}", args.file, err);
+            std::process::exit(1);
+        }
+    };
 
-    let bin_file_iter = bin_file.split(|num| num % lib_heat_spec::instruction::SIZE == 0);
-
-
-    let i = Interpreter::new(Constraints::new(0, args.max_stack_allocation));
-    let mut main_frame = Frame::default();
+    if args.fuel != 0 {
+        i.add_fuel(args.fuel);
+    }
 
-    for encoded_instruction in bin_file_iter {
-        main_frame.instructions.push(Instruction::from(encoded_instruction));
+    let main = match i.functions.iter().find(|function| function.name == "main") {
+        Some(main) => main,
+        None => {
+            eprintln!("module has no main function");
+            std::process::exit(1);
+        }
+    };
+    if main.param_count != 0 {
+        eprintln!("main function takes parameters");
+        std::process::exit(1);
     }
+    let mut main_frame = main.frame(Vec::new());
 
-    i.execute_frame(&mut main_frame);
+    if let Err(trap) = i.execute_frame(&mut main_frame) {
+        eprintln!("{}", trap);
+        std::process::exit(1);
+    }
 }
@@ -1,65 +1,897 @@
+use std::collections::HashMap;
+use std::fmt;
 use byteorder::{ByteOrder};
+use lib_heat_spec::frame::{MAX_LOCAL_SIZE, MAX_METHOD_PARAM};
+use lib_heat_spec::h_type::{self, immediate_range, is_float, HType};
+use lib_heat_spec::module::{Constant, FunctionEntry, Module};
 use lib_heat_spec::opcode;
-pub struct Instruction {
-    pub opcode: String,
-    pub arg1: String,
-    pub arg2: String,
-    pub arg3: String,
+
+/// An error in a HeatASM source file, lines and columns start at 1
+#[derive(Debug, PartialEq)]
+pub struct CompileError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
 }
 
-impl Instruction {
-    pub fn from(instruction_str: String) -> Result<Instruction, String> {
-        let mut parts: Vec<&str> = instruction_str.split(" ").collect();
-        if parts.len() > 4 {
-            return Result::Err("invalid number of parts in instruction".to_string());
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+/// Compile HeatASM source into a module
+///
+/// `.func name params` starts a new function taking `params` objects, which
+/// fill its first local slots. Everything in front of the first `.func` belongs
+/// to an implicit `main` function without parameters, which is left out when
+/// it is empty. Labels, constants declared with `.const` and locals declared
+/// with `.local` belong to the function they appear in. Labels are collected in
+/// a first pass, so instructions may reference labels that are defined further
+/// down, the same goes for constants, locals and the functions named by `CALL`.
+/// Constants become the constant pool of their function, and locals its local
+/// slots following the parameters in order of declaration.
+///
+/// ## Examples
+/// ```
+/// let module = heatc::compiler::compile("
+///     .const half F64 0.5
+///     .local sum F64
+///     start:  ; the first instruction
+///         LOAD_CONST half
+///         LOCAL_SET sum
+///         CALL double
+///         JMP start
+///
+///     .func double 1
+///         LOCAL_GET 0
+///         RET 1
+/// ").unwrap();
+/// assert_eq!(module.functions[0].code_length, 4);
+/// assert_eq!(module.functions[0].constant_length, 1);
+/// assert_eq!(module.functions[0].locals.len(), 1);
+/// assert_eq!(module.functions[1].name, "double");
+/// assert_eq!(module.functions[1].code_start, 4);
+/// ```
+pub fn compile(source: &str) -> Result<Module, CompileError> {
+    let mut functions = vec![FunctionSource::new("main".to_string(), 0)];
+    let mut function_names = HashMap::new();
+
+    for (index, text) in source.lines().enumerate() {
+        let line = Line::parse(index + 1, text)?;
+        if let Some(declaration) = line.function {
+            if function_names.is_empty() {
+                if functions[0].is_empty() {
+                    functions.clear();
+                } else {
+                    function_names.insert("main".to_string(), 0);
+                }
+            }
+            functions.push(FunctionSource::new(declaration.name.text.clone(), declaration.param_count));
+            if function_names.insert(declaration.name.text.clone(), functions.len() as u64 - 1).is_some() {
+                return Err(CompileError {
+                    line: index + 1,
+                    column: declaration.name.column,
+                    message: format!("function `{}` is already defined", declaration.name.text),
+                });
+            }
         }
 
-        for _ in 0..4-parts.len() {
-            parts.push("0");
+        let function = functions.last_mut().unwrap();
+        for label in line.labels {
+            if function.labels.insert(label.text.clone(), function.instructions.len() as u64).is_some() {
+                return Err(CompileError {
+                    line: index + 1,
+                    column: label.column,
+                    message: format!("label `{}` is already defined", label.text),
+                });
+            }
+        }
+        if let Some(declaration) = line.constant {
+            // declarations of the same value share a slot
+            let constants = &mut function.constants;
+            let slot = constants.iter().position(|constant| *constant == declaration.constant).unwrap_or_else(|| {
+                constants.push(declaration.constant);
+                constants.len() - 1
+            });
+            if function.constant_names.insert(declaration.name.text.clone(), slot as u64).is_some() {
+                return Err(CompileError {
+                    line: index + 1,
+                    column: declaration.name.column,
+                    message: format!("constant `{}` is already defined", declaration.name.text),
+                });
+            }
+        }
+        if let Some(declaration) = line.local {
+            let error = |message| CompileError { line: index + 1, column: declaration.name.column, message };
+            let slot = function.param_count as usize + function.locals.len();
+            if slot == MAX_LOCAL_SIZE as usize {
+                return Err(error(format!("more than {} locals", MAX_LOCAL_SIZE)));
+            }
+            if function.local_names.insert(declaration.name.text.clone(), slot as u64).is_some() {
+                return Err(error(format!("local `{}` is already defined", declaration.name.text)));
+            }
+            function.locals.push(declaration.h_type);
         }
+        function.instructions.extend(line.instruction);
+    }
+    if function_names.is_empty() {
+        function_names.insert("main".to_string(), 0);
+    }
 
-        return Result::Ok(Instruction{
-            opcode: parts[0].to_string(),
-            arg1: parts[1].to_string(),
-            arg2: parts[2].to_string(),
-            arg3: parts[3].to_string()
+    let mut module = Module {
+        metadata: vec![("compiler".to_string(), concat!("heatc ", env!("CARGO_PKG_VERSION")).to_string())],
+        ..Default::default()
+    };
+    for function in functions {
+        for instruction in &function.instructions {
+            let names = Names {
+                labels: &function.labels,
+                constants: &function.constant_names,
+                locals: &function.local_names,
+                functions: &function_names,
+            };
+            module.code.extend_from_slice(&instruction.to_byte_code(&names)?);
+        }
+        module.functions.push(FunctionEntry {
+            name: function.name,
+            param_count: function.param_count,
+            code_start: module.functions.iter().map(|entry| entry.code_length).sum(),
+            code_length: function.instructions.len() as u64,
+            constant_start: module.constants.len() as u32,
+            constant_length: function.constants.len() as u32,
+            locals: function.locals,
         });
+        module.constants.extend(function.constants);
     }
+    Ok(module)
+}
 
-    pub fn to_byte_code(self) -> Result<Vec<u8>, String> {
-        let opcode = string_to_opcode(self.opcode);
-        let arg1: u64 = match self.arg1.parse::<u64>() {
-            Ok(arg) => arg,
-            Err(err) => return Err(format!("Invalid argument 1: {}", err))
-        };
-        let arg2: u64 = match self.arg2.parse::<u64>() {
-            Ok(arg) => arg,
-            Err(err) => return Err(format!("Invalid argument 2: {}", err))
-        };
-        let arg3: u64 = match self.arg3.parse::<u64>() {
-            Ok(arg) => arg,
-            Err(err) => return Err(format!("Invalid argument 3: {}", err))
+/// A function collected from the source, everything but its name is only visible inside of it
+struct FunctionSource {
+    name: String,
+    param_count: u8,
+    labels: HashMap<String, u64>,
+    constant_names: HashMap<String, u64>,
+    constants: Vec<Constant>,
+    local_names: HashMap<String, u64>,
+    locals: Vec<HType>,
+    instructions: Vec<Instruction>,
+}
+
+impl FunctionSource {
+    fn new(name: String, param_count: u8) -> FunctionSource {
+        FunctionSource {
+            name,
+            param_count,
+            labels: HashMap::new(),
+            constant_names: HashMap::new(),
+            constants: Vec::new(),
+            local_names: HashMap::new(),
+            locals: Vec::new(),
+            instructions: Vec::new(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.labels.is_empty() && self.constant_names.is_empty() && self.local_names.is_empty() && self.instructions.is_empty()
+    }
+}
+
+/// The names an instruction's arguments can use, by the value they stand for
+pub struct Names<'a> {
+    /// instruction indices in the function
+    pub labels: &'a HashMap<String, u64>,
+    /// indices into the function's constant pool
+    pub constants: &'a HashMap<String, u64>,
+    /// local slots of the function
+    pub locals: &'a HashMap<String, u64>,
+    /// indices into the module's function table
+    pub functions: &'a HashMap<String, u64>,
+}
+
+/// Split a line into whitespace separated tokens, everything after `;` is a comment
+///
+/// Char literals are kept in one token, so `' '` and `';'` can be used as arguments.
+pub fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut current: Option<Token> = None;
+    let mut in_char = false;
+    let mut escaped = false;
+
+    for (index, c) in text.chars().enumerate() {
+        if in_char {
+            in_char = escaped || c != '\'';
+            escaped = !escaped && c == '\\';
+        } else if c == ';' {
+            break;
+        } else if c.is_whitespace() {
+            tokens.extend(current.take());
+            continue;
+        } else if c == '\'' {
+            in_char = true;
+        }
+        current.get_or_insert_with(|| Token { text: String::new(), column: index + 1 }).text.push(c);
+    }
+    tokens.extend(current);
+    tokens
+}
+
+/// Parse a literal argument
+///
+/// Numbers may be decimal, `0x` hexadecimal or `0b` binary, contain `_`
+/// separators and start with `-`. `true`, `false` and char literals like
+/// `'a'` or `'\n'` are numbers as well.
+pub fn parse_literal(text: &str) -> Result<i128, String> {
+    match text {
+        "true" => return Ok(1),
+        "false" => return Ok(0),
+        _ => {}
+    }
+
+    if let Some(inner) = text.strip_prefix('\'').and_then(|text| text.strip_suffix('\'')) {
+        let mut chars = inner.chars();
+        let c = match (chars.next(), chars.next(), chars.next()) {
+            (Some('\\'), Some(escape), None) => match escape {
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                '0' => '\0',
+                '\\' | '\'' => escape,
+                _ => return Err(format!("unknown escape `\\{}`", escape)),
+            },
+            (Some(c), None, None) if c != '\\' && c != '\'' => c,
+            _ => return Err(format!("invalid char literal `{}`", text)),
         };
+        return Ok(c as i128);
+    }
+
+    let (negative, unsigned) = match text.strip_prefix('-') {
+        Some(unsigned) => (true, unsigned),
+        None => (false, text),
+    };
+    let (radix, digits) = if let Some(digits) = unsigned.strip_prefix("0x") {
+        (16, digits)
+    } else if let Some(digits) = unsigned.strip_prefix("0b") {
+        (2, digits)
+    } else {
+        (10, unsigned)
+    };
+
+    let digits = digits.replace('_', "");
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return Err(format!("invalid literal `{}`", text));
+    }
+    let value = i128::from_str_radix(&digits, radix).map_err(|_| format!("literal `{}` is too large", text))?;
+    Ok(if negative { -value } else { value })
+}
+
+/// Parse a float literal like `1.5`, `-0.0`, `1e-3`, `inf` or `NaN` into the bits of an F32 or F64
+pub fn parse_float_literal(text: &str, h_type: HType) -> Result<i128, String> {
+    let digits = text.replace('_', "");
+    let bits = match h_type {
+        HType::F32 => digits.parse::<f32>().map(|value| value.to_bits() as i128),
+        _ => digits.parse::<f64>().map(|value| value.to_bits() as i128),
+    };
+    bits.map_err(|_| format!("invalid float literal `{}`", text))
+}
+
+/// Parse the value of an immediate or constant holding an `HType`, floats become their bits
+fn parse_immediate(text: &str, h_type: HType) -> Result<i128, String> {
+    if is_float(h_type) && !is_bit_pattern(text) {
+        parse_float_literal(text, h_type)
+    } else {
+        parse_literal(text)
+    }
+}
+
+/// Check if a literal is written in hexadecimal or binary, float immediates take those as bits
+fn is_bit_pattern(text: &str) -> bool {
+    text.starts_with("0x") || text.starts_with("0b")
+}
+
+/// Check if text can be used as a label name, `true` and `false` are literals
+///
+/// Constants, locals and functions are named like labels.
+pub fn is_label(text: &str) -> bool {
+    let mut chars = text.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && text != "true" && text != "false"
+}
+
+/// A part of an instruction and the column it starts at
+pub struct Token {
+    pub text: String,
+    pub column: usize,
+}
+
+/// A line of HeatASM source, labels are defined as `name:` in front of the instruction
+pub struct Line {
+    pub labels: Vec<Token>,
+    pub instruction: Option<Instruction>,
+    pub constant: Option<ConstantDeclaration>,
+    pub local: Option<LocalDeclaration>,
+    pub function: Option<FunctionDeclaration>,
+}
+
+impl Line {
+    pub fn parse(line: usize, text: &str) -> Result<Line, CompileError> {
+        let mut tokens = tokenize(text);
+
+        let label_count = tokens.iter().take_while(|token| token.text.ends_with(':')).count();
+        let mut labels: Vec<Token> = tokens.drain(..label_count).collect();
+        for label in &mut labels {
+            label.text.pop();
+            if !is_label(&label.text) {
+                return Err(CompileError {
+                    line,
+                    column: label.column,
+                    message: format!("invalid label name `{}`", label.text),
+                });
+            }
+        }
+
+        let mut constant = None;
+        let mut local = None;
+        let mut function = None;
+        let mut instruction = None;
+        match tokens.first() {
+            None => {}
+            Some(directive) if directive.text == ".const" => constant = Some(ConstantDeclaration::new(line, tokens)?),
+            Some(directive) if directive.text == ".local" => local = Some(LocalDeclaration::new(line, tokens)?),
+            Some(directive) if directive.text == ".func" => function = Some(FunctionDeclaration::new(line, tokens)?),
+            Some(directive) if directive.text.starts_with('.') => {
+                return Err(CompileError {
+                    line,
+                    column: directive.column,
+                    message: format!("unknown directive `{}`", directive.text),
+                });
+            }
+            Some(_) => instruction = Some(Instruction::new(line, tokens)?),
+        }
+        Ok(Line { labels, instruction, constant, local, function })
+    }
+}
+
+/// A `.const name TYPE value` declaration, loaded with `LOAD_CONST name`
+///
+/// Values are written like the immediates of `LOAD_[TYPE]`.
+pub struct ConstantDeclaration {
+    pub name: Token,
+    pub constant: Constant,
+}
+
+impl ConstantDeclaration {
+    fn new(line: usize, tokens: Vec<Token>) -> Result<ConstantDeclaration, CompileError> {
+        let error = |token: &Token, message: String| CompileError { line, column: token.column, message };
+        let [_, name, type_name, value]: [Token; 4] = tokens.try_into().map_err(|tokens: Vec<Token>| {
+            error(tokens.get(4).unwrap_or(&tokens[0]), "expected `.const name TYPE value`".to_string())
+        })?;
+
+        if !is_label(&name.text) {
+            return Err(error(&name, format!("invalid constant name `{}`", name.text)));
+        }
+        let h_type = h_type::from_name(&type_name.text)
+            .ok_or_else(|| error(&type_name, format!("`{}` is not a type", type_name.text)))?;
+        if h_type::is_handle(h_type) {
+            return Err(error(&type_name, format!("`{:?}` can not be a constant", h_type)));
+        }
+        let bits = parse_immediate(&value.text, h_type)
+            .map_err(|err| error(&value, format!("invalid constant: {}", err)))?;
+        if !immediate_range(h_type).contains(&bits) {
+            return Err(error(&value, format!("`{}` does not fit {:?}", value.text, h_type)));
+        }
+
+        Ok(ConstantDeclaration { name, constant: Constant::from_immediate(h_type, bits as u64) })
+    }
+}
+
+/// A `.local name TYPE` declaration of a zeroed local slot, used with `LOCAL_GET name`
+pub struct LocalDeclaration {
+    pub name: Token,
+    pub h_type: HType,
+}
+
+impl LocalDeclaration {
+    fn new(line: usize, tokens: Vec<Token>) -> Result<LocalDeclaration, CompileError> {
+        let error = |token: &Token, message: String| CompileError { line, column: token.column, message };
+        let [_, name, type_name]: [Token; 3] = tokens.try_into().map_err(|tokens: Vec<Token>| {
+            error(tokens.get(3).unwrap_or(&tokens[0]), "expected `.local name TYPE`".to_string())
+        })?;
+
+        if !is_label(&name.text) {
+            return Err(error(&name, format!("invalid local name `{}`", name.text)));
+        }
+        let h_type = h_type::from_name(&type_name.text)
+            .ok_or_else(|| error(&type_name, format!("`{}` is not a type", type_name.text)))?;
+
+        Ok(LocalDeclaration { name, h_type })
+    }
+}
+
+/// A `.func name params` declaration starting a function, called with `CALL name`
+pub struct FunctionDeclaration {
+    pub name: Token,
+    pub param_count: u8,
+}
+
+impl FunctionDeclaration {
+    fn new(line: usize, tokens: Vec<Token>) -> Result<FunctionDeclaration, CompileError> {
+        let error = |token: &Token, message: String| CompileError { line, column: token.column, message };
+        let [_, name, params]: [Token; 3] = tokens.try_into().map_err(|tokens: Vec<Token>| {
+            error(tokens.get(3).unwrap_or(&tokens[0]), "expected `.func name params`".to_string())
+        })?;
+
+        if !is_label(&name.text) {
+            return Err(error(&name, format!("invalid function name `{}`", name.text)));
+        }
+        let param_count = parse_literal(&params.text)
+            .ok()
+            .filter(|count| (0..=MAX_METHOD_PARAM as i128).contains(count))
+            .map(|count| count as u8)
+            .ok_or_else(|| error(&params, format!("`{}` is not a parameter count up to {}", params.text, MAX_METHOD_PARAM)))?;
+
+        Ok(FunctionDeclaration { name, param_count })
+    }
+}
+
+pub struct Instruction {
+    pub line: usize,
+    pub opcode: Token,
+    /// up to 3 arguments, missing arguments are 0
+    pub args: Vec<Token>,
+}
+
+impl Instruction {
+    pub fn parse(line: usize, instruction_str: &str) -> Result<Instruction, CompileError> {
+        Instruction::new(line, tokenize(instruction_str))
+    }
+
+    fn new(line: usize, mut tokens: Vec<Token>) -> Result<Instruction, CompileError> {
+        if tokens.is_empty() {
+            return Err(CompileError { line, column: 1, message: "expected an instruction".to_string() });
+        }
+        if tokens.len() > 4 {
+            return Err(CompileError {
+                line,
+                column: tokens[4].column,
+                message: "invalid number of parts in instruction".to_string(),
+            });
+        }
+
+        let opcode = tokens.remove(0);
+        Ok(Instruction { line, opcode, args: tokens })
+    }
+
+    /// Encode the instruction, arguments naming a label become the label's instruction index
+    ///
+    /// The immediate of opcodes with an `opcode::immediate_type` must fit that type,
+    /// any other argument must fit a u64. Float immediates are float literals, or
+    /// the IEEE-754 bits when written as `0x` or `0b` literals. Type arguments are
+    /// `HType` names like `U8` or their ids. LOAD_CONST takes a constant name or
+    /// an index into the constant pool, LOCAL_GET, LOCAL_SET and LOCAL_TEE a
+    /// local name or a slot index, and CALL a function name or an index into
+    /// the function table.
+    pub fn to_byte_code(&self, names: &Names) -> Result<Vec<u8>, CompileError> {
+        let opcode = opcode::from_mnemonic(&self.opcode.text)
+            .ok_or_else(|| self.error(&self.opcode, format!("unknown mnemonic `{}`", self.opcode.text)))?;
+
+        let mut src = vec![opcode, 0, 0, 0];
+        for (index, arg) in self.args.iter().enumerate() {
+            let h_type = opcode::immediate_type(opcode).filter(|_| index == 0);
+            let float_value = h_type.filter(|h_type| is_float(*h_type) && !is_bit_pattern(&arg.text));
+
+            let type_arg = index == 0 && opcode::takes_type(opcode);
+            let constant_arg = index == 0 && opcode == opcode::LOAD_CONST;
+            let local_arg = index == 0 && matches!(opcode, opcode::LOCAL_GET | opcode::LOCAL_SET | opcode::LOCAL_TEE);
+            let function_arg = index == 0 && opcode == opcode::CALL;
+
+            let value = if type_arg {
+                h_type::from_name(&arg.text)
+                    .map(|h_type| h_type as i128)
+                    .or_else(|| parse_literal(&arg.text).ok())
+                    .filter(|id| u8::try_from(*id).ok().and_then(h_type::from_id).is_some())
+                    .ok_or_else(|| self.error(arg, format!("argument {} `{}` is not a type", index + 1, arg.text)))?
+            } else if let Some(h_type) = float_value {
+                parse_float_literal(&arg.text, h_type)
+                    .map_err(|err| self.error(arg, format!("invalid argument {}: {}", index + 1, err)))?
+            } else if constant_arg && is_label(&arg.text) {
+                *names.constants.get(&arg.text)
+                    .ok_or_else(|| self.error(arg, format!("undefined constant `{}`", arg.text)))? as i128
+            } else if local_arg && is_label(&arg.text) {
+                *names.locals.get(&arg.text)
+                    .ok_or_else(|| self.error(arg, format!("undefined local `{}`", arg.text)))? as i128
+            } else if function_arg && is_label(&arg.text) {
+                *names.functions.get(&arg.text)
+                    .ok_or_else(|| self.error(arg, format!("undefined function `{}`", arg.text)))? as i128
+            } else if is_label(&arg.text) {
+                *names.labels.get(&arg.text)
+                    .ok_or_else(|| self.error(arg, format!("undefined label `{}`", arg.text)))? as i128
+            } else {
+                parse_literal(&arg.text)
+                    .map_err(|err| self.error(arg, format!("invalid argument {}: {}", index + 1, err)))?
+            };
+
+            let range = h_type.map_or(0..=u64::MAX as i128, immediate_range);
+            if !range.contains(&value) {
+                let expected = h_type.map_or("U64".to_string(), |h_type| format!("{:?}", h_type));
+                return Err(self.error(arg, format!("argument {} `{}` does not fit {}", index + 1, arg.text, expected)));
+            }
+            src[index + 1] = value as u64;
+        }
 
-        let src = vec!(opcode, arg1, arg2, arg3);
         let mut dest = [0u8;32];
         byteorder::BigEndian::write_u64_into(&src, &mut dest);
-        return Ok(dest.to_vec());
+        Ok(dest.to_vec())
+    }
+
+    fn error(&self, token: &Token, message: String) -> CompileError {
+        CompileError { line: self.line, column: token.column, message }
     }
 }
 
-fn string_to_opcode(opcode: String) -> u64 {
-    return match opcode.as_str() {
-        "NONE" => opcode::NONE,
-        "NEW_BOOL" => opcode::NEW_BOOL,
-        "NEW_U8"   => opcode::NEW_U8,
-        "NEW_U16"  => opcode::NEW_U16,
-        "NEW_U32"  => opcode::NEW_U32,
-        "NEW_U64"  => opcode::NEW_U64,
-        "EQUAL" => opcode::EQUAL,
-        "NOT" => opcode::NOT,
-        "AND" => opcode::AND,
-        "OR" => opcode::OR,
-        _ => opcode::ILLEGAL
+#[cfg(test)]
+mod tests {
+    use lib_heat_spec::h_type::HType;
+    use lib_heat_spec::module::Constant;
+    use lib_heat_spec::opcode;
+    use libvirt::constraints::Constraints;
+    use libvirt::instruction::Instruction;
+    use libvirt::interpreter::Interpreter;
+    use libvirt::loader::{load_instructions, load_module};
+    use libvirt::types::VirtualObject;
+    use crate::compiler::compile;
+
+    #[test]
+    /// Loads heatc output back with the libvirt loader
+    fn compile_load_round_trip() {
+        let module = compile("NEW_BOOL\nJMP_IF_TRUE 3 0 0\nCALL 0\nRET 1").unwrap();
+        let code = module.function_code(&module.functions[0]);
+
+        let instructions = load_instructions(code).unwrap();
+        assert_eq!(instructions, vec![
+            Instruction { opcode: opcode::NEW_BOOL, arg1: 0, arg2: 0, arg3: 0 },
+            Instruction { opcode: opcode::JMP_IF_TRUE, arg1: 3, arg2: 0, arg3: 0 },
+            Instruction { opcode: opcode::CALL, arg1: 0, arg2: 0, arg3: 0 },
+            Instruction { opcode: opcode::RET, arg1: 1, arg2: 0, arg3: 0 },
+        ]);
+
+        let encoded: Vec<u8> = instructions.iter().flat_map(Instruction::to_bytes).collect();
+        assert_eq!(encoded, code, "re-encoding loaded instructions gives heatc's bytes");
+
+        let functions = load_module(&lib_heat_spec::module::Module::from_bytes(&module.to_bytes().unwrap()).unwrap()).unwrap();
+        assert_eq!(functions[0].name, "main");
+        assert_eq!(*functions[0].instructions, instructions[..]);
+    }
+
+    #[test]
+    /// Checks every mnemonic in `opcode::OPCODES` assembles to its opcode
+    fn compile_all_mnemonics() {
+        for (mnemonic, value) in opcode::OPCODES {
+            let module = compile(mnemonic).unwrap();
+            let instructions = load_instructions(&module.code).unwrap();
+            assert_eq!(instructions[0].opcode, *value, "{}", mnemonic);
+        }
+    }
+
+    #[test]
+    fn compile_error_location() {
+        let err = compile("NEW_U8\nLOAD_U8 x").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 9));
+
+        let err = compile("NEW_U8\n\nLOAD_U9 1").unwrap_err();
+        assert_eq!((err.line, err.column), (3, 1));
+        assert_eq!(err.to_string(), "3:1: unknown mnemonic `LOAD_U9`");
+    }
+
+    #[test]
+    fn compile_comments_and_whitespace() {
+        let plain = compile("NEW_U8\nLOAD_U8 5").unwrap();
+        let messy = compile("; load five\n\n\tNEW_U8   ;new\r\n  LOAD_U8\t 5 ;\n   \n").unwrap();
+        assert_eq!(messy, plain);
+    }
+
+    #[test]
+    fn compile_labels() {
+        let module = compile("
+            JMP end         ; forward reference
+        start: loop:
+            NEW_U8
+            JMP_IF_TRUE loop
+        end: JMP start
+        ").unwrap();
+
+        let instructions = load_instructions(&module.code).unwrap();
+        let targets: Vec<u64> = instructions.iter().map(|i| i.arg1).collect();
+        assert_eq!(targets, vec![3, 0, 1, 1]);
+    }
+
+    #[test]
+    fn compile_label_errors() {
+        let err = compile("a: NEW_U8\n  a: NEW_U8").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 3));
+        assert_eq!(err.message, "label `a` is already defined");
+
+        let err = compile("JMP nowhere").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 5));
+        assert_eq!(err.message, "undefined label `nowhere`");
+
+        let err = compile("1x: NEW_U8").unwrap_err();
+        assert_eq!(err.message, "invalid label name `1x`");
+    }
+
+    #[test]
+    fn compile_literals() {
+        let module = compile("
+            LOAD_U64 0xFF_FF
+            LOAD_U64 0b1010
+            LOAD_U64 1_000_000
+            LOAD_BOOL true
+            LOAD_BOOL false
+            LOAD_U8 ';'
+            LOAD_U8 ' '
+            LOAD_U8 '\\n'
+            LOAD_U8 '\\''
+            LOAD_U32 'é'
+        ").unwrap();
+
+        let instructions = load_instructions(&module.code).unwrap();
+        let values: Vec<u64> = instructions.iter().map(|i| i.arg1).collect();
+        assert_eq!(values, vec![0xFFFF, 10, 1_000_000, 1, 0, b';' as u64, b' ' as u64, b'\n' as u64, b'\'' as u64, 'é' as u64]);
+    }
+
+    #[test]
+    fn compile_signed_literals() {
+        let module = compile("LOAD_I8 -128\nLOAD_I16 -0x10\nLOAD_I64 -1\nLOAD_I32 0x7FFF_FFFF").unwrap();
+        let instructions = load_instructions(&module.code).unwrap();
+        let values: Vec<u64> = instructions.iter().map(|i| i.arg1).collect();
+        assert_eq!(values, vec![-128i64 as u64, -16i64 as u64, u64::MAX, i32::MAX as u64]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn compile_float_literals() {
+        let module = compile("
+            LOAD_F32 1.5
+            LOAD_F32 -0.0
+            LOAD_F64 1_000.25e-2
+            LOAD_F64 -inf
+            LOAD_F32 NaN
+            LOAD_F64 2
+            LOAD_F32 0x7FC0_0001
+        ").unwrap();
+
+        let instructions = load_instructions(&module.code).unwrap();
+        let values: Vec<u64> = instructions.iter().map(|i| i.arg1).collect();
+        assert_eq!(values, vec![
+            1.5f32.to_bits() as u64,
+            (-0.0f32).to_bits() as u64,
+            10.0025f64.to_bits(),
+            f64::NEG_INFINITY.to_bits(),
+            f32::NAN.to_bits() as u64,
+            2.0f64.to_bits(),
+            0x7FC0_0001,
+        ]);
+
+        assert!(compile("LOAD_F32 1.5.0").is_err());
+        assert!(compile("LOAD_F32 true").is_err());
+        assert!(compile("LOAD_F32 0x1_0000_0000").is_err(), "F32 bit patterns have 32 bits");
+    }
+
+    #[test]
+    fn compile_type_args() {
+        let module = compile("CAST_WRAP F32\nCAST_CHECKED Bool\nCAST_CHECKED 3").unwrap();
+        let instructions = load_instructions(&module.code).unwrap();
+        let types: Vec<u64> = instructions.iter().map(|i| i.arg1).collect();
+        assert_eq!(types, vec![HType::F32 as u64, HType::Bool as u64, HType::U32 as u64]);
+
+        let err = compile("CAST_WRAP U128").unwrap_err();
+        assert_eq!(err.message, "argument 1 `U128` is not a type");
+        assert!(compile("CAST_WRAP 13").is_err());
+        assert!(compile("CAST_WRAP 257").is_err());
+    }
+
+    #[test]
+    fn compile_literal_errors() {
+        let err = compile("LOAD_U8 300").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 9));
+        assert_eq!(err.message, "argument 1 `300` does not fit U8");
+
+        assert!(compile("LOAD_U8 255").is_ok());
+        assert!(compile("LOAD_BOOL 2").is_err());
+        assert!(compile("LOAD_U8 -1").is_err());
+        assert!(compile("LOAD_I8 128").is_err());
+        assert!(compile("LOAD_I8 -129").is_err());
+        assert!(compile("LOAD_U8 '€'").is_err());
+        assert!(compile("JMP 0x1_0000_0000_0000_0000").is_err());
+        assert!(compile("LOAD_U8 0x").is_err());
+        assert!(compile("LOAD_U8 0b12").is_err());
+        assert!(compile("LOAD_U8 'ab'").is_err());
+        assert!(compile("true: NEW_U8").is_err());
+    }
+
+    #[test]
+    fn compile_constants() {
+        let module = compile("
+            .const big U64 0xFFFF_FFFF_FFFF_FFFF
+            .const pi F64 1.25
+            .const answer I32 -42
+            .const also_big U64 18446744073709551615    ; same value as `big`
+            LOAD_CONST pi
+            LOAD_CONST also_big
+            LOAD_CONST answer
+            LOAD_CONST big
+            LOAD_CONST later
+            .const later Bool true
+            LOAD_CONST 1
+        ").unwrap();
+
+        assert_eq!(module.constants, vec![
+            Constant::from_immediate(HType::U64, u64::MAX),
+            Constant { h_type: HType::F64, data: 1.25f64.to_be_bytes().to_vec() },
+            Constant { h_type: HType::I32, data: (-42i32).to_be_bytes().to_vec() },
+            Constant { h_type: HType::Bool, data: vec![1] },
+        ]);
+        assert_eq!(module.functions[0].constant_length, 4);
+
+        let instructions = load_instructions(&module.code).unwrap();
+        let indices: Vec<u64> = instructions.iter().map(|i| i.arg1).collect();
+        assert_eq!(indices, vec![1, 0, 2, 0, 3, 1]);
+    }
+
+    #[test]
+    fn compile_constant_errors() {
+        let err = compile(".const a U8 1
+.const a U8 2").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 8));
+        assert_eq!(err.message, "constant `a` is already defined");
+
+        let err = compile("LOAD_CONST nothing").unwrap_err();
+        assert_eq!(err.message, "undefined constant `nothing`");
+
+        let err = compile(".const a U8 256").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 13));
+        assert_eq!(err.message, "`256` does not fit U8");
+
+        assert_eq!(compile(".const a U128 1").unwrap_err().message, "`U128` is not a type");
+        assert_eq!(compile(".const 1a U8 1").unwrap_err().message, "invalid constant name `1a`");
+        assert_eq!(compile(".const a U8").unwrap_err().message, "expected `.const name TYPE value`");
+        assert_eq!(compile(".const a U8 1 2").unwrap_err().column, 15);
+        assert_eq!(compile(".constant a U8 1").unwrap_err().message, "unknown directive `.constant`");
+        assert!(compile(".const a F32 1.5.0").is_err());
+        assert_eq!(compile(".const a Ptr 1").unwrap_err().message, "`Ptr` can not be a constant");
+        assert_eq!(compile(".const a Ref 0").unwrap_err().message, "`Ref` can not be a constant");
+        assert!(compile("JMP a\n.const a U8 1").is_err(), "constants are not labels");
+    }
+
+    #[test]
+    fn compile_locals() {
+        let module = compile("
+            .local count U32
+            .local done Bool
+            LOCAL_GET done
+            LOCAL_SET count
+            LOCAL_TEE later
+            .local later F64
+            LOCAL_GET 1
+        ").unwrap();
+
+        assert_eq!(module.functions[0].locals, vec![HType::U32, HType::Bool, HType::F64]);
+        let instructions = load_instructions(&module.code).unwrap();
+        let slots: Vec<u64> = instructions.iter().map(|i| i.arg1).collect();
+        assert_eq!(slots, vec![1, 0, 2, 1]);
+    }
+
+    #[test]
+    fn compile_local_errors() {
+        let err = compile(".local a U8\n.local a U16").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 8));
+        assert_eq!(err.message, "local `a` is already defined");
+
+        assert_eq!(compile("LOCAL_GET nothing").unwrap_err().message, "undefined local `nothing`");
+        assert_eq!(compile(".local a U128").unwrap_err().message, "`U128` is not a type");
+        assert_eq!(compile(".local 1a U8").unwrap_err().message, "invalid local name `1a`");
+        assert_eq!(compile(".local a").unwrap_err().message, "expected `.local name TYPE`");
+        assert_eq!(compile(".local a U8 1").unwrap_err().column, 13);
+        assert!(compile(".local a U8\nJMP a").is_err(), "locals are not labels");
+        assert!(compile(".const a U8 1\n.local a U8").is_ok(), "locals and constants have their own names");
+
+        let source: String = (0..=u16::MAX as usize).map(|index| format!(".local l{} U8\n", index)).collect();
+        assert_eq!(compile(&source).unwrap_err().message, "more than 65535 locals");
+    }
+
+    #[test]
+    fn compile_functions() {
+        let module = compile("
+            .const one U8 1
+            CALL last           ; forward reference
+            CALL 0
+            JMP end
+        end: NEW_U8
+
+        .func pair 2
+            .const two U8 2
+            .const one U8 1     ; pools are not shared between functions
+            .local sum U16
+        end: LOCAL_GET sum
+            LOAD_CONST one
+            JMP end
+
+        .func last 0
+            CALL main
+            CALL pair
+        ").unwrap();
+
+        let names: Vec<&str> = module.functions.iter().map(|function| function.name.as_str()).collect();
+        assert_eq!(names, vec!["main", "pair", "last"]);
+        let layout: Vec<(u8, u64, u64, u32, u32)> = module.functions.iter()
+            .map(|f| (f.param_count, f.code_start, f.code_length, f.constant_start, f.constant_length))
+            .collect();
+        assert_eq!(layout, vec![(0, 0, 4, 0, 1), (2, 4, 3, 1, 2), (0, 7, 2, 3, 0)]);
+        assert_eq!(module.functions[1].locals, vec![HType::U16]);
+        assert_eq!(module.function_constants(&module.functions[1])[1], Constant::from_immediate(HType::U8, 1));
+
+        let instructions = load_instructions(&module.code).unwrap();
+        let args: Vec<u64> = instructions.iter().map(|i| i.arg1).collect();
+        assert_eq!(args, vec![2, 0, 3, 0, 2, 1, 0, 0, 1], "labels and locals belong to their function");
+
+        let module = compile(".func only 0\nNEW_U8").unwrap();
+        assert_eq!(module.functions.len(), 1, "an empty implicit main is left out");
+        assert_eq!(module.functions[0].name, "only");
+        assert_eq!(compile("").unwrap().functions[0].name, "main");
+    }
+
+    #[test]
+    fn compile_function_errors() {
+        let err = compile(".func a 0\n.func a 1").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 7));
+        assert_eq!(err.message, "function `a` is already defined");
+
+        assert_eq!(compile("NEW_U8\n.func main 0").unwrap_err().message, "function `main` is already defined");
+        assert!(compile(".func main 0\nNEW_U8").is_ok(), "main can be declared explicitly");
+        assert_eq!(compile("CALL nothing").unwrap_err().message, "undefined function `nothing`");
+        assert_eq!(compile(".func a 256").unwrap_err().message, "`256` is not a parameter count up to 255");
+        assert_eq!(compile(".func 1a 0").unwrap_err().message, "invalid function name `1a`");
+        assert_eq!(compile(".func a").unwrap_err().message, "expected `.func name params`");
+        assert!(compile("a: NEW_U8\n.func f 0\nJMP a").is_err(), "labels do not cross functions");
+        assert!(compile(".local a U8\n.func f 0\nLOCAL_GET a").is_err(), "locals do not cross functions");
+        assert!(compile(".func f 0\nJMP f").is_err(), "functions are not labels");
+
+        let source: String = (0..u16::MAX as usize).map(|index| format!(".local l{} U8\n", index)).collect();
+        assert_eq!(compile(&format!(".func f 1\n{}", source)).unwrap_err().message, "more than 65535 locals");
+    }
+
+    #[test]
+    /// Assembles a program calling a second function and runs it
+    fn compile_run_functions() {
+        let module = compile("
+            NEW_U32
+            LOAD_U32 20
+            NEW_U32
+            LOAD_U32 19
+            CALL add_bonus
+            STORE
+            RET 1
+
+        .func add_bonus 2
+            .const bonus U32 3
+            .local sum U32
+            LOCAL_GET 0
+            LOCAL_GET 1
+            ADD_U32
+            STORE
+            LOCAL_SET sum
+            LOCAL_GET sum
+            LOAD_CONST bonus
+            ADD_U32
+            STORE
+            RET 1
+        ").unwrap();
+
+        let mut interpreter = Interpreter::new(Constraints::new_none());
+        interpreter.functions = load_module(&lib_heat_spec::module::Module::from_bytes(&module.to_bytes().unwrap()).unwrap()).unwrap();
+        let mut frame = interpreter.functions[0].frame(Vec::new());
+        interpreter.execute_frame(&mut frame).unwrap();
+        assert_eq!(frame.operand_stack, vec![VirtualObject::from(42u32)]);
+    }
+}
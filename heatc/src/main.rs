@@ -1,10 +1,8 @@
-mod compiler;
-
 use std::fs::{File, read_to_string};
 use std::io::Write;
 use std::path::Path;
 use clap::Parser;
-use crate::compiler::Instruction;
+use heatc::compiler::compile;
 
 /// The heat compiler is an program to compile HeatASM files to Heat byte code
 #[derive(Parser, Debug)]
@@ -25,29 +23,25 @@ fn main() {
 
     for source in args.sources {
         let source = Path::new(&source);
-        let contents = read_to_string(&source).unwrap();
-        let lines: Vec<&str> = contents.split("\n").collect();
+        let contents = read_to_string(source).unwrap();
+
+        let module = match compile(&contents) {
+            Ok(module) => module,
+            Err(err) => {
+                eprintln!("{}:{}", source.display(), err);
+                std::process::exit(1);
+            }
+        };
+
+        let bytes = match module.to_bytes() {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                eprintln!("{}: {}", source.display(), err);
+                std::process::exit(1);
+            }
+        };
 
         let mut file = File::create(build_location.join(source.file_stem().unwrap())).unwrap();
-
-        for line_index in 0..lines.len() {
-            let instruction = Instruction::from(lines[line_index].to_string());
-            let instruction = match instruction{
-                Ok(i) => i,
-                Err(err) => {
-                    panic!("{}:{}:0 {}", &source.display(), line_index, err);
-                },
-            };
-
-            let byte_code = instruction.to_byte_code();
-            let byte_code = match byte_code {
-                Ok(b) => b,
-                Err(err) => {
-                    panic!("{}:{}:0 {}", &source.display(), line_index, err);
-                },
-            };
-
-            file.write(&byte_code).unwrap();
-        }
+        file.write_all(&bytes).unwrap();
     }
 }
\ No newline at end of file
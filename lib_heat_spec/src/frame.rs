@@ -1,4 +1,6 @@
 pub const MAX_LOCAL_SIZE: u16 = u16::MAX;
 pub const MAX_STACK_SIZE: u16 = u16::MAX;
 pub const MAX_METHOD_PARAM: u8 = u8::MAX;
+/// calls a frame may have in progress, nested CALLs past it trap
+pub const MAX_CALL_DEPTH: u16 = 1024;
 pub const MAX_CHAR_NO_METHOD_NAME: u16 = u16::MAX;
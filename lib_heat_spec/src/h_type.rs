@@ -1,3 +1,5 @@
+use std::ops::RangeInclusive;
+
 #[repr(usize)]
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum HType {
@@ -6,6 +8,16 @@ pub enum HType {
     U16,
     U32,
     U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+    /// typed pointer into the heap, the address followed by the id of the type it points to
+    Ptr,
+    /// reference to a garbage collected object, the object's id
+    Ref,
 }
 
 /// size of `hType::Bool` in bytes
@@ -23,13 +35,131 @@ pub const U32_SIZE: usize = 4;
 /// size of `hType::U64` in bytes
 pub const U64_SIZE: usize = 8;
 
+/// size of `hType::I8` in bytes
+pub const I8_SIZE: usize = 1;
+
+/// size of `hType::I16` in bytes
+pub const I16_SIZE: usize = 2;
+
+/// size of `hType::I32` in bytes
+pub const I32_SIZE: usize = 4;
+
+/// size of `hType::I64` in bytes
+pub const I64_SIZE: usize = 8;
+
+/// size of `hType::F32` in bytes
+pub const F32_SIZE: usize = 4;
+
+/// size of `hType::F64` in bytes
+pub const F64_SIZE: usize = 8;
+
+/// size of `hType::Ptr` in bytes
+pub const PTR_SIZE: usize = 9;
+
+/// size of `hType::Ref` in bytes
+pub const REF_SIZE: usize = 8;
+
+
+/// Get the `HType` encoded as `h_type as u8` in Heat modules
+pub fn from_id(id: u8) -> Option<HType> {
+    match id {
+        0 => Some(HType::Bool),
+        1 => Some(HType::U8),
+        2 => Some(HType::U16),
+        3 => Some(HType::U32),
+        4 => Some(HType::U64),
+        5 => Some(HType::I8),
+        6 => Some(HType::I16),
+        7 => Some(HType::I32),
+        8 => Some(HType::I64),
+        9 => Some(HType::F32),
+        10 => Some(HType::F64),
+        11 => Some(HType::Ptr),
+        12 => Some(HType::Ref),
+        _ => None,
+    }
+}
+
+/// Get the `HType` with a HeatASM name, which is the name of its variant
+///
+/// ## Examples
+/// ```
+/// use lib_heat_spec::h_type::{from_name, HType};
+///
+/// assert_eq!(from_name("I16"), Some(HType::I16));
+/// assert_eq!(from_name("i16"), None);
+/// ```
+pub fn from_name(name: &str) -> Option<HType> {
+    (0..=u8::MAX).map_while(from_id).find(|h_type| format!("{:?}", h_type) == name)
+}
+
+/// Get the smallest and largest value an immediate argument of an `HType` holds
+///
+/// Integer immediates hold their value, bool holds 0 and 1. Float immediates
+/// hold the IEEE-754 bit pattern, so F32 immediates are limited to 32 bits.
+///
+/// ## Examples
+/// ```
+/// use lib_heat_spec::h_type::{immediate_range, HType};
+///
+/// assert!(immediate_range(HType::U8).contains(&255));
+/// assert!(!immediate_range(HType::U8).contains(&256));
+/// assert!(immediate_range(HType::F32).contains(&(f32::NAN.to_bits() as i128)));
+/// ```
+pub fn immediate_range(h_type: HType) -> RangeInclusive<i128> {
+    match h_type {
+        HType::Bool => 0..=1,
+        HType::U8 => 0..=u8::MAX as i128,
+        HType::U16 => 0..=u16::MAX as i128,
+        HType::U32 | HType::F32 => 0..=u32::MAX as i128,
+        HType::U64 | HType::F64 | HType::Ptr | HType::Ref => 0..=u64::MAX as i128,
+        HType::I8 => i8::MIN as i128..=i8::MAX as i128,
+        HType::I16 => i16::MIN as i128..=i16::MAX as i128,
+        HType::I32 => i32::MIN as i128..=i32::MAX as i128,
+        HType::I64 => i64::MIN as i128..=i64::MAX as i128,
+    }
+}
+
+/// Check if an `HType` is a two's complement signed integer
+pub fn is_signed(h_type: HType) -> bool {
+    matches!(h_type, HType::I8 | HType::I16 | HType::I32 | HType::I64)
+}
+
+/// Check if an `HType` is an IEEE-754 float
+pub fn is_float(h_type: HType) -> bool {
+    matches!(h_type, HType::F32 | HType::F64)
+}
+
+/// Check if an `HType` refers to memory, `Ptr` and `Ref` objects can not be made from numbers
+pub fn is_handle(h_type: HType) -> bool {
+    matches!(h_type, HType::Ptr | HType::Ref)
+}
+
+/// Get the value of an immediate argument holding an `HType`
+///
+/// Signed immediates are stored sign extended to 64 bits, so `-1i8` is `u64::MAX`.
+pub fn immediate_value(h_type: HType, arg: u64) -> i128 {
+    if is_signed(h_type) {
+        arg as i64 as i128
+    } else {
+        arg as i128
+    }
+}
 
 pub fn get_size(h_type: HType) -> usize {
-    return match h_type {
+    match h_type {
         HType::Bool => BOOL_SIZE,
         HType::U8 => U8_SIZE,
         HType::U16 => U16_SIZE,
         HType::U32 => U32_SIZE,
         HType::U64 => U64_SIZE,
+        HType::I8 => I8_SIZE,
+        HType::I16 => I16_SIZE,
+        HType::I32 => I32_SIZE,
+        HType::I64 => I64_SIZE,
+        HType::F32 => F32_SIZE,
+        HType::F64 => F64_SIZE,
+        HType::Ptr => PTR_SIZE,
+        HType::Ref => REF_SIZE,
     }
 }
\ No newline at end of file
@@ -2,3 +2,4 @@ pub mod h_type;
 pub mod opcode;
 pub mod instruction;
 pub mod frame;
+pub mod module;
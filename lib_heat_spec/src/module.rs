@@ -0,0 +1,520 @@
+//! Heat module binary format
+//!
+//! A module starts with a header followed by sections, all integers are big endian
+//! like the instructions they contain.
+//!
+//! | field         | size             |
+//! |---------------|------------------|
+//! | magic         | 4 (`MAGIC`)      |
+//! | version       | 2                |
+//! | section count | 2                |
+//! | sections      | section count    |
+//!
+//! Every section is a one byte section id, an 8 byte payload length and the payload.
+//! Each section may appear at most once, missing sections are empty.
+
+use std::fmt;
+use crate::frame::MAX_LOCAL_SIZE;
+use crate::h_type::{self, HType};
+use crate::instruction;
+
+/// magic bytes every Heat module starts with
+pub const MAGIC: [u8; 4] = *b"HEAT";
+
+/// version of the module format written by this crate, the only version it reads
+pub const VERSION: u16 = 3;
+
+/// encoded instructions, `instruction::SIZE` bytes each
+pub const SECTION_CODE: u8 = 0x01;
+/// `u32` count followed by constants: `HType` id (`u8`) and the object's bytes
+pub const SECTION_CONSTANTS: u8 = 0x02;
+/// `u32` count followed by functions: name, param count (`u8`), first instruction (`u64`), instruction count (`u64`),
+/// first constant (`u32`), constant count (`u32`) and the `u16` count and `HType` ids (`u8`) of its locals
+pub const SECTION_FUNCTIONS: u8 = 0x03;
+/// `u32` count followed by key and value string pairs
+pub const SECTION_METADATA: u8 = 0x04;
+
+/// A typed constant stored in the module, pointers and references can not be constants
+#[derive(Clone, Debug, PartialEq)]
+pub struct Constant {
+    pub h_type: HType,
+    /// big endian bytes of the value, `h_type::get_size(h_type)` long
+    pub data: Vec<u8>,
+}
+
+impl Constant {
+    /// Create a constant from a value encoded like an immediate argument, see `h_type::immediate_value`
+    ///
+    /// Panics if `h_type` is `HType::Ptr` or `HType::Ref`.
+    ///
+    /// ## Examples
+    /// ```
+    /// use lib_heat_spec::h_type::HType;
+    /// use lib_heat_spec::module::Constant;
+    ///
+    /// let constant = Constant::from_immediate(HType::I16, -2i64 as u64);
+    /// assert_eq!(constant.data, vec![0xFF, 0xFE]);
+    /// assert_eq!(constant.immediate(), -2i64 as u64);
+    /// ```
+    pub fn from_immediate(h_type: HType, value: u64) -> Constant {
+        let size = h_type::get_size(h_type);
+        Constant { h_type, data: value.to_be_bytes()[8 - size..].to_vec() }
+    }
+
+    /// Get the value of the constant encoded like an immediate argument, signed values are sign extended
+    pub fn immediate(&self) -> u64 {
+        let value = self.data.iter().fold(0u64, |value, byte| value << 8 | *byte as u64);
+        let unused = 64 - 8 * self.data.len() as u32;
+        if h_type::is_signed(self.h_type) && unused < 64 {
+            ((value << unused) as i64 >> unused) as u64
+        } else {
+            value
+        }
+    }
+}
+
+/// An entry of the function table
+#[derive(Clone, Debug, PartialEq)]
+pub struct FunctionEntry {
+    pub name: String,
+    pub param_count: u8,
+    /// index of the function's first instruction in the code section
+    pub code_start: u64,
+    /// number of instructions in the function
+    pub code_length: u64,
+    /// index of the function's first constant in the constants section
+    pub constant_start: u32,
+    /// number of constants in the function's constant pool
+    pub constant_length: u32,
+    /// types of the local slots following the parameters, the slots of a
+    /// function are bound by `frame::MAX_LOCAL_SIZE`
+    pub locals: Vec<HType>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Module {
+    pub code: Vec<u8>,
+    pub constants: Vec<Constant>,
+    pub functions: Vec<FunctionEntry>,
+    pub metadata: Vec<(String, String)>,
+}
+
+/// An error found while reading a module
+#[derive(Clone, Debug, PartialEq)]
+pub enum ModuleError {
+    /// the file does not start with `MAGIC`
+    BadMagic,
+    /// the module was written with a different format version
+    UnsupportedVersion { found: u16, supported: u16 },
+    /// the file or section ended while reading the header or a section, `offset` is where the read started
+    Truncated { offset: usize },
+    /// bytes were left over after the last section
+    TrailingBytes { offset: usize },
+    UnknownSection { id: u8, offset: usize },
+    DuplicateSection { id: u8, offset: usize },
+    /// a section's contents are inconsistent
+    Malformed { section: u8, reason: String },
+}
+
+impl fmt::Display for ModuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModuleError::BadMagic => write!(f, "not a heat module"),
+            ModuleError::UnsupportedVersion { found, supported } => {
+                write!(f, "unsupported module version {} (supported: {})", found, supported)
+            }
+            ModuleError::Truncated { offset } => write!(f, "module truncated at byte {}", offset),
+            ModuleError::TrailingBytes { offset } => write!(f, "unexpected bytes after the last section at byte {}", offset),
+            ModuleError::UnknownSection { id, offset } => write!(f, "unknown section {:#x} at byte {}", id, offset),
+            ModuleError::DuplicateSection { id, offset } => write!(f, "duplicate section {:#x} at byte {}", id, offset),
+            ModuleError::Malformed { section, reason } => write!(f, "malformed section {:#x}: {}", section, reason),
+        }
+    }
+}
+
+impl std::error::Error for ModuleError {}
+
+impl Module {
+    pub fn find_function(&self, name: &str) -> Option<&FunctionEntry> {
+        self.functions.iter().find(|function| function.name == name)
+    }
+
+    /// Get the encoded instructions of a function in the module
+    ///
+    /// Panics if the function is outside of the code section, see `validate`.
+    pub fn function_code(&self, function: &FunctionEntry) -> &[u8] {
+        let size = instruction::SIZE as usize;
+        let start = function.code_start as usize * size;
+        &self.code[start..start + function.code_length as usize * size]
+    }
+
+    /// Get the constant pool of a function in the module
+    ///
+    /// Panics if the constants are outside of the constants section, see `validate`.
+    pub fn function_constants(&self, function: &FunctionEntry) -> &[Constant] {
+        let start = function.constant_start as usize;
+        &self.constants[start..start + function.constant_length as usize]
+    }
+
+    /// Encode the module
+    ///
+    /// Fails with `ModuleError::Malformed` when a count, name or list of locals
+    /// does not fit the field the format stores its length in.
+    ///
+    /// ## Examples
+    /// ```
+    /// use lib_heat_spec::module::Module;
+    ///
+    /// let module = Module { code: vec![0u8; 32], ..Default::default() };
+    /// let bytes = module.to_bytes().unwrap();
+    /// assert_eq!(Module::from_bytes(&bytes), Ok(module));
+    /// ```
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ModuleError> {
+        let mut constants = Vec::new();
+        put_u32(&mut constants, SECTION_CONSTANTS, self.constants.len())?;
+        for constant in &self.constants {
+            constants.push(constant.h_type as u8);
+            constants.extend_from_slice(&constant.data);
+        }
+
+        let mut functions = Vec::new();
+        put_u32(&mut functions, SECTION_FUNCTIONS, self.functions.len())?;
+        for function in &self.functions {
+            put_str(&mut functions, SECTION_FUNCTIONS, &function.name)?;
+            functions.push(function.param_count);
+            functions.extend_from_slice(&function.code_start.to_be_bytes());
+            functions.extend_from_slice(&function.code_length.to_be_bytes());
+            functions.extend_from_slice(&function.constant_start.to_be_bytes());
+            functions.extend_from_slice(&function.constant_length.to_be_bytes());
+            let locals = u16::try_from(function.locals.len())
+                .map_err(|_| malformed(SECTION_FUNCTIONS, &format!("function `{}` has more than {} locals", function.name, u16::MAX)))?;
+            functions.extend_from_slice(&locals.to_be_bytes());
+            functions.extend(function.locals.iter().map(|h_type| *h_type as u8));
+        }
+
+        let mut metadata = Vec::new();
+        put_u32(&mut metadata, SECTION_METADATA, self.metadata.len())?;
+        for (key, value) in &self.metadata {
+            put_str(&mut metadata, SECTION_METADATA, key)?;
+            put_str(&mut metadata, SECTION_METADATA, value)?;
+        }
+
+        let sections = [
+            (SECTION_CODE, &self.code),
+            (SECTION_CONSTANTS, &constants),
+            (SECTION_FUNCTIONS, &functions),
+            (SECTION_METADATA, &metadata),
+        ];
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&MAGIC);
+        bytes.extend_from_slice(&VERSION.to_be_bytes());
+        bytes.extend_from_slice(&(sections.len() as u16).to_be_bytes());
+        for (id, payload) in sections {
+            bytes.push(id);
+            bytes.extend_from_slice(&(payload.len() as u64).to_be_bytes());
+            bytes.extend_from_slice(payload);
+        }
+        Ok(bytes)
+    }
+
+    /// Decode and validate a module
+    pub fn from_bytes(bytes: &[u8]) -> Result<Module, ModuleError> {
+        let mut reader = Reader { bytes, offset: 0, end: bytes.len() };
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(ModuleError::BadMagic);
+        }
+        let version = reader.u16()?;
+        if version != VERSION {
+            return Err(ModuleError::UnsupportedVersion { found: version, supported: VERSION });
+        }
+
+        let mut module = Module::default();
+        let mut seen = Vec::new();
+        for _ in 0..reader.u16()? {
+            let offset = reader.offset;
+            let id = reader.u8()?;
+            if seen.contains(&id) {
+                return Err(ModuleError::DuplicateSection { id, offset });
+            }
+            seen.push(id);
+
+            let length = usize::try_from(reader.u64()?).unwrap_or(usize::MAX);
+            let start = reader.offset;
+            let payload = reader.take(length)?;
+            let mut section = Reader { bytes, offset: start, end: reader.offset };
+            match id {
+                SECTION_CODE => module.code = payload.to_vec(),
+                SECTION_CONSTANTS => module.constants = read_constants(&mut section)?,
+                SECTION_FUNCTIONS => module.functions = read_functions(&mut section)?,
+                SECTION_METADATA => module.metadata = read_metadata(&mut section)?,
+                _ => return Err(ModuleError::UnknownSection { id, offset }),
+            }
+            if id != SECTION_CODE && section.offset != section.end {
+                return Err(malformed(id, "unexpected bytes at the end of the section"));
+            }
+        }
+        if reader.offset != bytes.len() {
+            return Err(ModuleError::TrailingBytes { offset: reader.offset });
+        }
+
+        module.validate()?;
+        Ok(module)
+    }
+
+    /// Check the function table against the code and constants sections
+    ///
+    /// `from_bytes` validates the modules it reads, modules built by hand
+    /// have to be validated before `function_code` and `function_constants`
+    /// are called on them.
+    pub fn validate(&self) -> Result<(), ModuleError> {
+        let size = instruction::SIZE as usize;
+        if !self.code.len().is_multiple_of(size) {
+            return Err(malformed(SECTION_CODE, &format!("length {} is not a multiple of {}", self.code.len(), size)));
+        }
+
+        let instruction_count = (self.code.len() / size) as u64;
+        for function in &self.functions {
+            let end = function.code_start.checked_add(function.code_length);
+            if end.is_none_or(|end| end > instruction_count) {
+                return Err(malformed(SECTION_FUNCTIONS, &format!("function `{}` is outside of the code section", function.name)));
+            }
+            let end = function.constant_start as usize + function.constant_length as usize;
+            if end > self.constants.len() {
+                return Err(malformed(SECTION_FUNCTIONS, &format!("constants of function `{}` are outside of the constants section", function.name)));
+            }
+            if function.param_count as usize + function.locals.len() > MAX_LOCAL_SIZE as usize {
+                return Err(malformed(SECTION_FUNCTIONS, &format!("function `{}` has more than {} locals", function.name, MAX_LOCAL_SIZE)));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn malformed(section: u8, reason: &str) -> ModuleError {
+    ModuleError::Malformed { section, reason: reason.to_string() }
+}
+
+fn put_u32(bytes: &mut Vec<u8>, section: u8, count: usize) -> Result<(), ModuleError> {
+    let count = u32::try_from(count).map_err(|_| malformed(section, &format!("more than {} entries", u32::MAX)))?;
+    bytes.extend_from_slice(&count.to_be_bytes());
+    Ok(())
+}
+
+fn put_str(bytes: &mut Vec<u8>, section: u8, value: &str) -> Result<(), ModuleError> {
+    let len = u16::try_from(value.len()).map_err(|_| malformed(section, &format!("string is longer than {} bytes", u16::MAX)))?;
+    bytes.extend_from_slice(&len.to_be_bytes());
+    bytes.extend_from_slice(value.as_bytes());
+    Ok(())
+}
+
+fn read_constants(reader: &mut Reader) -> Result<Vec<Constant>, ModuleError> {
+    let count = reader.u32()?;
+    let mut constants = Vec::new();
+    for _ in 0..count {
+        let id = reader.u8()?;
+        let h_type = h_type::from_id(id)
+            .filter(|h_type| !h_type::is_handle(*h_type))
+            .ok_or_else(|| malformed(SECTION_CONSTANTS, &format!("unknown type id {}", id)))?;
+        let data = reader.take(h_type::get_size(h_type))?.to_vec();
+        constants.push(Constant { h_type, data });
+    }
+    Ok(constants)
+}
+
+fn read_functions(reader: &mut Reader) -> Result<Vec<FunctionEntry>, ModuleError> {
+    let count = reader.u32()?;
+    let mut functions = Vec::new();
+    for _ in 0..count {
+        functions.push(FunctionEntry {
+            name: reader.string(SECTION_FUNCTIONS)?,
+            param_count: reader.u8()?,
+            code_start: reader.u64()?,
+            code_length: reader.u64()?,
+            constant_start: reader.u32()?,
+            constant_length: reader.u32()?,
+            locals: read_locals(reader)?,
+        });
+    }
+    Ok(functions)
+}
+
+fn read_locals(reader: &mut Reader) -> Result<Vec<HType>, ModuleError> {
+    let count = reader.u16()?;
+    let mut locals = Vec::new();
+    for _ in 0..count {
+        let id = reader.u8()?;
+        locals.push(h_type::from_id(id).ok_or_else(|| malformed(SECTION_FUNCTIONS, &format!("unknown type id {}", id)))?);
+    }
+    Ok(locals)
+}
+
+fn read_metadata(reader: &mut Reader) -> Result<Vec<(String, String)>, ModuleError> {
+    let count = reader.u32()?;
+    let mut metadata = Vec::new();
+    for _ in 0..count {
+        metadata.push((reader.string(SECTION_METADATA)?, reader.string(SECTION_METADATA)?));
+    }
+    Ok(metadata)
+}
+
+/// Cursor over `bytes[offset..end]` that reports where it ran out of bytes
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    end: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ModuleError> {
+        let end = self.offset.checked_add(len).filter(|end| *end <= self.end);
+        let end = end.ok_or(ModuleError::Truncated { offset: self.offset })?;
+        let slice = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8, ModuleError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, ModuleError> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, ModuleError> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, ModuleError> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self, section: u8) -> Result<String, ModuleError> {
+        let len = self.u16()? as usize;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| malformed(section, "string is not valid utf-8"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::frame::MAX_LOCAL_SIZE;
+    use crate::h_type::HType;
+    use crate::module::{Constant, FunctionEntry, Module, ModuleError, VERSION};
+
+    fn module() -> Module {
+        Module {
+            code: vec![0u8; 64],
+            constants: vec![Constant { h_type: HType::U16, data: vec![0x12, 0x34] }],
+            functions: vec![FunctionEntry {
+                name: "main".to_string(),
+                param_count: 0,
+                code_start: 0,
+                code_length: 2,
+                constant_start: 0,
+                constant_length: 1,
+                locals: vec![HType::U8, HType::F64],
+            }],
+            metadata: vec![("compiler".to_string(), "heatc".to_string())],
+        }
+    }
+
+    #[test]
+    fn module_round_trip() {
+        let module = module();
+        assert_eq!(Module::from_bytes(&module.to_bytes().unwrap()).unwrap(), module);
+        assert_eq!(module.function_code(&module.functions[0]).len(), 64);
+        assert_eq!(module.function_constants(&module.functions[0]), &module.constants[..]);
+    }
+
+    #[test]
+    fn constant_immediates() {
+        let constant = Constant::from_immediate(HType::I8, i8::MIN as i64 as u64);
+        assert_eq!(constant.data, vec![0x80]);
+        assert_eq!(constant.immediate(), i8::MIN as i64 as u64);
+
+        let constant = Constant::from_immediate(HType::F32, 1.5f32.to_bits() as u64);
+        assert_eq!(constant.data, 1.5f32.to_be_bytes());
+        assert_eq!(constant.immediate(), 1.5f32.to_bits() as u64);
+
+        assert_eq!(Constant::from_immediate(HType::U64, u64::MAX).immediate(), u64::MAX);
+        assert_eq!(Constant::from_immediate(HType::I64, i64::MIN as u64).immediate(), i64::MIN as u64);
+        assert_eq!(Constant::from_immediate(HType::Bool, 1).data, vec![1]);
+    }
+
+    #[test]
+    fn module_header_errors() {
+        let bytes = module().to_bytes().unwrap();
+
+        assert_eq!(Module::from_bytes(b"HEAP\x00\x01\x00\x00"), Err(ModuleError::BadMagic));
+
+        let mut newer = bytes.clone();
+        newer[4..6].copy_from_slice(&(VERSION + 1).to_be_bytes());
+        assert_eq!(Module::from_bytes(&newer), Err(ModuleError::UnsupportedVersion { found: VERSION + 1, supported: VERSION }));
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(Module::from_bytes(&trailing), Err(ModuleError::TrailingBytes { offset: bytes.len() }));
+    }
+
+    #[test]
+    fn module_truncated() {
+        let bytes = module().to_bytes().unwrap();
+        for len in 0..bytes.len() {
+            let err = Module::from_bytes(&bytes[..len]).unwrap_err();
+            assert!(matches!(err, ModuleError::Truncated { .. }), "length {} gave {:?}", len, err);
+        }
+
+        // magic, version and section count, then the code section's id and payload length
+        assert_eq!(Module::from_bytes(&bytes[..5]), Err(ModuleError::Truncated { offset: 4 }));
+        assert_eq!(Module::from_bytes(&bytes[..20]), Err(ModuleError::Truncated { offset: 17 }), "the payload starts at byte 17");
+    }
+
+    #[test]
+    fn module_too_long() {
+        let mut module = module();
+        module.functions[0].name = "f".repeat(u16::MAX as usize + 1);
+        assert!(matches!(module.to_bytes(), Err(ModuleError::Malformed { .. })), "function name");
+        module.functions[0].name = "f".repeat(u16::MAX as usize);
+        assert_eq!(Module::from_bytes(&module.to_bytes().unwrap()).unwrap(), module);
+
+        let mut module = self::module();
+        module.metadata.push(("key".to_string(), "v".repeat(u16::MAX as usize + 1)));
+        assert!(matches!(module.to_bytes(), Err(ModuleError::Malformed { .. })), "metadata value");
+
+        let mut module = self::module();
+        module.functions[0].locals = vec![HType::Bool; u16::MAX as usize + 1];
+        assert!(matches!(module.to_bytes(), Err(ModuleError::Malformed { .. })), "locals");
+    }
+
+    #[test]
+    fn module_malformed() {
+        let mut module = module();
+        module.code.pop();
+        assert!(matches!(Module::from_bytes(&module.to_bytes().unwrap()), Err(ModuleError::Malformed { .. })));
+
+        let mut module = self::module();
+        module.functions[0].code_length = 3;
+        assert!(matches!(Module::from_bytes(&module.to_bytes().unwrap()), Err(ModuleError::Malformed { .. })));
+
+        let mut module = self::module();
+        module.functions[0].constant_start = 1;
+        assert!(matches!(Module::from_bytes(&module.to_bytes().unwrap()), Err(ModuleError::Malformed { .. })));
+
+        let mut module = self::module();
+        module.functions[0].param_count = 1;
+        module.functions[0].locals = vec![HType::Bool; MAX_LOCAL_SIZE as usize];
+        assert!(matches!(Module::from_bytes(&module.to_bytes().unwrap()), Err(ModuleError::Malformed { .. })));
+
+        let mut bytes = self::module().to_bytes().unwrap();
+        let id = bytes.iter().rposition(|byte| *byte == HType::F64 as u8).unwrap();
+        bytes[id] = 0xEE;
+        assert!(matches!(Module::from_bytes(&bytes), Err(ModuleError::Malformed { .. })), "unknown local type");
+
+        let mut module = self::module();
+        module.constants[0] = Constant { h_type: HType::Ptr, data: vec![0; 9] };
+        assert!(matches!(Module::from_bytes(&module.to_bytes().unwrap()), Err(ModuleError::Malformed { .. })), "pointer constant");
+    }
+}
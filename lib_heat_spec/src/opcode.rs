@@ -1,52 +1,576 @@
+use crate::h_type::HType;
 
-pub const NONE: u64 = 0x00; // None
+// Opcodes of an instruction family working on numbers share a 0x10 block, the
+// low nibble is the operand type: 0x0 U8, 0x1 U16, 0x2 U32, 0x3 U64, 0x4 I8,
+// 0x5 I16, 0x6 I32, 0x7 I64, 0x8 F32 and 0x9 F64. See `operand_type`.
+//
+// Integer ADD, SUB, MUL, DIV and PWR trap with `Overflow` when the result does not
+// fit the type, regardless of how the runtime was built. The `_WRAP` variants
+// wrap around in two's complement and the `_SAT` variants clamp to the type's
+// bounds instead, `_CHECKED` spells out the default. Variants live at 0x100
+// (WRAP), 0x200 (CHECKED) and 0x300 (SAT) above the family's block. Division
+// by zero traps in every variant, as it does for REM, MOD and DIVREM. The
+// remainder of MIN by -1 is 0. Families from 0x400 up have no variants.
+//
+// A frame has two stacks. Stack holds the values instructions work on: NEW,
+// LOAD_CONST and LOCAL_GET push onto it, LOAD_[TYPE] overwrite its front and
+// JMP_IF, CALL and RET pop from it. The operand stack holds results:
+// instructions on numbers and bools, comparisons and casts pop their operands
+// from the front of stack and push their result onto the operand stack, and
+// CALL leaves the callee's return values there. STORE moves the front of the
+// operand stack onto stack to use a result as an operand, STORE_OPERAND moves
+// it back. Every stack manipulation opcode has an `_OPERAND` twin working on
+// the operand stack. Reading past the back of either stack traps with
+// `StackUnderflow`.
+//
+// A function's local slots hold its parameters followed by the locals it
+// declares, which start out zeroed. LOCAL_SET and LOCAL_TEE keep the type of
+// the slot. A CALL nesting more than `frame::MAX_CALL_DEPTH` calls traps.
+//
+// ALLOC reserves zeroed heap memory for a number of objects of one type and
+// returns a pointer of that type to the first of them. Pointers are checked on
+// every use: they must point into a live allocation of their type, LOAD_PTR and
+// STORE_PTR must stay within its objects, FREE must get the pointer ALLOC
+// returned. Heap memory counts against the runtime's memory limit, and a single
+// allocation is bound by the runtime even without one.
+//
+// NEW_OBJECT moves objects from stack into the fields of a garbage collected
+// object and returns a reference to it. Fields keep their type. Objects live as
+// long as a reference to them is held by a frame, directly or through the fields
+// of other objects, the runtime frees the rest when it runs low on memory.
+// References can not be stored in the linear heap.
+//
+// Shifts and rotations take the amount modulo the bit width of the type, so
+// SHL_U8 by 9 shifts by 1.
 
-pub const NEW_BOOL: u64 = 0x01; // Allocates an object in the frame's stack of type bool
-pub const NEW_U8: u64 = 0x02;   // Allocates an object in the frame's stack of type u8
-pub const NEW_U16: u64 = 0x03;  // Allocates an object in the frame's stack of type u16
-pub const NEW_U32: u64 = 0x04;  // Allocates an object in the frame's stack of type u32
-pub const NEW_U64: u64 = 0x05;  // Allocates an object in the frame's stack of type u64
+/// Defines the opcode constants and `OPCODES`, the table HeatASM mnemonics are looked up in
+macro_rules! opcodes {
+    ($($name:ident = $value:expr;)*) => {
+        $(pub const $name: u64 = $value;)*
 
+        /// Every opcode paired with its HeatASM mnemonic
+        pub const OPCODES: &[(&str, u64)] = &[$((stringify!($name), $name)),*];
+    };
+}
 
-pub const EQUAL: u64 = 0x20; // Returns true if two values are equal
-pub const NOT: u64 = 0x21;   // Returns not value of a boolean
-pub const AND: u64 = 0x22;   // Returns not value of 2 booleans
-pub const OR: u64 = 0x23;    // Returns or value of 2 booleans
+opcodes! {
+    NONE = 0x00; // None
 
-pub const LOAD_BOOL: u64 = 0x30; // Load bool into stack
-pub const LOAD_U8: u64 = 0x31;   // Load u8 into stack
-pub const LOAD_U16: u64 = 0x32;  // Load u16 into stack
-pub const LOAD_U32: u64 = 0x33;  // Load u32 into stack
-pub const LOAD_U64: u64 = 0x34;  // Load u64 into stack
+    NEW_BOOL = 0x01; // Allocates an object in the frame's stack of type bool
+    NEW_U8 = 0x02;   // Allocates an object in the frame's stack of type u8
+    NEW_U16 = 0x03;  // Allocates an object in the frame's stack of type u16
+    NEW_U32 = 0x04;  // Allocates an object in the frame's stack of type u32
+    NEW_U64 = 0x05;  // Allocates an object in the frame's stack of type u64
+    NEW_I8 = 0x06;   // Allocates an object in the frame's stack of type i8
+    NEW_I16 = 0x07;  // Allocates an object in the frame's stack of type i16
+    NEW_I32 = 0x08;  // Allocates an object in the frame's stack of type i32
+    NEW_I64 = 0x09;  // Allocates an object in the frame's stack of type i64
+    NEW_F32 = 0x0A;  // Allocates an object in the frame's stack of type f32
+    NEW_F64 = 0x0B;  // Allocates an object in the frame's stack of type f64
 
-pub const STORE: u64 = 0x40; // Store from operand stack
-pub const LOCAL_LOAD: u64 = 0x41; // Load an object from stack to locals
+    JMP = 0x10;           // Jump to the instruction index in arg1
+    JMP_IF_TRUE = 0x11;   // Pop a bool from stack and jump to the instruction index in arg1 if it is true
+    JMP_IF_FALSE = 0x12;  // Pop a bool from stack and jump to the instruction index in arg1 if it is false
 
-pub const ADD_U8: u64 = 0x50;   // Pop 2 objects from stack and add them together u8
-pub const ADD_U16: u64 = 0x51;  // Pop 2 objects from stack and add them together u16
-pub const ADD_U32: u64 = 0x52;  // Pop 2 objects from stack and add them together u32
-pub const ADD_U64: u64 = 0x53;  // Pop 2 objects from stack and add them together u64
+    CALL = 0x18; // Call the function at index arg1, popping its parameters from stack into the callee's locals
+    RET = 0x19;  // Return from the frame, moving arg1 objects from stack onto the caller's operand stack
 
-pub const SUB_U8: u64 = 0x60;   // Pop 2 objects from stack and subtract them u8
-pub const SUB_U16: u64 = 0x61;  // Pop 2 objects from stack and subtract them u16
-pub const SUB_U32: u64 = 0x62;  // Pop 2 objects from stack and subtract them u32
-pub const SUB_U64: u64 = 0x63;  // Pop 2 objects from stack and subtract them u64
+    EQUAL = 0x20; // Returns true if two values are equal, floats compare by IEEE-754 rules
+    NOT = 0x21;   // Returns not value of a boolean
+    AND = 0x22;   // Returns not value of 2 booleans
+    OR = 0x23;    // Returns or value of 2 booleans
+    XOR = 0x24;   // Returns xor value of 2 booleans
 
-pub const DIV_U8: u64 = 0x70;   // Pop 2 objects from stack and divide them u8
-pub const DIV_U16: u64 = 0x71;  // Pop 2 objects from stack and divide them u16
-pub const DIV_U32: u64 = 0x72;  // Pop 2 objects from stack and divide them u32
-pub const DIV_U64: u64 = 0x73;  // Pop 2 objects from stack and divide them u64
+    LOAD_BOOL = 0x30; // Load bool into stack
+    LOAD_U8 = 0x31;   // Load u8 into stack
+    LOAD_U16 = 0x32;  // Load u16 into stack
+    LOAD_U32 = 0x33;  // Load u32 into stack
+    LOAD_U64 = 0x34;  // Load u64 into stack
+    LOAD_I8 = 0x35;   // Load i8 into stack, arg1 holds the value sign extended to 64 bits
+    LOAD_I16 = 0x36;  // Load i16 into stack, arg1 holds the value sign extended to 64 bits
+    LOAD_I32 = 0x37;  // Load i32 into stack, arg1 holds the value sign extended to 64 bits
+    LOAD_I64 = 0x38;  // Load i64 into stack
+    LOAD_F32 = 0x39;  // Load f32 into stack, arg1 holds the IEEE-754 bits
+    LOAD_F64 = 0x3A;  // Load f64 into stack, arg1 holds the IEEE-754 bits
+    LOAD_CONST = 0x3F; // Push a copy of the constant at index arg1 of the frame's constant pool onto stack
 
-pub const MUL_U8: u64 = 0x80;   // Pop 2 objects from stack and multiply them u8
-pub const MUL_U16: u64 = 0x81;  // Pop 2 objects from stack and multiply them u16
-pub const MUL_U32: u64 = 0x82;  // Pop 2 objects from stack and multiply them u32
-pub const MUL_U64: u64 = 0x83;  // Pop 2 objects from stack and multiply them u64
+    STORE = 0x40; // Move the object at the front of operand stack onto stack
+    // 0x41 was LOCAL_LOAD and stays unused, so old bytecode traps instead of running something else
+    POP = 0x42;   // Drop the object at the front of stack
+    DUP = 0x43;   // Push a copy of the object at the front of stack
+    SWAP = 0x44;  // Swap the two objects at the front of stack
+    OVER = 0x45;  // Push a copy of the object below the front of stack
+    ROT = 0x46;   // Move the third object from the front of stack to the front
+    PICK = 0x47;  // Push a copy of the object arg1 objects below the front of stack, PICK 0 is DUP
 
-pub const PWR_U8: u64 = 0x90;   // Pop 2 objects from stack and get the power of them u8
-pub const PWR_U16: u64 = 0x91;  // Pop 2 objects from stack and get the power of them u16
-pub const PWR_U32: u64 = 0x92;  // Pop 2 objects from stack and get the power of them u32
-pub const PWR_U64: u64 = 0x93;  // Pop 2 objects from stack and get the power of them u64
+    CAST_WRAP = 0x48;     // Convert the object at the front of stack to the `HType` id in arg1, truncating, rounding or saturating values that don't fit
+    CAST_CHECKED = 0x49;  // Convert the object at the front of stack to the `HType` id in arg1, trapping if the value changes
+    POP_OPERAND = 0x4A;   // Drop the object at the front of operand stack
+    DUP_OPERAND = 0x4B;   // Push a copy of the object at the front of operand stack
+    SWAP_OPERAND = 0x4C;  // Swap the two objects at the front of operand stack
+    OVER_OPERAND = 0x4D;  // Push a copy of the object below the front of operand stack
+    ROT_OPERAND = 0x4E;   // Move the third object from the front of operand stack to the front
+    PICK_OPERAND = 0x4F;  // Push a copy of the object arg1 objects below the front of operand stack, PICK_OPERAND 0 is DUP_OPERAND
 
+    ADD_U8 = 0x50;   // Pop 2 objects from stack and add them together u8
+    ADD_U16 = 0x51;  // Pop 2 objects from stack and add them together u16
+    ADD_U32 = 0x52;  // Pop 2 objects from stack and add them together u32
+    ADD_U64 = 0x53;  // Pop 2 objects from stack and add them together u64
+    ADD_I8 = 0x54;   // Pop 2 objects from stack and add them together i8
+    ADD_I16 = 0x55;  // Pop 2 objects from stack and add them together i16
+    ADD_I32 = 0x56;  // Pop 2 objects from stack and add them together i32
+    ADD_I64 = 0x57;  // Pop 2 objects from stack and add them together i64
+    ADD_F32 = 0x58;  // Pop 2 objects from stack and add them together f32
+    ADD_F64 = 0x59;  // Pop 2 objects from stack and add them together f64
 
+    SUB_U8 = 0x60;   // Pop 2 objects from stack and subtract them u8
+    SUB_U16 = 0x61;  // Pop 2 objects from stack and subtract them u16
+    SUB_U32 = 0x62;  // Pop 2 objects from stack and subtract them u32
+    SUB_U64 = 0x63;  // Pop 2 objects from stack and subtract them u64
+    SUB_I8 = 0x64;   // Pop 2 objects from stack and subtract them i8
+    SUB_I16 = 0x65;  // Pop 2 objects from stack and subtract them i16
+    SUB_I32 = 0x66;  // Pop 2 objects from stack and subtract them i32
+    SUB_I64 = 0x67;  // Pop 2 objects from stack and subtract them i64
+    SUB_F32 = 0x68;  // Pop 2 objects from stack and subtract them f32
+    SUB_F64 = 0x69;  // Pop 2 objects from stack and subtract them f64
 
-pub const ILLEGAL: u64 = u64::MAX;    // ILLEGAL opcode
+    DIV_U8 = 0x70;   // Pop 2 objects from stack and divide them u8
+    DIV_U16 = 0x71;  // Pop 2 objects from stack and divide them u16
+    DIV_U32 = 0x72;  // Pop 2 objects from stack and divide them u32
+    DIV_U64 = 0x73;  // Pop 2 objects from stack and divide them u64
+    DIV_I8 = 0x74;   // Pop 2 objects from stack and divide them i8 (rounds toward zero)
+    DIV_I16 = 0x75;  // Pop 2 objects from stack and divide them i16 (rounds toward zero)
+    DIV_I32 = 0x76;  // Pop 2 objects from stack and divide them i32 (rounds toward zero)
+    DIV_I64 = 0x77;  // Pop 2 objects from stack and divide them i64 (rounds toward zero)
+    DIV_F32 = 0x78;  // Pop 2 objects from stack and divide them f32, dividing by zero gives an infinity or NaN
+    DIV_F64 = 0x79;  // Pop 2 objects from stack and divide them f64, dividing by zero gives an infinity or NaN
+
+    MUL_U8 = 0x80;   // Pop 2 objects from stack and multiply them u8
+    MUL_U16 = 0x81;  // Pop 2 objects from stack and multiply them u16
+    MUL_U32 = 0x82;  // Pop 2 objects from stack and multiply them u32
+    MUL_U64 = 0x83;  // Pop 2 objects from stack and multiply them u64
+    MUL_I8 = 0x84;   // Pop 2 objects from stack and multiply them i8
+    MUL_I16 = 0x85;  // Pop 2 objects from stack and multiply them i16
+    MUL_I32 = 0x86;  // Pop 2 objects from stack and multiply them i32
+    MUL_I64 = 0x87;  // Pop 2 objects from stack and multiply them i64
+    MUL_F32 = 0x88;  // Pop 2 objects from stack and multiply them f32
+    MUL_F64 = 0x89;  // Pop 2 objects from stack and multiply them f64
+
+    PWR_U8 = 0x90;   // Pop 2 objects from stack and raise the front one to the power of the other u8
+    PWR_U16 = 0x91;  // Pop 2 objects from stack and raise the front one to the power of the other u16
+    PWR_U32 = 0x92;  // Pop 2 objects from stack and raise the front one to the power of the other u32
+    PWR_U64 = 0x93;  // Pop 2 objects from stack and raise the front one to the power of the other u64
+    PWR_I8 = 0x94;   // Pop 2 objects from stack and raise the front one to the power of the other i8, negative exponents trap
+    PWR_I16 = 0x95;  // Pop 2 objects from stack and raise the front one to the power of the other i16, negative exponents trap
+    PWR_I32 = 0x96;  // Pop 2 objects from stack and raise the front one to the power of the other i32, negative exponents trap
+    PWR_I64 = 0x97;  // Pop 2 objects from stack and raise the front one to the power of the other i64, negative exponents trap
+    PWR_F32 = 0x98;  // Pop 2 objects from stack and raise the front one to the power of the other f32
+    PWR_F64 = 0x99;  // Pop 2 objects from stack and raise the front one to the power of the other f64
+
+    LT_U8 = 0xA0;   // Returns true if the object at the front of stack is less than the one below it u8
+    LT_U16 = 0xA1;  // Returns true if the object at the front of stack is less than the one below it u16
+    LT_U32 = 0xA2;  // Returns true if the object at the front of stack is less than the one below it u32
+    LT_U64 = 0xA3;  // Returns true if the object at the front of stack is less than the one below it u64
+    LT_I8 = 0xA4;   // Returns true if the object at the front of stack is less than the one below it i8
+    LT_I16 = 0xA5;  // Returns true if the object at the front of stack is less than the one below it i16
+    LT_I32 = 0xA6;  // Returns true if the object at the front of stack is less than the one below it i32
+    LT_I64 = 0xA7;  // Returns true if the object at the front of stack is less than the one below it i64
+    LT_F32 = 0xA8;  // Returns true if the object at the front of stack is less than the one below it f32, false if either is NaN
+    LT_F64 = 0xA9;  // Returns true if the object at the front of stack is less than the one below it f64, false if either is NaN
+
+    LE_U8 = 0xB0;   // Returns true if the object at the front of stack is less than or equal to the one below it u8
+    LE_U16 = 0xB1;  // Returns true if the object at the front of stack is less than or equal to the one below it u16
+    LE_U32 = 0xB2;  // Returns true if the object at the front of stack is less than or equal to the one below it u32
+    LE_U64 = 0xB3;  // Returns true if the object at the front of stack is less than or equal to the one below it u64
+    LE_I8 = 0xB4;   // Returns true if the object at the front of stack is less than or equal to the one below it i8
+    LE_I16 = 0xB5;  // Returns true if the object at the front of stack is less than or equal to the one below it i16
+    LE_I32 = 0xB6;  // Returns true if the object at the front of stack is less than or equal to the one below it i32
+    LE_I64 = 0xB7;  // Returns true if the object at the front of stack is less than or equal to the one below it i64
+    LE_F32 = 0xB8;  // Returns true if the object at the front of stack is less than or equal to the one below it f32, false if either is NaN
+    LE_F64 = 0xB9;  // Returns true if the object at the front of stack is less than or equal to the one below it f64, false if either is NaN
+
+    GT_U8 = 0xC0;   // Returns true if the object at the front of stack is greater than the one below it u8
+    GT_U16 = 0xC1;  // Returns true if the object at the front of stack is greater than the one below it u16
+    GT_U32 = 0xC2;  // Returns true if the object at the front of stack is greater than the one below it u32
+    GT_U64 = 0xC3;  // Returns true if the object at the front of stack is greater than the one below it u64
+    GT_I8 = 0xC4;   // Returns true if the object at the front of stack is greater than the one below it i8
+    GT_I16 = 0xC5;  // Returns true if the object at the front of stack is greater than the one below it i16
+    GT_I32 = 0xC6;  // Returns true if the object at the front of stack is greater than the one below it i32
+    GT_I64 = 0xC7;  // Returns true if the object at the front of stack is greater than the one below it i64
+    GT_F32 = 0xC8;  // Returns true if the object at the front of stack is greater than the one below it f32, false if either is NaN
+    GT_F64 = 0xC9;  // Returns true if the object at the front of stack is greater than the one below it f64, false if either is NaN
+
+    GE_U8 = 0xD0;   // Returns true if the object at the front of stack is greater than or equal to the one below it u8
+    GE_U16 = 0xD1;  // Returns true if the object at the front of stack is greater than or equal to the one below it u16
+    GE_U32 = 0xD2;  // Returns true if the object at the front of stack is greater than or equal to the one below it u32
+    GE_U64 = 0xD3;  // Returns true if the object at the front of stack is greater than or equal to the one below it u64
+    GE_I8 = 0xD4;   // Returns true if the object at the front of stack is greater than or equal to the one below it i8
+    GE_I16 = 0xD5;  // Returns true if the object at the front of stack is greater than or equal to the one below it i16
+    GE_I32 = 0xD6;  // Returns true if the object at the front of stack is greater than or equal to the one below it i32
+    GE_I64 = 0xD7;  // Returns true if the object at the front of stack is greater than or equal to the one below it i64
+    GE_F32 = 0xD8;  // Returns true if the object at the front of stack is greater than or equal to the one below it f32, false if either is NaN
+    GE_F64 = 0xD9;  // Returns true if the object at the front of stack is greater than or equal to the one below it f64, false if either is NaN
+
+    ADD_WRAP_U8 = 0x150;   // Pop 2 objects from stack and add them together u8, wrapping on overflow
+    ADD_WRAP_U16 = 0x151;  // Pop 2 objects from stack and add them together u16, wrapping on overflow
+    ADD_WRAP_U32 = 0x152;  // Pop 2 objects from stack and add them together u32, wrapping on overflow
+    ADD_WRAP_U64 = 0x153;  // Pop 2 objects from stack and add them together u64, wrapping on overflow
+    ADD_WRAP_I8 = 0x154;   // Pop 2 objects from stack and add them together i8, wrapping on overflow
+    ADD_WRAP_I16 = 0x155;  // Pop 2 objects from stack and add them together i16, wrapping on overflow
+    ADD_WRAP_I32 = 0x156;  // Pop 2 objects from stack and add them together i32, wrapping on overflow
+    ADD_WRAP_I64 = 0x157;  // Pop 2 objects from stack and add them together i64, wrapping on overflow
+
+    ADD_CHECKED_U8 = 0x250;   // Pop 2 objects from stack and add them together u8, trapping on overflow
+    ADD_CHECKED_U16 = 0x251;  // Pop 2 objects from stack and add them together u16, trapping on overflow
+    ADD_CHECKED_U32 = 0x252;  // Pop 2 objects from stack and add them together u32, trapping on overflow
+    ADD_CHECKED_U64 = 0x253;  // Pop 2 objects from stack and add them together u64, trapping on overflow
+    ADD_CHECKED_I8 = 0x254;   // Pop 2 objects from stack and add them together i8, trapping on overflow
+    ADD_CHECKED_I16 = 0x255;  // Pop 2 objects from stack and add them together i16, trapping on overflow
+    ADD_CHECKED_I32 = 0x256;  // Pop 2 objects from stack and add them together i32, trapping on overflow
+    ADD_CHECKED_I64 = 0x257;  // Pop 2 objects from stack and add them together i64, trapping on overflow
+
+    ADD_SAT_U8 = 0x350;   // Pop 2 objects from stack and add them together u8, saturating on overflow
+    ADD_SAT_U16 = 0x351;  // Pop 2 objects from stack and add them together u16, saturating on overflow
+    ADD_SAT_U32 = 0x352;  // Pop 2 objects from stack and add them together u32, saturating on overflow
+    ADD_SAT_U64 = 0x353;  // Pop 2 objects from stack and add them together u64, saturating on overflow
+    ADD_SAT_I8 = 0x354;   // Pop 2 objects from stack and add them together i8, saturating on overflow
+    ADD_SAT_I16 = 0x355;  // Pop 2 objects from stack and add them together i16, saturating on overflow
+    ADD_SAT_I32 = 0x356;  // Pop 2 objects from stack and add them together i32, saturating on overflow
+    ADD_SAT_I64 = 0x357;  // Pop 2 objects from stack and add them together i64, saturating on overflow
+
+    SUB_WRAP_U8 = 0x160;   // Pop 2 objects from stack and subtract them u8, wrapping on overflow
+    SUB_WRAP_U16 = 0x161;  // Pop 2 objects from stack and subtract them u16, wrapping on overflow
+    SUB_WRAP_U32 = 0x162;  // Pop 2 objects from stack and subtract them u32, wrapping on overflow
+    SUB_WRAP_U64 = 0x163;  // Pop 2 objects from stack and subtract them u64, wrapping on overflow
+    SUB_WRAP_I8 = 0x164;   // Pop 2 objects from stack and subtract them i8, wrapping on overflow
+    SUB_WRAP_I16 = 0x165;  // Pop 2 objects from stack and subtract them i16, wrapping on overflow
+    SUB_WRAP_I32 = 0x166;  // Pop 2 objects from stack and subtract them i32, wrapping on overflow
+    SUB_WRAP_I64 = 0x167;  // Pop 2 objects from stack and subtract them i64, wrapping on overflow
+
+    SUB_CHECKED_U8 = 0x260;   // Pop 2 objects from stack and subtract them u8, trapping on overflow
+    SUB_CHECKED_U16 = 0x261;  // Pop 2 objects from stack and subtract them u16, trapping on overflow
+    SUB_CHECKED_U32 = 0x262;  // Pop 2 objects from stack and subtract them u32, trapping on overflow
+    SUB_CHECKED_U64 = 0x263;  // Pop 2 objects from stack and subtract them u64, trapping on overflow
+    SUB_CHECKED_I8 = 0x264;   // Pop 2 objects from stack and subtract them i8, trapping on overflow
+    SUB_CHECKED_I16 = 0x265;  // Pop 2 objects from stack and subtract them i16, trapping on overflow
+    SUB_CHECKED_I32 = 0x266;  // Pop 2 objects from stack and subtract them i32, trapping on overflow
+    SUB_CHECKED_I64 = 0x267;  // Pop 2 objects from stack and subtract them i64, trapping on overflow
+
+    SUB_SAT_U8 = 0x360;   // Pop 2 objects from stack and subtract them u8, saturating on overflow
+    SUB_SAT_U16 = 0x361;  // Pop 2 objects from stack and subtract them u16, saturating on overflow
+    SUB_SAT_U32 = 0x362;  // Pop 2 objects from stack and subtract them u32, saturating on overflow
+    SUB_SAT_U64 = 0x363;  // Pop 2 objects from stack and subtract them u64, saturating on overflow
+    SUB_SAT_I8 = 0x364;   // Pop 2 objects from stack and subtract them i8, saturating on overflow
+    SUB_SAT_I16 = 0x365;  // Pop 2 objects from stack and subtract them i16, saturating on overflow
+    SUB_SAT_I32 = 0x366;  // Pop 2 objects from stack and subtract them i32, saturating on overflow
+    SUB_SAT_I64 = 0x367;  // Pop 2 objects from stack and subtract them i64, saturating on overflow
+
+    DIV_WRAP_U8 = 0x170;   // Pop 2 objects from stack and divide them u8, wrapping on overflow
+    DIV_WRAP_U16 = 0x171;  // Pop 2 objects from stack and divide them u16, wrapping on overflow
+    DIV_WRAP_U32 = 0x172;  // Pop 2 objects from stack and divide them u32, wrapping on overflow
+    DIV_WRAP_U64 = 0x173;  // Pop 2 objects from stack and divide them u64, wrapping on overflow
+    DIV_WRAP_I8 = 0x174;   // Pop 2 objects from stack and divide them i8, wrapping on overflow
+    DIV_WRAP_I16 = 0x175;  // Pop 2 objects from stack and divide them i16, wrapping on overflow
+    DIV_WRAP_I32 = 0x176;  // Pop 2 objects from stack and divide them i32, wrapping on overflow
+    DIV_WRAP_I64 = 0x177;  // Pop 2 objects from stack and divide them i64, wrapping on overflow
+
+    DIV_CHECKED_U8 = 0x270;   // Pop 2 objects from stack and divide them u8, trapping on overflow
+    DIV_CHECKED_U16 = 0x271;  // Pop 2 objects from stack and divide them u16, trapping on overflow
+    DIV_CHECKED_U32 = 0x272;  // Pop 2 objects from stack and divide them u32, trapping on overflow
+    DIV_CHECKED_U64 = 0x273;  // Pop 2 objects from stack and divide them u64, trapping on overflow
+    DIV_CHECKED_I8 = 0x274;   // Pop 2 objects from stack and divide them i8, trapping on overflow
+    DIV_CHECKED_I16 = 0x275;  // Pop 2 objects from stack and divide them i16, trapping on overflow
+    DIV_CHECKED_I32 = 0x276;  // Pop 2 objects from stack and divide them i32, trapping on overflow
+    DIV_CHECKED_I64 = 0x277;  // Pop 2 objects from stack and divide them i64, trapping on overflow
+
+    DIV_SAT_U8 = 0x370;   // Pop 2 objects from stack and divide them u8, saturating on overflow
+    DIV_SAT_U16 = 0x371;  // Pop 2 objects from stack and divide them u16, saturating on overflow
+    DIV_SAT_U32 = 0x372;  // Pop 2 objects from stack and divide them u32, saturating on overflow
+    DIV_SAT_U64 = 0x373;  // Pop 2 objects from stack and divide them u64, saturating on overflow
+    DIV_SAT_I8 = 0x374;   // Pop 2 objects from stack and divide them i8, saturating on overflow
+    DIV_SAT_I16 = 0x375;  // Pop 2 objects from stack and divide them i16, saturating on overflow
+    DIV_SAT_I32 = 0x376;  // Pop 2 objects from stack and divide them i32, saturating on overflow
+    DIV_SAT_I64 = 0x377;  // Pop 2 objects from stack and divide them i64, saturating on overflow
+
+    MUL_WRAP_U8 = 0x180;   // Pop 2 objects from stack and multiply them u8, wrapping on overflow
+    MUL_WRAP_U16 = 0x181;  // Pop 2 objects from stack and multiply them u16, wrapping on overflow
+    MUL_WRAP_U32 = 0x182;  // Pop 2 objects from stack and multiply them u32, wrapping on overflow
+    MUL_WRAP_U64 = 0x183;  // Pop 2 objects from stack and multiply them u64, wrapping on overflow
+    MUL_WRAP_I8 = 0x184;   // Pop 2 objects from stack and multiply them i8, wrapping on overflow
+    MUL_WRAP_I16 = 0x185;  // Pop 2 objects from stack and multiply them i16, wrapping on overflow
+    MUL_WRAP_I32 = 0x186;  // Pop 2 objects from stack and multiply them i32, wrapping on overflow
+    MUL_WRAP_I64 = 0x187;  // Pop 2 objects from stack and multiply them i64, wrapping on overflow
+
+    MUL_CHECKED_U8 = 0x280;   // Pop 2 objects from stack and multiply them u8, trapping on overflow
+    MUL_CHECKED_U16 = 0x281;  // Pop 2 objects from stack and multiply them u16, trapping on overflow
+    MUL_CHECKED_U32 = 0x282;  // Pop 2 objects from stack and multiply them u32, trapping on overflow
+    MUL_CHECKED_U64 = 0x283;  // Pop 2 objects from stack and multiply them u64, trapping on overflow
+    MUL_CHECKED_I8 = 0x284;   // Pop 2 objects from stack and multiply them i8, trapping on overflow
+    MUL_CHECKED_I16 = 0x285;  // Pop 2 objects from stack and multiply them i16, trapping on overflow
+    MUL_CHECKED_I32 = 0x286;  // Pop 2 objects from stack and multiply them i32, trapping on overflow
+    MUL_CHECKED_I64 = 0x287;  // Pop 2 objects from stack and multiply them i64, trapping on overflow
+
+    MUL_SAT_U8 = 0x380;   // Pop 2 objects from stack and multiply them u8, saturating on overflow
+    MUL_SAT_U16 = 0x381;  // Pop 2 objects from stack and multiply them u16, saturating on overflow
+    MUL_SAT_U32 = 0x382;  // Pop 2 objects from stack and multiply them u32, saturating on overflow
+    MUL_SAT_U64 = 0x383;  // Pop 2 objects from stack and multiply them u64, saturating on overflow
+    MUL_SAT_I8 = 0x384;   // Pop 2 objects from stack and multiply them i8, saturating on overflow
+    MUL_SAT_I16 = 0x385;  // Pop 2 objects from stack and multiply them i16, saturating on overflow
+    MUL_SAT_I32 = 0x386;  // Pop 2 objects from stack and multiply them i32, saturating on overflow
+    MUL_SAT_I64 = 0x387;  // Pop 2 objects from stack and multiply them i64, saturating on overflow
+
+    REM_U8 = 0xE0;   // Pop 2 objects from stack and get the remainder of dividing them u8
+    REM_U16 = 0xE1;  // Pop 2 objects from stack and get the remainder of dividing them u16
+    REM_U32 = 0xE2;  // Pop 2 objects from stack and get the remainder of dividing them u32
+    REM_U64 = 0xE3;  // Pop 2 objects from stack and get the remainder of dividing them u64
+    REM_I8 = 0xE4;   // Pop 2 objects from stack and get the remainder of dividing them i8, it has the sign of the front one
+    REM_I16 = 0xE5;  // Pop 2 objects from stack and get the remainder of dividing them i16, it has the sign of the front one
+    REM_I32 = 0xE6;  // Pop 2 objects from stack and get the remainder of dividing them i32, it has the sign of the front one
+    REM_I64 = 0xE7;  // Pop 2 objects from stack and get the remainder of dividing them i64, it has the sign of the front one
+
+    MOD_U8 = 0xF0;   // Pop 2 objects from stack and get the remainder of dividing them u8
+    MOD_U16 = 0xF1;  // Pop 2 objects from stack and get the remainder of dividing them u16
+    MOD_U32 = 0xF2;  // Pop 2 objects from stack and get the remainder of dividing them u32
+    MOD_U64 = 0xF3;  // Pop 2 objects from stack and get the remainder of dividing them u64
+    MOD_I8 = 0xF4;   // Pop 2 objects from stack and get the remainder of dividing them i8, it is never negative
+    MOD_I16 = 0xF5;  // Pop 2 objects from stack and get the remainder of dividing them i16, it is never negative
+    MOD_I32 = 0xF6;  // Pop 2 objects from stack and get the remainder of dividing them i32, it is never negative
+    MOD_I64 = 0xF7;  // Pop 2 objects from stack and get the remainder of dividing them i64, it is never negative
+
+    PWR_WRAP_U8 = 0x190;   // Pop 2 objects from stack and raise the front one to the power of the other u8, wrapping on overflow
+    PWR_WRAP_U16 = 0x191;  // Pop 2 objects from stack and raise the front one to the power of the other u16, wrapping on overflow
+    PWR_WRAP_U32 = 0x192;  // Pop 2 objects from stack and raise the front one to the power of the other u32, wrapping on overflow
+    PWR_WRAP_U64 = 0x193;  // Pop 2 objects from stack and raise the front one to the power of the other u64, wrapping on overflow
+    PWR_WRAP_I8 = 0x194;   // Pop 2 objects from stack and raise the front one to the power of the other i8, wrapping on overflow
+    PWR_WRAP_I16 = 0x195;  // Pop 2 objects from stack and raise the front one to the power of the other i16, wrapping on overflow
+    PWR_WRAP_I32 = 0x196;  // Pop 2 objects from stack and raise the front one to the power of the other i32, wrapping on overflow
+    PWR_WRAP_I64 = 0x197;  // Pop 2 objects from stack and raise the front one to the power of the other i64, wrapping on overflow
+
+    PWR_CHECKED_U8 = 0x290;   // Pop 2 objects from stack and raise the front one to the power of the other u8, trapping on overflow
+    PWR_CHECKED_U16 = 0x291;  // Pop 2 objects from stack and raise the front one to the power of the other u16, trapping on overflow
+    PWR_CHECKED_U32 = 0x292;  // Pop 2 objects from stack and raise the front one to the power of the other u32, trapping on overflow
+    PWR_CHECKED_U64 = 0x293;  // Pop 2 objects from stack and raise the front one to the power of the other u64, trapping on overflow
+    PWR_CHECKED_I8 = 0x294;   // Pop 2 objects from stack and raise the front one to the power of the other i8, trapping on overflow
+    PWR_CHECKED_I16 = 0x295;  // Pop 2 objects from stack and raise the front one to the power of the other i16, trapping on overflow
+    PWR_CHECKED_I32 = 0x296;  // Pop 2 objects from stack and raise the front one to the power of the other i32, trapping on overflow
+    PWR_CHECKED_I64 = 0x297;  // Pop 2 objects from stack and raise the front one to the power of the other i64, trapping on overflow
+
+    PWR_SAT_U8 = 0x390;   // Pop 2 objects from stack and raise the front one to the power of the other u8, saturating on overflow
+    PWR_SAT_U16 = 0x391;  // Pop 2 objects from stack and raise the front one to the power of the other u16, saturating on overflow
+    PWR_SAT_U32 = 0x392;  // Pop 2 objects from stack and raise the front one to the power of the other u32, saturating on overflow
+    PWR_SAT_U64 = 0x393;  // Pop 2 objects from stack and raise the front one to the power of the other u64, saturating on overflow
+    PWR_SAT_I8 = 0x394;   // Pop 2 objects from stack and raise the front one to the power of the other i8, saturating on overflow
+    PWR_SAT_I16 = 0x395;  // Pop 2 objects from stack and raise the front one to the power of the other i16, saturating on overflow
+    PWR_SAT_I32 = 0x396;  // Pop 2 objects from stack and raise the front one to the power of the other i32, saturating on overflow
+    PWR_SAT_I64 = 0x397;  // Pop 2 objects from stack and raise the front one to the power of the other i64, saturating on overflow
+
+    AND_U8 = 0x400;   // Pop 2 objects from stack and bitwise and them u8
+    AND_U16 = 0x401;  // Pop 2 objects from stack and bitwise and them u16
+    AND_U32 = 0x402;  // Pop 2 objects from stack and bitwise and them u32
+    AND_U64 = 0x403;  // Pop 2 objects from stack and bitwise and them u64
+    AND_I8 = 0x404;   // Pop 2 objects from stack and bitwise and them i8
+    AND_I16 = 0x405;  // Pop 2 objects from stack and bitwise and them i16
+    AND_I32 = 0x406;  // Pop 2 objects from stack and bitwise and them i32
+    AND_I64 = 0x407;  // Pop 2 objects from stack and bitwise and them i64
+
+    OR_U8 = 0x410;   // Pop 2 objects from stack and bitwise or them u8
+    OR_U16 = 0x411;  // Pop 2 objects from stack and bitwise or them u16
+    OR_U32 = 0x412;  // Pop 2 objects from stack and bitwise or them u32
+    OR_U64 = 0x413;  // Pop 2 objects from stack and bitwise or them u64
+    OR_I8 = 0x414;   // Pop 2 objects from stack and bitwise or them i8
+    OR_I16 = 0x415;  // Pop 2 objects from stack and bitwise or them i16
+    OR_I32 = 0x416;  // Pop 2 objects from stack and bitwise or them i32
+    OR_I64 = 0x417;  // Pop 2 objects from stack and bitwise or them i64
+
+    XOR_U8 = 0x420;   // Pop 2 objects from stack and bitwise xor them u8
+    XOR_U16 = 0x421;  // Pop 2 objects from stack and bitwise xor them u16
+    XOR_U32 = 0x422;  // Pop 2 objects from stack and bitwise xor them u32
+    XOR_U64 = 0x423;  // Pop 2 objects from stack and bitwise xor them u64
+    XOR_I8 = 0x424;   // Pop 2 objects from stack and bitwise xor them i8
+    XOR_I16 = 0x425;  // Pop 2 objects from stack and bitwise xor them i16
+    XOR_I32 = 0x426;  // Pop 2 objects from stack and bitwise xor them i32
+    XOR_I64 = 0x427;  // Pop 2 objects from stack and bitwise xor them i64
+
+    NOT_U8 = 0x430;   // Returns the bitwise not of the object at the front of stack u8
+    NOT_U16 = 0x431;  // Returns the bitwise not of the object at the front of stack u16
+    NOT_U32 = 0x432;  // Returns the bitwise not of the object at the front of stack u32
+    NOT_U64 = 0x433;  // Returns the bitwise not of the object at the front of stack u64
+    NOT_I8 = 0x434;   // Returns the bitwise not of the object at the front of stack i8
+    NOT_I16 = 0x435;  // Returns the bitwise not of the object at the front of stack i16
+    NOT_I32 = 0x436;  // Returns the bitwise not of the object at the front of stack i32
+    NOT_I64 = 0x437;  // Returns the bitwise not of the object at the front of stack i64
+
+    SHL_U8 = 0x440;   // Pop 2 objects from stack and shift the front one left by the other u8
+    SHL_U16 = 0x441;  // Pop 2 objects from stack and shift the front one left by the other u16
+    SHL_U32 = 0x442;  // Pop 2 objects from stack and shift the front one left by the other u32
+    SHL_U64 = 0x443;  // Pop 2 objects from stack and shift the front one left by the other u64
+    SHL_I8 = 0x444;   // Pop 2 objects from stack and shift the front one left by the other i8
+    SHL_I16 = 0x445;  // Pop 2 objects from stack and shift the front one left by the other i16
+    SHL_I32 = 0x446;  // Pop 2 objects from stack and shift the front one left by the other i32
+    SHL_I64 = 0x447;  // Pop 2 objects from stack and shift the front one left by the other i64
+
+    SHR_U8 = 0x450;   // Pop 2 objects from stack and shift the front one right by the other u8, zero filling
+    SHR_U16 = 0x451;  // Pop 2 objects from stack and shift the front one right by the other u16, zero filling
+    SHR_U32 = 0x452;  // Pop 2 objects from stack and shift the front one right by the other u32, zero filling
+    SHR_U64 = 0x453;  // Pop 2 objects from stack and shift the front one right by the other u64, zero filling
+    SHR_I8 = 0x454;   // Pop 2 objects from stack and shift the front one right by the other i8, sign extending
+    SHR_I16 = 0x455;  // Pop 2 objects from stack and shift the front one right by the other i16, sign extending
+    SHR_I32 = 0x456;  // Pop 2 objects from stack and shift the front one right by the other i32, sign extending
+    SHR_I64 = 0x457;  // Pop 2 objects from stack and shift the front one right by the other i64, sign extending
+
+    ROTL_U8 = 0x460;   // Pop 2 objects from stack and rotate the front one left by the other u8
+    ROTL_U16 = 0x461;  // Pop 2 objects from stack and rotate the front one left by the other u16
+    ROTL_U32 = 0x462;  // Pop 2 objects from stack and rotate the front one left by the other u32
+    ROTL_U64 = 0x463;  // Pop 2 objects from stack and rotate the front one left by the other u64
+    ROTL_I8 = 0x464;   // Pop 2 objects from stack and rotate the front one left by the other i8
+    ROTL_I16 = 0x465;  // Pop 2 objects from stack and rotate the front one left by the other i16
+    ROTL_I32 = 0x466;  // Pop 2 objects from stack and rotate the front one left by the other i32
+    ROTL_I64 = 0x467;  // Pop 2 objects from stack and rotate the front one left by the other i64
+
+    ROTR_U8 = 0x470;   // Pop 2 objects from stack and rotate the front one right by the other u8
+    ROTR_U16 = 0x471;  // Pop 2 objects from stack and rotate the front one right by the other u16
+    ROTR_U32 = 0x472;  // Pop 2 objects from stack and rotate the front one right by the other u32
+    ROTR_U64 = 0x473;  // Pop 2 objects from stack and rotate the front one right by the other u64
+    ROTR_I8 = 0x474;   // Pop 2 objects from stack and rotate the front one right by the other i8
+    ROTR_I16 = 0x475;  // Pop 2 objects from stack and rotate the front one right by the other i16
+    ROTR_I32 = 0x476;  // Pop 2 objects from stack and rotate the front one right by the other i32
+    ROTR_I64 = 0x477;  // Pop 2 objects from stack and rotate the front one right by the other i64
+
+    DIVREM_U8 = 0x480;   // Pop 2 objects from stack and divide them u8, pushing the quotient and then the remainder
+    DIVREM_U16 = 0x481;  // Pop 2 objects from stack and divide them u16, pushing the quotient and then the remainder
+    DIVREM_U32 = 0x482;  // Pop 2 objects from stack and divide them u32, pushing the quotient and then the remainder
+    DIVREM_U64 = 0x483;  // Pop 2 objects from stack and divide them u64, pushing the quotient and then the remainder
+    DIVREM_I8 = 0x484;   // Pop 2 objects from stack and divide them i8, pushing the quotient and then the remainder
+    DIVREM_I16 = 0x485;  // Pop 2 objects from stack and divide them i16, pushing the quotient and then the remainder
+    DIVREM_I32 = 0x486;  // Pop 2 objects from stack and divide them i32, pushing the quotient and then the remainder
+    DIVREM_I64 = 0x487;  // Pop 2 objects from stack and divide them i64, pushing the quotient and then the remainder
+
+    NE_U8 = 0x490;   // Returns true if the object at the front of stack is not equal to the one below it u8
+    NE_U16 = 0x491;  // Returns true if the object at the front of stack is not equal to the one below it u16
+    NE_U32 = 0x492;  // Returns true if the object at the front of stack is not equal to the one below it u32
+    NE_U64 = 0x493;  // Returns true if the object at the front of stack is not equal to the one below it u64
+    NE_I8 = 0x494;   // Returns true if the object at the front of stack is not equal to the one below it i8
+    NE_I16 = 0x495;  // Returns true if the object at the front of stack is not equal to the one below it i16
+    NE_I32 = 0x496;  // Returns true if the object at the front of stack is not equal to the one below it i32
+    NE_I64 = 0x497;  // Returns true if the object at the front of stack is not equal to the one below it i64
+    NE_F32 = 0x498;  // Returns true if the object at the front of stack is not equal to the one below it f32, true if either is NaN
+    NE_F64 = 0x499;  // Returns true if the object at the front of stack is not equal to the one below it f64, true if either is NaN
+
+    LOCAL_GET = 0x4A0;  // Push a copy of the local in slot arg1 onto stack
+    LOCAL_SET = 0x4A1;  // Pop the object at the front of stack into the local in slot arg1, which must hold the same type
+    LOCAL_TEE = 0x4A2;  // Copy the object at the front of stack into the local in slot arg1, which must hold the same type
+
+    ALLOC = 0x4B0;      // Pop a u64 count from stack and allocate that many zeroed objects of the type in arg1 on the heap, returns a pointer to the first
+    FREE = 0x4B1;       // Pop a pointer from stack and free the heap allocation it points to the start of
+    LOAD_PTR = 0x4B2;   // Pop a pointer from stack and return a copy of the object it points to
+    STORE_PTR = 0x4B3;  // Pop an object and the pointer below it from stack and overwrite the object the pointer points to
+    PTR_ADD = 0x4B4;    // Pop a u64 count and the pointer below it from stack and return the pointer moved by count objects
+
+    NEW_OBJECT = 0x4C0; // Pop arg1 objects from stack into the fields of a new object, the deepest is field 0, returns a reference to it
+    GET_FIELD = 0x4C1;  // Pop a reference from stack and return a copy of field arg1 of the object
+    SET_FIELD = 0x4C2;  // Pop an object and the reference below it from stack into field arg1 of the object, which must hold the same type
+
+    STORE_OPERAND = 0x4D0; // Move the object at the front of stack onto operand stack
+
+    ILLEGAL = u64::MAX;    // ILLEGAL opcode
+}
+
+/// Get the opcode of a HeatASM mnemonic
+///
+/// ## Examples
+/// ```
+/// use lib_heat_spec::opcode;
+///
+/// assert_eq!(opcode::from_mnemonic("ADD_U8"), Some(opcode::ADD_U8));
+/// assert_eq!(opcode::from_mnemonic("ADD_U128"), None);
+/// ```
+pub fn from_mnemonic(mnemonic: &str) -> Option<u64> {
+    OPCODES.iter().find(|(name, _)| *name == mnemonic).map(|(_, opcode)| *opcode)
+}
+
+/// Get the type of the immediate value an opcode reads from arg1
+///
+/// Arguments of other opcodes are indices or counts and may hold any u64.
+///
+/// ## Examples
+/// ```
+/// use lib_heat_spec::h_type::HType;
+/// use lib_heat_spec::opcode;
+///
+/// assert_eq!(opcode::immediate_type(opcode::LOAD_U16), Some(HType::U16));
+/// assert_eq!(opcode::immediate_type(opcode::JMP), None);
+/// ```
+pub fn immediate_type(opcode: u64) -> Option<HType> {
+    match opcode {
+        LOAD_BOOL => Some(HType::Bool),
+        LOAD_U8 => Some(HType::U8),
+        LOAD_U16 => Some(HType::U16),
+        LOAD_U32 => Some(HType::U32),
+        LOAD_U64 => Some(HType::U64),
+        LOAD_I8 => Some(HType::I8),
+        LOAD_I16 => Some(HType::I16),
+        LOAD_I32 => Some(HType::I32),
+        LOAD_I64 => Some(HType::I64),
+        LOAD_F32 => Some(HType::F32),
+        LOAD_F64 => Some(HType::F64),
+        _ => None,
+    }
+}
+
+/// Check if an opcode takes an `HType` id in arg1, see `h_type::from_id`
+pub fn takes_type(opcode: u64) -> bool {
+    matches!(opcode, CAST_WRAP | CAST_CHECKED | ALLOC)
+}
+
+/// Get the operand type encoded in the low nibble of a number family opcode
+///
+/// ## Examples
+/// ```
+/// use lib_heat_spec::h_type::HType;
+/// use lib_heat_spec::opcode;
+///
+/// assert_eq!(opcode::operand_type(opcode::ADD_U16), Some(HType::U16));
+/// assert_eq!(opcode::operand_type(opcode::DIV_I64), Some(HType::I64));
+/// assert_eq!(opcode::operand_type(opcode::MUL_F32), Some(HType::F32));
+/// ```
+pub fn operand_type(opcode: u64) -> Option<HType> {
+    match opcode & 0xF {
+        0x0 => Some(HType::U8),
+        0x1 => Some(HType::U16),
+        0x2 => Some(HType::U32),
+        0x3 => Some(HType::U64),
+        0x4 => Some(HType::I8),
+        0x5 => Some(HType::I16),
+        0x6 => Some(HType::I32),
+        0x7 => Some(HType::I64),
+        0x8 => Some(HType::F32),
+        0x9 => Some(HType::F64),
+        _ => None,
+    }
+}
+
+/// Get the HeatASM mnemonic of an opcode
+pub fn to_mnemonic(opcode: u64) -> Option<&'static str> {
+    OPCODES.iter().find(|(_, value)| *value == opcode).map(|(name, _)| *name)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::opcode::{self, OPCODES};
+
+    #[test]
+    fn opcodes_unique() {
+        for (i, (name, opcode)) in OPCODES.iter().enumerate() {
+            for (other_name, other_opcode) in &OPCODES[i + 1..] {
+                assert_ne!(name, other_name);
+                assert_ne!(opcode, other_opcode, "{} and {} share an opcode", name, other_name);
+            }
+        }
+    }
+
+    #[test]
+    fn opcodes_retired() {
+        // LOCAL_LOAD
+        assert_eq!(opcode::to_mnemonic(0x41), None);
+    }
+}
@@ -0,0 +1,179 @@
+use lib_heat_spec::h_type::HType;
+use crate::types::VirtualObject;
+
+/// The value of an object, wide enough to hold every `HType` without loss
+#[derive(Clone, Copy, Debug)]
+enum Number {
+    Int(i128),
+    Float(f64),
+}
+
+impl Number {
+    fn of(obj: &VirtualObject) -> Number {
+        match obj.data_type {
+            HType::Bool => Number::Int(obj.get_bool() as i128),
+            HType::U8 => Number::Int(obj.get_u8() as i128),
+            HType::U16 => Number::Int(obj.get_u16() as i128),
+            HType::U32 => Number::Int(obj.get_u32() as i128),
+            HType::U64 => Number::Int(obj.get_u64() as i128),
+            HType::I8 => Number::Int(obj.get_i8() as i128),
+            HType::I16 => Number::Int(obj.get_i16() as i128),
+            HType::I32 => Number::Int(obj.get_i32() as i128),
+            HType::I64 => Number::Int(obj.get_i64() as i128),
+            HType::F32 => Number::Float(obj.get_f32() as f64),
+            HType::F64 => Number::Float(obj.get_f64()),
+            HType::Ptr => Number::Int(obj.get_pointer().location as i128),
+            HType::Ref => Number::Int(obj.get_reference().id as i128),
+        }
+    }
+
+    /// Check if two numbers hold the same value, NaNs are the same as each other
+    fn same(self, other: Number) -> bool {
+        match (self, other) {
+            (Number::Int(a), Number::Int(b)) => a == b,
+            (Number::Float(a), Number::Float(b)) => a == b || (a.is_nan() && b.is_nan()),
+            (Number::Int(int), Number::Float(float)) | (Number::Float(float), Number::Int(int)) => {
+                float.fract() == 0.0 && float as i128 == int
+            }
+        }
+    }
+}
+
+/// Convert an object to another `HType`, as done by CAST_WRAP
+///
+/// - integers are zero or sign extended and truncated to the target's bits
+/// - floats become integers rounding toward zero, saturating at the target's
+///   bounds, NaN becomes 0
+/// - integers become the nearest float, F64 becomes the nearest F32
+/// - anything becomes a bool by comparing it to 0, a bool becomes 0 or 1
+/// - pointers and references become their address or id, anything becomes a
+///   null pointer or reference; the interpreter does not cast them, so they
+///   can not be forged
+///
+/// ## Examples
+/// ```
+/// use lib_heat_spec::h_type::HType;
+/// use libvirt::cast;
+/// use libvirt::types::VirtualObject;
+///
+/// assert_eq!(cast::wrap(&VirtualObject::from(-1i8), HType::U16), VirtualObject::from(u16::MAX));
+/// assert_eq!(cast::wrap(&VirtualObject::from(300u16), HType::U8), VirtualObject::from(44u8));
+/// assert_eq!(cast::wrap(&VirtualObject::from(-2.7f32), HType::I32), VirtualObject::from(-2i32));
+/// ```
+pub fn wrap(obj: &VirtualObject, target: HType) -> VirtualObject {
+    match Number::of(obj) {
+        Number::Int(value) => match target {
+            HType::Bool => VirtualObject::from(value != 0),
+            HType::U8 => VirtualObject::from(value as u8),
+            HType::U16 => VirtualObject::from(value as u16),
+            HType::U32 => VirtualObject::from(value as u32),
+            HType::U64 => VirtualObject::from(value as u64),
+            HType::I8 => VirtualObject::from(value as i8),
+            HType::I16 => VirtualObject::from(value as i16),
+            HType::I32 => VirtualObject::from(value as i32),
+            HType::I64 => VirtualObject::from(value as i64),
+            HType::F32 => VirtualObject::from(value as f32),
+            HType::F64 => VirtualObject::from(value as f64),
+            HType::Ptr | HType::Ref => VirtualObject::new_empty(target),
+        },
+        Number::Float(value) => match target {
+            HType::Bool => VirtualObject::from(value != 0.0),
+            HType::U8 => VirtualObject::from(value as u8),
+            HType::U16 => VirtualObject::from(value as u16),
+            HType::U32 => VirtualObject::from(value as u32),
+            HType::U64 => VirtualObject::from(value as u64),
+            HType::I8 => VirtualObject::from(value as i8),
+            HType::I16 => VirtualObject::from(value as i16),
+            HType::I32 => VirtualObject::from(value as i32),
+            HType::I64 => VirtualObject::from(value as i64),
+            HType::F32 => VirtualObject::from(value as f32),
+            HType::F64 => VirtualObject::from(value),
+            HType::Ptr | HType::Ref => VirtualObject::new_empty(target),
+        },
+    }
+}
+
+/// Convert an object to another `HType` without losing information, as done by CAST_CHECKED
+///
+/// Returns `None` if `wrap` would change the value: integers out of the
+/// target's range, floats with a fraction, out of range or NaN converted to
+/// integers, integers and F64s without an exact float of the target type,
+/// and anything but 0 or 1 converted to a bool. NaN stays NaN between floats.
+///
+/// ## Examples
+/// ```
+/// use lib_heat_spec::h_type::HType;
+/// use libvirt::cast;
+/// use libvirt::types::VirtualObject;
+///
+/// assert_eq!(cast::checked(&VirtualObject::from(255u64), HType::U8), Some(VirtualObject::from(255u8)));
+/// assert_eq!(cast::checked(&VirtualObject::from(256u64), HType::U8), None);
+/// assert_eq!(cast::checked(&VirtualObject::from(1.5f64), HType::I64), None);
+/// ```
+pub fn checked(obj: &VirtualObject, target: HType) -> Option<VirtualObject> {
+    let result = wrap(obj, target);
+    if Number::of(&result).same(Number::of(obj)) {
+        Some(result)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lib_heat_spec::h_type::HType;
+    use crate::cast::{checked, wrap};
+    use crate::types::VirtualObject;
+
+    #[test]
+    fn cast_integers() {
+        // widening zero and sign extends
+        assert_eq!(checked(&VirtualObject::from(u8::MAX), HType::U64), Some(VirtualObject::from(255u64)));
+        assert_eq!(checked(&VirtualObject::from(-1i8), HType::I64), Some(VirtualObject::from(-1i64)));
+        assert_eq!(wrap(&VirtualObject::from(-1i32), HType::U64), VirtualObject::from(u64::MAX));
+
+        // narrowing truncates or fails
+        assert_eq!(wrap(&VirtualObject::from(0x1234u16), HType::U8), VirtualObject::from(0x34u8));
+        assert_eq!(wrap(&VirtualObject::from(200u8), HType::I8), VirtualObject::from(-56i8));
+        assert_eq!(checked(&VirtualObject::from(200u8), HType::I8), None);
+        assert_eq!(checked(&VirtualObject::from(-1i16), HType::U16), None);
+        assert_eq!(checked(&VirtualObject::from(-128i64), HType::I8), Some(VirtualObject::from(i8::MIN)));
+    }
+
+    #[test]
+    fn cast_bools() {
+        assert_eq!(checked(&VirtualObject::from(true), HType::U32), Some(VirtualObject::from(1u32)));
+        assert_eq!(checked(&VirtualObject::from(false), HType::F64), Some(VirtualObject::from(0.0f64)));
+        assert_eq!(checked(&VirtualObject::from(1i8), HType::Bool), Some(VirtualObject::from(true)));
+        assert_eq!(wrap(&VirtualObject::from(2u8), HType::Bool), VirtualObject::from(true));
+        assert_eq!(checked(&VirtualObject::from(2u8), HType::Bool), None);
+        assert_eq!(checked(&VirtualObject::from(-1i8), HType::Bool), None);
+    }
+
+    #[test]
+    fn cast_floats() {
+        // float to int
+        assert_eq!(checked(&VirtualObject::from(-3.0f32), HType::I8), Some(VirtualObject::from(-3i8)));
+        assert_eq!(wrap(&VirtualObject::from(1e10f64), HType::U16), VirtualObject::from(u16::MAX));
+        assert_eq!(wrap(&VirtualObject::from(f64::NAN), HType::I32), VirtualObject::from(0i32));
+        assert_eq!(checked(&VirtualObject::from(f64::NAN), HType::I32), None);
+        assert_eq!(checked(&VirtualObject::from(f32::INFINITY), HType::U64), None);
+        assert_eq!(checked(&VirtualObject::from(18446744073709551616.0f64), HType::U64), None, "2^64 saturates");
+        assert_eq!(checked(&VirtualObject::from(0.5f32), HType::U8), None);
+
+        // int to float
+        assert_eq!(checked(&VirtualObject::from(16_777_216u32), HType::F32), Some(VirtualObject::from(16_777_216.0f32)));
+        assert_eq!(checked(&VirtualObject::from(16_777_217u32), HType::F32), None);
+        assert_eq!(wrap(&VirtualObject::from(16_777_217u32), HType::F32), VirtualObject::from(16_777_216.0f32));
+        assert_eq!(checked(&VirtualObject::from(u64::MAX), HType::F64), None);
+        assert_eq!(checked(&VirtualObject::from(i64::MIN), HType::F64), Some(VirtualObject::from(i64::MIN as f64)));
+
+        // float to float
+        assert_eq!(checked(&VirtualObject::from(0.1f32), HType::F64), Some(VirtualObject::from(0.1f32 as f64)));
+        assert_eq!(checked(&VirtualObject::from(0.1f64), HType::F32), None);
+        assert_eq!(wrap(&VirtualObject::from(1e300f64), HType::F32), VirtualObject::from(f32::INFINITY));
+        assert_eq!(checked(&VirtualObject::from(f64::NEG_INFINITY), HType::F32), Some(VirtualObject::from(f32::NEG_INFINITY)));
+        assert!(checked(&VirtualObject::from(f64::NAN), HType::F32).unwrap().get_f32().is_nan());
+        assert_eq!(checked(&VirtualObject::from(-0.0f64), HType::F32), Some(VirtualObject::from(-0.0f32)));
+    }
+}
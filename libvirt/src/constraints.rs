@@ -1,15 +1,27 @@
+/// Limits enforced by the interpreter, a limit of 0 turns it off
 pub struct Constraints {
+    /// maximum number of bytes held by the objects in the stacks, locals and
+    /// constant pools of a frame and its children, every child counting
+    /// `frame::FRAME_HEADER_SIZE` on top, together with the heap and the
+    /// garbage collected objects
     pub max_memory: u64,
 
     /// maximum number of allocation possible in the stack
     pub max_stack_allocation: u64,
 }
 
+/// A limit of `Constraints`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Constraint {
+    MaxMemory,
+    MaxStackAllocation,
+}
+
 impl Constraints {
     pub fn new(max_memory: u64, max_stack_allocation: u64) -> Constraints {
-        return Constraints{ max_memory, max_stack_allocation }
+        Constraints{ max_memory, max_stack_allocation }
     }
     pub fn new_none() -> Constraints {
-        return Constraints{ max_memory: 0, max_stack_allocation: 0 };
+        Constraints{ max_memory: 0, max_stack_allocation: 0 }
     }
 }
@@ -1,3 +1,4 @@
+use std::sync::Arc;
 use crate::instruction::Instruction;
 use crate::types;
 use uuid::Uuid;
@@ -6,9 +7,17 @@ use crate::types::VirtualObject;
 
 pub type FrameAddress = Uuid;
 
+/// bytes every called frame counts against `Constraints::max_memory` on top of
+/// its objects, covering the `Frame` itself
+pub const FRAME_HEADER_SIZE: u64 = 256;
+
 /// frames are independent virtual machines that
 /// * have their own stack
 /// * have no dependency to any other frame other than the frame's children
+///
+/// Instructions read their operands from the front of `stack` and push their
+/// results onto `operand_stack`, see `lib_heat_spec::opcode`. STORE and
+/// STORE_OPERAND move objects between the two.
 pub struct Frame {
 
     /// unique identifier for the frame
@@ -17,20 +26,28 @@ pub struct Frame {
     /// instructions local to the frame
     pub instructions: Vec<Instruction>,
 
+    /// instructions shared with the function the frame runs, executed instead
+    /// of `instructions` if set, see `Function::frame`
+    pub shared_instructions: Option<Arc<[Instruction]>>,
+
     /// constant pool stores constant `VirtualObjects` that are local to the frame
     pub constant_pool: Vec<types::VirtualObject>,
 
-    /// local is a vector that holds variables local to the frame
+    /// local slots of the frame, the parameters followed by the locals the function declares
     pub local: Vec<types::VirtualObject>,
 
     /// stack stores `VirtualObjects` that are meant to be used/consumed by instructions
     pub stack: Vec<types::VirtualObject>,
 
     /// operand stack is stack which stores results of instructions such as ADD_U8, SUB_U16
+    /// and the objects returned by called functions
     pub operand_stack: Vec<types::VirtualObject>,
 
     /// points to the current instruction
     pub pc: u64,
+
+    /// call stack of the frames called from this frame, the innermost call is last
+    pub children: Vec<Frame>,
 }
 
 impl Default for Frame {
@@ -38,11 +55,13 @@ impl Default for Frame {
         Frame{
             address: Uuid::new_v4(),
             instructions: Default::default(),
+            shared_instructions: None,
             constant_pool: Default::default(),
             local: Default::default(),
             stack: Default::default(),
             operand_stack: Default::default(),
-            pc: 0
+            pc: 0,
+            children: Default::default(),
         }
     }
 }
@@ -53,12 +72,26 @@ impl Frame {
         Frame {
             address: uuid,
             instructions: Default::default(),
+            shared_instructions: None,
             constant_pool: Default::default(),
             local: Vec::with_capacity(local_max as usize),
             stack: Vec::with_capacity(stack_max as usize),
             operand_stack: Default::default(),
-            pc: 0
+            pc: 0,
+            children: Default::default(),
+        }
+    }
+
+    /// Get the frame that is currently executing, the innermost child if there is one
+    pub fn active_frame(&self) -> &Frame {
+        self.children.last().unwrap_or(self)
+    }
+
+    pub fn active_frame_mut(&mut self) -> &mut Frame {
+        if self.children.is_empty() {
+            return self;
         }
+        self.children.last_mut().unwrap()
     }
 
     /// Allocate the `HType` and `push_back` it to the frame's stack
@@ -85,27 +118,78 @@ impl Frame {
             .push(VirtualObject::new_empty(htype));
     }
 
+    /// Get the object `offset` objects below the front of the stack, `None` if the stack is shorter
+    ///
+    /// ## Examples
+    /// ```
+    /// use libvirt::frame::Frame;
+    /// use libvirt::types::VirtualObject;
+    ///
+    /// let frame = Frame { stack: vec![VirtualObject::from(1u8), VirtualObject::from(2u8)], ..Default::default() };
+    /// assert_eq!(frame.get_front_in_stack(0), Some(&VirtualObject::from(2u8)));
+    /// assert_eq!(frame.get_front_in_stack(1), Some(&VirtualObject::from(1u8)));
+    /// assert_eq!(frame.get_front_in_stack(2), None);
+    /// assert_eq!(frame.get_front_in_op_stack(0), None);
+    /// ```
     pub fn get_front_in_stack(&self, offset: usize) -> Option<&VirtualObject> {
-        self.stack.get(self.stack.len() - 1 - offset)
+        front(&self.stack, offset)
     }
 
     pub fn get_mut_front_in_stack(&mut self, offset: usize) -> Option<&mut VirtualObject> {
-        let len = self.stack.len()-1;
-        self.stack.get_mut(len-offset)
+        front_mut(&mut self.stack, offset)
     }
 
+    /// Get the object `offset` objects below the front of the operand stack, `None` if the operand stack is shorter
     pub fn get_front_in_op_stack(&self, offset: usize) -> Option<&VirtualObject> {
-        let len = self.stack.len()-1;
-        self.stack.get(len-offset)
+        front(&self.operand_stack, offset)
     }
 
     pub fn get_mut_front_in_op_stack(&mut self, offset: usize) -> Option<&mut VirtualObject> {
-        self.operand_stack.get_mut(self.stack.len() - 1 - offset)
+        front_mut(&mut self.operand_stack, offset)
+    }
+
+    /// Get the instructions the frame executes, the shared ones if there are any
+    pub fn code(&self) -> &[Instruction] {
+        self.shared_instructions.as_deref().unwrap_or(&self.instructions)
+    }
+
+    /// Check if the frame and all of its children have run out of instructions
+    pub fn is_finished(&self) -> bool {
+        self.children.is_empty() && self.pc as usize >= self.code().len()
+    }
+
+    /// Get the number of bytes held by the objects of the frame and its children,
+    /// every child counts `FRAME_HEADER_SIZE` on top
+    ///
+    /// This walks the whole call stack, the interpreter measures a frame once
+    /// and keeps count of what its instructions allocate and free from then on.
+    pub fn memory_used(&self) -> u64 {
+        let own: usize = [&self.constant_pool, &self.local, &self.stack, &self.operand_stack]
+            .iter()
+            .flat_map(|objects| objects.iter())
+            .map(|obj| obj.data.len())
+            .sum();
+        own as u64 + self.children.iter().map(|child| FRAME_HEADER_SIZE + child.memory_used()).sum::<u64>()
     }
 
     /// Clear instruction storage and resets the program counter
     pub fn clear_instructions(&mut self) {
         self.pc = 0;
         self.instructions.clear();
+        self.shared_instructions = None;
     }
-}
\ No newline at end of file
+}
+
+/// Get the index of the object `offset` objects below the front of a stack
+fn front_index(stack: &[VirtualObject], offset: usize) -> Option<usize> {
+    stack.len().checked_sub(offset)?.checked_sub(1)
+}
+
+fn front(stack: &[VirtualObject], offset: usize) -> Option<&VirtualObject> {
+    stack.get(front_index(stack, offset)?)
+}
+
+fn front_mut(stack: &mut [VirtualObject], offset: usize) -> Option<&mut VirtualObject> {
+    let index = front_index(stack, offset)?;
+    stack.get_mut(index)
+}
@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+
+/// Fuel bounds how many instructions an interpreter may execute
+///
+/// Every instruction burns the cost of its opcode before it is executed.
+/// When the remaining fuel does not cover the cost the interpreter traps with
+/// `TrapKind::OutOfFuel` without executing it, so execution can be resumed
+/// after adding more fuel. Instructions that trap for any other reason get
+/// their cost refunded, so resuming after fixing the cause burns it once.
+///
+/// ## Examples
+/// ```
+/// use libvirt::fuel::Fuel;
+/// use lib_heat_spec::opcode;
+///
+/// let mut fuel = Fuel::new(10);
+/// fuel.set_cost(opcode::CALL, 5);
+/// assert_eq!(fuel.cost(opcode::CALL), 5);
+/// assert_eq!(fuel.cost(opcode::NONE), 1);
+///
+/// fuel.add(5);
+/// assert_eq!(fuel.remaining(), 15);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Fuel {
+    remaining: u64,
+
+    /// cost of opcodes without a cost of their own
+    pub default_cost: u64,
+
+    /// cost of individual opcodes
+    pub costs: HashMap<u64, u64>,
+}
+
+impl Fuel {
+    /// Create fuel where every opcode costs 1
+    pub fn new(amount: u64) -> Fuel {
+        Fuel { remaining: amount, default_cost: 1, costs: Default::default() }
+    }
+
+    pub fn remaining(&self) -> u64 {
+        self.remaining
+    }
+
+    /// Top up the fuel, saturating at `u64::MAX`
+    pub fn add(&mut self, amount: u64) {
+        self.remaining = self.remaining.saturating_add(amount);
+    }
+
+    pub fn set_cost(&mut self, opcode: u64, cost: u64) {
+        self.costs.insert(opcode, cost);
+    }
+
+    pub fn cost(&self, opcode: u64) -> u64 {
+        *self.costs.get(&opcode).unwrap_or(&self.default_cost)
+    }
+
+    /// Burn the cost of an opcode, returns false and burns nothing if there is not enough fuel
+    pub fn burn(&mut self, opcode: u64) -> bool {
+        match self.remaining.checked_sub(self.cost(opcode)) {
+            Some(remaining) => {
+                self.remaining = remaining;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Give back the cost of an opcode burnt by an instruction that trapped
+    pub fn refund(&mut self, opcode: u64) {
+        self.add(self.cost(opcode));
+    }
+}
@@ -0,0 +1,52 @@
+use std::sync::Arc;
+use lib_heat_spec::h_type::HType;
+use crate::frame::Frame;
+use crate::instruction::Instruction;
+use crate::types::VirtualObject;
+
+/// A function that frames can call with the CALL opcode
+#[derive(Clone, Debug, Default)]
+pub struct Function {
+    pub name: String,
+
+    /// number of objects the caller passes from its stack into the callee's locals,
+    /// bound by `lib_heat_spec::frame::MAX_METHOD_PARAM`
+    pub param_count: u8,
+
+    /// instructions shared by every frame running the function
+    pub instructions: Arc<[Instruction]>,
+
+    /// constants copied into the constant pool of every frame running the function
+    pub constants: Vec<VirtualObject>,
+
+    /// types of the local slots following the parameters,
+    /// bound by `lib_heat_spec::frame::MAX_LOCAL_SIZE` together with the parameters
+    pub locals: Vec<HType>,
+}
+
+impl Function {
+    pub fn new(name: &str, param_count: u8, instructions: Vec<Instruction>) -> Function {
+        Function { name: name.to_string(), param_count, instructions: instructions.into(), constants: Vec::new(), locals: Vec::new() }
+    }
+
+    /// Create a frame running the function
+    ///
+    /// The frame's local slots hold `params` followed by a zeroed object for
+    /// every declared local. The frame shares the function's instructions
+    /// instead of copying them.
+    pub fn frame(&self, params: Vec<VirtualObject>) -> Frame {
+        let mut local = params;
+        local.extend(self.locals.iter().map(|h_type| VirtualObject::new_empty(*h_type)));
+        Frame {
+            shared_instructions: Some(self.instructions.clone()),
+            constant_pool: self.constants.clone(),
+            local,
+            ..Default::default()
+        }
+    }
+
+    /// Get the number of local slots of the frames running the function
+    pub fn local_count(&self) -> usize {
+        self.param_count as usize + self.locals.len()
+    }
+}
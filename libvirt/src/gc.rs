@@ -0,0 +1,260 @@
+use std::collections::{HashMap, HashSet};
+use lib_heat_spec::h_type::HType;
+use crate::frame::Frame;
+use crate::trap::TrapKind;
+use crate::types::{ObjectId, VirtualObject, VirtualReference};
+
+/// bytes every object counts against `Constraints::max_memory` on top of its fields
+pub const OBJECT_HEADER_SIZE: u64 = 8;
+
+/// A heap of garbage collected objects, addressed by `VirtualReference`s
+///
+/// Objects hold a fixed number of fields, which keep the type they were
+/// created with. Objects stay alive as long as they are reachable from the
+/// roots passed to `collect`, usually the references frames hold. Ids are
+/// handed out in increasing order and never reused.
+#[derive(Debug)]
+pub struct ObjectHeap {
+    objects: HashMap<ObjectId, Vec<VirtualObject>>,
+
+    /// id of the next object
+    next: ObjectId,
+
+    /// bytes held by the live objects, headers included
+    used: u64,
+}
+
+impl Default for ObjectHeap {
+    fn default() -> Self {
+        ObjectHeap { objects: Default::default(), next: 1, used: 0 }
+    }
+}
+
+impl ObjectHeap {
+    /// Get the number of bytes held by the objects, headers included
+    pub fn used(&self) -> u64 {
+        self.used
+    }
+
+    /// Get the number of objects, reachable or not
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// Get the number of bytes an object with these fields holds
+    pub fn size_of(fields: &[VirtualObject]) -> u64 {
+        OBJECT_HEADER_SIZE + fields.iter().map(|field| field.data.len() as u64).sum::<u64>()
+    }
+
+    /// Create an object holding `fields` and get a reference to it
+    ///
+    /// ## Examples
+    /// ```
+    /// use libvirt::gc::ObjectHeap;
+    /// use libvirt::types::VirtualObject;
+    ///
+    /// let mut objects = ObjectHeap::default();
+    /// let reference = objects.alloc(vec![VirtualObject::from(1u8), VirtualObject::from(2u16)]);
+    /// objects.set_field(reference, 1, VirtualObject::from(7u16)).unwrap();
+    /// assert_eq!(objects.field(reference, 1).unwrap(), &VirtualObject::from(7u16));
+    /// assert_eq!(objects.used(), 8 + 3);
+    /// ```
+    pub fn alloc(&mut self, fields: Vec<VirtualObject>) -> VirtualReference {
+        let id = self.next;
+        self.next += 1;
+        self.used += ObjectHeap::size_of(&fields);
+        self.objects.insert(id, fields);
+        VirtualReference { id }
+    }
+
+    /// Get the fields of the object a reference points to
+    pub fn fields(&self, reference: VirtualReference) -> Result<&[VirtualObject], TrapKind> {
+        self.objects.get(&reference.id).map(Vec::as_slice).ok_or(TrapKind::NullReference)
+    }
+
+    /// Get a field of the object a reference points to
+    pub fn field(&self, reference: VirtualReference, index: usize) -> Result<&VirtualObject, TrapKind> {
+        self.fields(reference)?.get(index).ok_or(TrapKind::FieldOutOfRange)
+    }
+
+    /// Overwrite a field of the object a reference points to, the field keeps its type
+    pub fn set_field(&mut self, reference: VirtualReference, index: usize, obj: VirtualObject) -> Result<(), TrapKind> {
+        let fields = self.objects.get_mut(&reference.id).ok_or(TrapKind::NullReference)?;
+        let field = fields.get_mut(index).ok_or(TrapKind::FieldOutOfRange)?;
+        if field.data_type != obj.data_type {
+            return Err(TrapKind::TypeMismatch { expected: field.data_type, found: obj.data_type });
+        }
+        *field = obj;
+        Ok(())
+    }
+
+    /// Free every object that is not reachable from the ids in `roots`, see `roots`
+    ///
+    /// Objects are reachable from the roots through the references in their
+    /// fields. Returns the number of freed objects.
+    ///
+    /// ## Examples
+    /// ```
+    /// use libvirt::frame::Frame;
+    /// use libvirt::gc::{self, ObjectHeap};
+    /// use libvirt::types::VirtualObject;
+    ///
+    /// let mut objects = ObjectHeap::default();
+    /// let kept = objects.alloc(Vec::new());
+    /// objects.alloc(vec![VirtualObject::from(kept)]);
+    ///
+    /// let frame = Frame { local: vec![VirtualObject::from(kept)], ..Default::default() };
+    /// assert_eq!(objects.collect(gc::roots(&frame)), 1);
+    /// assert!(objects.fields(kept).is_ok());
+    /// ```
+    pub fn collect<I: IntoIterator<Item = ObjectId>>(&mut self, roots: I) -> usize {
+        let mut marked = HashSet::new();
+        let mut pending: Vec<ObjectId> = roots.into_iter().collect();
+
+        while let Some(id) = pending.pop() {
+            if !marked.insert(id) {
+                continue;
+            }
+            if let Some(fields) = self.objects.get(&id) {
+                pending.extend(references(fields));
+            }
+        }
+
+        let before = self.objects.len();
+        let mut freed = 0;
+        self.objects.retain(|id, fields| {
+            let keep = marked.contains(id);
+            if !keep {
+                freed += ObjectHeap::size_of(fields);
+            }
+            keep
+        });
+        self.used -= freed;
+        before - self.objects.len()
+    }
+}
+
+/// Get the ids of the objects a frame and its children reference
+///
+/// References in the stack, operand stack, locals and constant pool of every
+/// frame are roots.
+pub fn roots(frame: &Frame) -> Vec<ObjectId> {
+    let mut ids = Vec::new();
+    push_roots(frame, &mut ids);
+    ids
+}
+
+fn push_roots(frame: &Frame, ids: &mut Vec<ObjectId>) {
+    for objects in [&frame.stack, &frame.operand_stack, &frame.local, &frame.constant_pool] {
+        ids.extend(references(objects));
+    }
+    for child in &frame.children {
+        push_roots(child, ids);
+    }
+}
+
+/// Get the ids of the objects referenced by `objects`, null references excluded
+pub(crate) fn references(objects: &[VirtualObject]) -> impl Iterator<Item = ObjectId> + '_ {
+    objects.iter()
+        .filter(|obj| obj.data_type == HType::Ref)
+        .map(|obj| obj.get_reference().id)
+        .filter(|id| *id != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use lib_heat_spec::h_type::HType;
+    use crate::frame::Frame;
+    use crate::gc::{roots, ObjectHeap, OBJECT_HEADER_SIZE};
+    use crate::trap::TrapKind;
+    use crate::types::{VirtualObject, VirtualReference};
+
+    #[test]
+    fn object_heap_fields() {
+        let mut objects = ObjectHeap::default();
+        let reference = objects.alloc(vec![VirtualObject::from(1u32)]);
+
+        assert_eq!(objects.field(reference, 1), Err(TrapKind::FieldOutOfRange));
+        assert_eq!(
+            objects.set_field(reference, 0, VirtualObject::from(1u8)),
+            Err(TrapKind::TypeMismatch { expected: HType::U32, found: HType::U8 }),
+        );
+        let null = VirtualReference { id: 0 };
+        assert_eq!(objects.fields(null), Err(TrapKind::NullReference));
+        assert_eq!(objects.set_field(null, 0, VirtualObject::from(1u32)), Err(TrapKind::NullReference));
+    }
+
+    #[test]
+    fn object_heap_collect_roots() {
+        let mut objects = ObjectHeap::default();
+        let in_stack = objects.alloc(Vec::new());
+        let in_operand_stack = objects.alloc(Vec::new());
+        let in_local = objects.alloc(Vec::new());
+        let in_child = objects.alloc(Vec::new());
+        let garbage = objects.alloc(vec![VirtualObject::from(in_stack)]);
+
+        let frame = Frame {
+            stack: vec![VirtualObject::from(in_stack), VirtualObject::from(1u64)],
+            operand_stack: vec![VirtualObject::from(in_operand_stack)],
+            local: vec![VirtualObject::from(in_local), VirtualObject::new_empty(HType::Ref)],
+            children: vec![Frame { stack: vec![VirtualObject::from(in_child)], ..Default::default() }],
+            ..Default::default()
+        };
+        assert_eq!(objects.collect(roots(&frame)), 1);
+        assert_eq!(objects.fields(garbage), Err(TrapKind::NullReference));
+        assert_eq!(objects.len(), 4);
+        assert_eq!(objects.used(), 4 * OBJECT_HEADER_SIZE);
+
+        // numbers holding an object's id do not keep it alive
+        let frame = Frame { stack: vec![VirtualObject::from(in_stack.id)], ..Default::default() };
+        assert_eq!(objects.collect(roots(&frame)), 4);
+        assert!(objects.is_empty());
+        assert_eq!(objects.used(), 0);
+    }
+
+    #[test]
+    /// Collects a long chain and a ring of objects many times over
+    fn object_heap_collect_stress() {
+        let mut objects = ObjectHeap::default();
+        let mut head = VirtualObject::new_empty(HType::Ref);
+        for value in 0..10_000u64 {
+            head = VirtualObject::from(objects.alloc(vec![VirtualObject::from(value), head]));
+            // garbage pointing into the live chain
+            objects.alloc(vec![head.clone()]);
+        }
+
+        let frame = Frame { local: vec![head.clone()], ..Default::default() };
+        assert_eq!(objects.collect(roots(&frame)), 10_000);
+        assert_eq!(objects.len(), 10_000);
+
+        // walking the chain finds every value in order
+        let mut reference = head.get_reference();
+        for value in (0..10_000u64).rev() {
+            let fields = objects.fields(reference).unwrap();
+            assert_eq!(fields[0], VirtualObject::from(value));
+            reference = fields[1].get_reference();
+        }
+        assert_eq!(reference.id, 0);
+
+        // closing the chain into a ring does not keep it alive without a root
+        let mut last = head.get_reference();
+        while let Ok(fields) = objects.fields(last) {
+            match fields[1].get_reference() {
+                next if next.id == 0 => break,
+                next => last = next,
+            }
+        }
+        objects.set_field(last, 1, head).unwrap();
+        assert_eq!(objects.collect(roots(&frame)), 0);
+        assert_eq!(objects.collect(Vec::new()), 10_000);
+        assert!(objects.is_empty());
+        assert_eq!(objects.used(), 0);
+
+        // ids are not reused
+        assert!(objects.alloc(Vec::new()).id > 20_000);
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::BTreeMap;
+use lib_heat_spec::h_type::{self, HType};
+use crate::trap::TrapKind;
+use crate::types::{VirtualAddress, VirtualObject, VirtualPointer, NULL};
+
+/// largest number of bytes a single allocation may hold, enforced even when
+/// `Constraints::max_memory` is unlimited
+pub const MAX_ALLOCATION: u64 = 1 << 30;
+
+/// A linear heap of typed allocations, addressed by `VirtualPointer`s
+///
+/// Every allocation holds a number of objects of one `HType` and takes its
+/// size plus one byte of address space, so the address one past its end still
+/// belongs to it. Addresses are handed out in increasing order and never
+/// reused, so pointers into freed allocations stay invalid.
+#[derive(Debug)]
+pub struct Heap {
+    /// live allocations by their first address
+    allocations: BTreeMap<VirtualAddress, Allocation>,
+
+    /// address of the next allocation
+    next: VirtualAddress,
+
+    /// bytes held by the live allocations
+    used: u64,
+}
+
+#[derive(Debug)]
+struct Allocation {
+    h_type: HType,
+    data: Vec<u8>,
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Heap { allocations: Default::default(), next: NULL + 1, used: 0 }
+    }
+}
+
+impl Heap {
+    /// Get the number of bytes held by the live allocations
+    pub fn used(&self) -> u64 {
+        self.used
+    }
+
+    /// Get the number of live allocations
+    pub fn len(&self) -> usize {
+        self.allocations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.allocations.is_empty()
+    }
+
+    /// Get the number of bytes an allocation of `count` objects of an `HType` holds
+    pub fn size_of(h_type: HType, count: u64) -> Result<u64, TrapKind> {
+        count.checked_mul(h_type::get_size(h_type) as u64).ok_or(TrapKind::Overflow)
+    }
+
+    /// Allocate `count` zeroed objects of an `HType` and get a pointer to the first one
+    ///
+    /// Fails with `TrapKind::OutOfMemory` if the allocation would hold more
+    /// than `MAX_ALLOCATION` bytes or the host can not provide them.
+    ///
+    /// ## Examples
+    /// ```
+    /// use lib_heat_spec::h_type::HType;
+    /// use libvirt::heap::Heap;
+    /// use libvirt::types::VirtualObject;
+    ///
+    /// let mut heap = Heap::default();
+    /// let pointer = heap.alloc(HType::U16, 4).unwrap();
+    /// assert_eq!(heap.used(), 8);
+    ///
+    /// let second = heap.offset(pointer, 1).unwrap();
+    /// heap.store(second, &VirtualObject::from(7u16)).unwrap();
+    /// assert_eq!(heap.load(second).unwrap(), VirtualObject::from(7u16));
+    /// assert_eq!(heap.load(pointer).unwrap(), VirtualObject::from(0u16));
+    /// ```
+    pub fn alloc(&mut self, h_type: HType, count: u64) -> Result<VirtualPointer, TrapKind> {
+        let size = Heap::size_of(h_type, count)?;
+        let next = self.next.checked_add(size).and_then(|end| end.checked_add(1)).ok_or(TrapKind::Overflow)?;
+        if size > MAX_ALLOCATION {
+            return Err(TrapKind::OutOfMemory);
+        }
+        let len = usize::try_from(size).map_err(|_| TrapKind::OutOfMemory)?;
+        let mut data = Vec::new();
+        data.try_reserve_exact(len).map_err(|_| TrapKind::OutOfMemory)?;
+        data.resize(len, 0);
+
+        let location = self.next;
+        self.allocations.insert(location, Allocation { h_type, data });
+        self.next = next;
+        self.used += size;
+        Ok(VirtualPointer { location, data_type: h_type })
+    }
+
+    /// Free the allocation a pointer points to the start of
+    pub fn free(&mut self, pointer: VirtualPointer) -> Result<(), TrapKind> {
+        let allocation = self.allocations.get(&pointer.location).ok_or(TrapKind::InvalidPointer)?;
+        expect_type(allocation, pointer)?;
+
+        let allocation = self.allocations.remove(&pointer.location).ok_or(TrapKind::InvalidPointer)?;
+        self.used -= allocation.data.len() as u64;
+        Ok(())
+    }
+
+    /// Move a pointer by `count` objects of its type
+    ///
+    /// The result may point anywhere inside the allocation or one past its end,
+    /// it is checked again when dereferenced.
+    pub fn offset(&self, pointer: VirtualPointer, count: u64) -> Result<VirtualPointer, TrapKind> {
+        let (start, allocation) = self.find(pointer)?;
+        let location = Heap::size_of(pointer.data_type, count)?
+            .checked_add(pointer.location)
+            .filter(|location| *location <= start + allocation.data.len() as u64)
+            .ok_or(TrapKind::OutOfBounds)?;
+        Ok(VirtualPointer { location, ..pointer })
+    }
+
+    /// Read the object a pointer points to
+    pub fn load(&self, pointer: VirtualPointer) -> Result<VirtualObject, TrapKind> {
+        let (start, allocation) = self.find(pointer)?;
+        let range = object_range(start, allocation, pointer)?;
+        Ok(VirtualObject::new(allocation.data[range].to_vec(), pointer.data_type))
+    }
+
+    /// Overwrite the object a pointer points to, the object must hold the pointer's type
+    pub fn store(&mut self, pointer: VirtualPointer, obj: &VirtualObject) -> Result<(), TrapKind> {
+        let (start, allocation) = self.find(pointer)?;
+        let range = object_range(start, allocation, pointer)?;
+        if obj.data_type != pointer.data_type {
+            return Err(TrapKind::TypeMismatch { expected: pointer.data_type, found: obj.data_type });
+        }
+        let allocation = self.allocations.get_mut(&start).ok_or(TrapKind::InvalidPointer)?;
+        allocation.data[range].copy_from_slice(&obj.data);
+        Ok(())
+    }
+
+    /// Get the live allocation a pointer points into and its first address
+    fn find(&self, pointer: VirtualPointer) -> Result<(VirtualAddress, &Allocation), TrapKind> {
+        let (start, allocation) = self.allocations.range(..=pointer.location)
+            .next_back()
+            .filter(|(start, allocation)| pointer.location - **start <= allocation.data.len() as u64)
+            .ok_or(TrapKind::InvalidPointer)?;
+        expect_type(allocation, pointer)?;
+        Ok((*start, allocation))
+    }
+}
+
+fn expect_type(allocation: &Allocation, pointer: VirtualPointer) -> Result<(), TrapKind> {
+    if allocation.h_type != pointer.data_type {
+        return Err(TrapKind::TypeMismatch { expected: allocation.h_type, found: pointer.data_type });
+    }
+    Ok(())
+}
+
+/// Get the bytes of an allocation holding the object a pointer points to
+fn object_range(start: VirtualAddress, allocation: &Allocation, pointer: VirtualPointer) -> Result<std::ops::Range<usize>, TrapKind> {
+    let size = h_type::get_size(pointer.data_type);
+    let offset = (pointer.location - start) as usize;
+    if !offset.is_multiple_of(size) || offset + size > allocation.data.len() {
+        return Err(TrapKind::OutOfBounds);
+    }
+    Ok(offset..offset + size)
+}
+
+#[cfg(test)]
+mod tests {
+    use lib_heat_spec::h_type::HType;
+    use crate::heap::{Heap, MAX_ALLOCATION};
+    use crate::trap::TrapKind;
+    use crate::types::{VirtualObject, VirtualPointer, NULL};
+
+    #[test]
+    fn heap_bounds() {
+        let mut heap = Heap::default();
+        let first = heap.alloc(HType::U32, 2).unwrap();
+        let second = heap.alloc(HType::U32, 1).unwrap();
+
+        let end = heap.offset(first, 2).unwrap();
+        assert_eq!(end.location, first.location + 8, "pointers may point one past the end");
+        assert_eq!(heap.load(end), Err(TrapKind::OutOfBounds));
+        assert_eq!(heap.store(end, &VirtualObject::from(1u32)), Err(TrapKind::OutOfBounds));
+        assert_eq!(heap.offset(first, 3), Err(TrapKind::OutOfBounds), "allocations do not run into each other");
+        assert_eq!(heap.offset(first, u64::MAX), Err(TrapKind::Overflow));
+
+        let misaligned = VirtualPointer { location: first.location + 1, ..first };
+        assert_eq!(heap.load(misaligned), Err(TrapKind::OutOfBounds));
+
+        heap.store(heap.offset(first, 1).unwrap(), &VirtualObject::from(5u32)).unwrap();
+        assert_eq!(heap.load(second).unwrap(), VirtualObject::from(0u32));
+        assert_eq!(heap.load(heap.offset(first, 1).unwrap()).unwrap(), VirtualObject::from(5u32));
+
+        let empty = heap.alloc(HType::U8, 0).unwrap();
+        assert_eq!(heap.load(empty), Err(TrapKind::OutOfBounds));
+        assert_ne!(empty, heap.alloc(HType::U8, 0).unwrap(), "empty allocations get their own address");
+
+        assert_eq!(heap.alloc(HType::U8, MAX_ALLOCATION + 1), Err(TrapKind::OutOfMemory));
+        assert_eq!(heap.alloc(HType::U64, MAX_ALLOCATION / 8 + 1), Err(TrapKind::OutOfMemory));
+    }
+
+    #[test]
+    fn heap_types() {
+        let mut heap = Heap::default();
+        let pointer = heap.alloc(HType::I16, 1).unwrap();
+
+        assert_eq!(
+            heap.store(pointer, &VirtualObject::from(1u16)),
+            Err(TrapKind::TypeMismatch { expected: HType::I16, found: HType::U16 }),
+        );
+        let retyped = VirtualPointer { data_type: HType::U16, ..pointer };
+        assert_eq!(heap.load(retyped), Err(TrapKind::TypeMismatch { expected: HType::I16, found: HType::U16 }));
+        assert_eq!(heap.free(retyped), Err(TrapKind::TypeMismatch { expected: HType::I16, found: HType::U16 }));
+    }
+
+    #[test]
+    fn heap_free() {
+        let mut heap = Heap::default();
+        let pointer = heap.alloc(HType::U64, 3).unwrap();
+        let other = heap.alloc(HType::Bool, 1).unwrap();
+        assert_eq!(heap.used(), 25);
+
+        assert_eq!(heap.free(heap.offset(pointer, 1).unwrap()), Err(TrapKind::InvalidPointer), "only the start can be freed");
+        heap.free(pointer).unwrap();
+        assert_eq!(heap.used(), 1);
+        assert_eq!(heap.len(), 1);
+
+        assert_eq!(heap.free(pointer), Err(TrapKind::InvalidPointer), "double free");
+        assert_eq!(heap.load(pointer), Err(TrapKind::InvalidPointer), "use after free");
+        assert_eq!(heap.load(VirtualPointer { location: NULL, data_type: HType::Bool }), Err(TrapKind::InvalidPointer));
+        assert_eq!(heap.load(other).unwrap(), VirtualObject::from(false));
+
+        let reused = heap.alloc(HType::U64, 3).unwrap();
+        assert!(reused.location > other.location, "addresses are not reused");
+    }
+}
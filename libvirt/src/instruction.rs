@@ -1,7 +1,7 @@
 use std::io::Cursor;
-use byteorder::{BigEndian, ReadBytesExt};
+use byteorder::{BigEndian, ByteOrder, ReadBytesExt};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Instruction {
     pub opcode: u64,
     pub arg1: u64,
@@ -18,11 +18,18 @@ impl Instruction {
         let arg2 = rdr.read_u64::<BigEndian>().unwrap();
         let arg3 = rdr.read_u64::<BigEndian>().unwrap();
 
-        return Instruction{
+        Instruction{
             opcode,
             arg1,
             arg2,
             arg3
         }
     }
-}
\ No newline at end of file
+
+    /// Encode the instruction into `lib_heat_spec::instruction::SIZE` bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0u8; lib_heat_spec::instruction::SIZE as usize];
+        BigEndian::write_u64_into(&[self.opcode, self.arg1, self.arg2, self.arg3], &mut bytes);
+        bytes
+    }
+}
@@ -1,278 +1,1076 @@
-use crate::constraints::Constraints;
-use crate::frame::Frame;
-use lib_heat_spec;
-use lib_heat_spec::h_type::HType;
+use std::collections::HashMap;
+use crate::cast;
+use crate::constraints::{Constraint, Constraints};
+use crate::frame::{Frame, FrameAddress, FRAME_HEADER_SIZE};
+use crate::fuel::Fuel;
+use crate::function::Function;
+use crate::gc::{self, ObjectHeap, OBJECT_HEADER_SIZE};
+use crate::heap::Heap;
+use crate::instruction::Instruction;
+use crate::trap::{Trap, TrapKind};
+use lib_heat_spec::frame::MAX_CALL_DEPTH;
+use lib_heat_spec::h_type::{self, HType};
 use lib_heat_spec::opcode;
-use crate::types::VirtualObject;
+use crate::types::{ObjectId, Primitive, VirtualObject};
+use crate::verifier;
+
+/// Evaluate `$body` with `$t` aliased to the Rust type of the integer type in
+/// the low nibble of an opcode, see `opcode::operand_type`
+macro_rules! integer_op {
+    ($opcode:expr, $t:ident => $body:expr) => {
+        match opcode::operand_type($opcode) {
+            Some(HType::U8) => integer_op!(@ $t = u8, $body),
+            Some(HType::U16) => integer_op!(@ $t = u16, $body),
+            Some(HType::U32) => integer_op!(@ $t = u32, $body),
+            Some(HType::U64) => integer_op!(@ $t = u64, $body),
+            Some(HType::I8) => integer_op!(@ $t = i8, $body),
+            Some(HType::I16) => integer_op!(@ $t = i16, $body),
+            Some(HType::I32) => integer_op!(@ $t = i32, $body),
+            Some(HType::I64) => integer_op!(@ $t = i64, $body),
+            _ => Err(TrapKind::IllegalOpcode),
+        }
+    };
+    (@ $t:ident = $type:ty, $body:expr) => {{
+        type $t = $type;
+        // casts like `val as u32` are no-ops for some of the types
+        #[allow(clippy::unnecessary_cast)]
+        let result = $body;
+        result
+    }};
+}
+
+/// Evaluate `$body` with `$t` aliased to the Rust type of the float type in
+/// the low nibble of an opcode, see `opcode::operand_type`
+macro_rules! float_op {
+    ($opcode:expr, $t:ident => $body:expr) => {
+        match opcode::operand_type($opcode) {
+            Some(HType::F32) => { type $t = f32; $body }
+            Some(HType::F64) => { type $t = f64; $body }
+            _ => Err(TrapKind::IllegalOpcode),
+        }
+    };
+}
+
+/// Evaluate `$body` with `$t` aliased to the Rust type of the integer or float
+/// type in the low nibble of an opcode, see `opcode::operand_type`
+macro_rules! number_op {
+    ($opcode:expr, $t:ident => $body:expr) => {
+        match opcode::operand_type($opcode) {
+            Some(HType::F32 | HType::F64) => float_op!($opcode, $t => $body),
+            _ => integer_op!($opcode, $t => $body),
+        }
+    };
+}
 
 pub struct Interpreter {
     pub constraints: Constraints,
+
+    /// functions callable with the CALL opcode, indexed by CALL's arg1
+    pub functions: Vec<Function>,
+
+    /// fuel burnt by executed instructions, execution is unbounded if `None`
+    pub fuel: Option<Fuel>,
+
+    /// memory allocated with ALLOC, shared by every frame the interpreter executes
+    pub heap: Heap,
+
+    /// objects created with NEW_OBJECT, shared by every frame the interpreter executes
+    pub objects: ObjectHeap,
+
+    /// references held by the frames the interpreter ran, counted per object
+    frame_roots: HashMap<FrameAddress, HashMap<ObjectId, usize>>,
+
+    /// the frame the interpreter ran last, whose memory and references it keeps count of
+    running: Option<FrameAddress>,
+
+    /// bytes held by the running frame, see `Frame::memory_used`
+    frame_memory: u64,
+}
+
+/// Allocations an instruction may still make, checked before objects are pushed
+/// so a `ConstraintViolation` leaves the frame untouched
+struct Budget {
+    /// bytes left under `Constraints::max_memory`, `None` if unlimited
+    memory: Option<u64>,
+    max_stack_allocation: u64,
+    /// calls that may still be nested below the active frame
+    calls: usize,
+    /// bytes the instruction added to the frames
+    allocated: u64,
+    /// bytes the instruction removed from the frames
+    freed: u64,
+    /// references the instruction added to the frames
+    referenced: Vec<ObjectId>,
+    /// references the instruction removed from the frames
+    released: Vec<ObjectId>,
+}
+
+impl Budget {
+    /// Reserve the bytes of an object pushed onto a frame
+    fn allocate(&mut self, obj: &VirtualObject) -> Result<(), TrapKind> {
+        self.allocate_bytes(obj.data.len() as u64)?;
+        self.referenced.extend(gc::references(std::slice::from_ref(obj)));
+        Ok(())
+    }
+
+    /// Reserve bytes held by the frames
+    fn allocate_bytes(&mut self, bytes: u64) -> Result<(), TrapKind> {
+        self.reserve(bytes)?;
+        self.allocated += bytes;
+        Ok(())
+    }
+
+    /// Count an object removed from a frame
+    fn free(&mut self, obj: &VirtualObject) {
+        self.freed += obj.data.len() as u64;
+        self.released.extend(gc::references(std::slice::from_ref(obj)));
+    }
+
+    /// Count an object replaced by another one of the same type, which needs no more bytes
+    fn replace(&mut self, old: &VirtualObject, new: &VirtualObject) {
+        self.free(old);
+        self.allocated += new.data.len() as u64;
+        self.referenced.extend(gc::references(std::slice::from_ref(new)));
+    }
+
+    /// Reserve bytes held outside of the frames, by the heap or the object heap
+    fn reserve(&mut self, bytes: u64) -> Result<(), TrapKind> {
+        if let Some(memory) = self.memory {
+            let remaining = memory.checked_sub(bytes)
+                .ok_or(TrapKind::ConstraintViolation(Constraint::MaxMemory))?;
+            self.memory = Some(remaining);
+        }
+        Ok(())
+    }
+}
+
+/// State of a frame after the interpreter stepped through it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StepResult {
+    /// the frame has more instructions to execute
+    Running,
+    /// `run_until` stopped before an instruction because its predicate matched
+    Paused,
+    /// the frame and all of its children have finished
+    Finished,
+}
+
+/// What the interpreter should do after an instruction
+enum Flow {
+    Continue,
+    /// push the frame onto the call stack
+    Call(Frame),
+    /// pop the active frame, handing these objects to the caller
+    Return(Vec<VirtualObject>),
 }
 
 impl Interpreter {
     pub fn new(constraints: Constraints) -> Interpreter {
-        return Interpreter { constraints };
+        Interpreter {
+            constraints,
+            functions: Default::default(),
+            fuel: None,
+            heap: Default::default(),
+            objects: Default::default(),
+            frame_roots: Default::default(),
+            running: None,
+            frame_memory: 0,
+        }
+    }
+
+    /// Get the remaining fuel, `None` if execution is unbounded
+    pub fn remaining_fuel(&self) -> Option<u64> {
+        self.fuel.as_ref().map(Fuel::remaining)
+    }
+
+    /// Top up the fuel, starting to meter execution if it was unbounded
+    pub fn add_fuel(&mut self, amount: u64) {
+        match &mut self.fuel {
+            Some(fuel) => fuel.add(amount),
+            None => self.fuel = Some(Fuel::new(amount)),
+        }
     }
 
     /// Execute a frame within an interpreter
-    pub fn execute_frame(&self, frame: &mut Frame) {
-        loop {
-            if frame.pc as usize == frame.instructions.len() { break; }
-            let i = frame.instructions.get(frame.pc as usize).unwrap().clone();
+    ///
+    /// Execution stops at the first faulting instruction and the fault is
+    /// returned as a `Trap`, leaving `frame.pc` on the faulting instruction.
+    /// Jump targets are not checked here, frames should be verified with
+    /// `verifier::verify` when they are loaded.
+    ///
+    /// Called functions run in child frames kept in `frame.children`, a trap
+    /// inside a call leaves the call stack in place. Objects returned by RET
+    /// from `frame` itself are pushed onto its operand stack for the host.
+    ///
+    /// Calling `execute_frame` again after a trap resumes at `frame.pc`,
+    /// e.g. after topping up the fuel.
+    pub fn execute_frame(&mut self, frame: &mut Frame) -> Result<(), Trap> {
+        self.run_until(frame, |_| false).map(|_| ())
+    }
 
-            match i.opcode {
-                opcode::NONE => {}
-                opcode::NEW_BOOL => {
-                    frame.allocate_in_stack(HType::Bool);
-                }
-                opcode::NEW_U8 => {
-                    frame.allocate_in_stack(HType::U8);
-                }
-                opcode::NEW_U16 => {
-                    frame.allocate_in_stack(HType::U16);
-                }
-                opcode::NEW_U32 => {
-                    frame.allocate_in_stack(HType::U32);
-                }
-                opcode::NEW_U64 => {
-                    frame.allocate_in_stack(HType::U64);
-                }
-                opcode::EQUAL => {
-                    let obj_1 = frame.get_front_in_stack(0).unwrap();
-                    let obj_2 = frame.get_front_in_stack(1).unwrap();
-                    let result = VirtualObject::from(obj_1 == obj_2);
-                    frame.operand_stack.push(result);
-                }
-                opcode::NOT => {
-                    let obj = frame.get_front_in_stack(0).unwrap();
-                    let result = VirtualObject::from(!obj.get_bool());
-                    frame.operand_stack.push(result)
-                }
-                opcode::AND => {
-                    let obj_1 = frame.get_front_in_stack(0).unwrap();
-                    let obj_2 = frame.get_front_in_stack(1).unwrap();
+    /// Free the objects no longer reachable from any frame, returns the number of freed objects
+    ///
+    /// The roots are the references held by `frames` and by every other frame
+    /// the interpreter ran, as of when it last stopped running. The interpreter
+    /// collects by itself when an instruction would exceed
+    /// `Constraints::max_memory`, the host may collect at any other time.
+    /// Frames the host changes after they stopped running have to be passed in
+    /// `frames`.
+    ///
+    /// A frame that finishes only keeps the objects referenced by its stacks,
+    /// and nothing once they hold no references. Frames dropped before they
+    /// finished, or whose results the host no longer needs, should be released
+    /// with `release_frame`.
+    ///
+    /// ## Examples
+    /// ```
+    /// use libvirt::constraints::Constraints;
+    /// use libvirt::frame::Frame;
+    /// use libvirt::instruction::Instruction;
+    /// use libvirt::interpreter::Interpreter;
+    /// use lib_heat_spec::opcode;
+    ///
+    /// let mut interpreter = Interpreter::new(Constraints::new_none());
+    /// let mut frame = Frame::default();
+    /// frame.instructions.push(Instruction { opcode: opcode::NEW_OBJECT, arg1: 0, arg2: 0, arg3: 0 });
+    /// interpreter.execute_frame(&mut frame).unwrap();
+    ///
+    /// // the finished frame still holds its result on the operand stack
+    /// assert_eq!(interpreter.collect_garbage(&[]), 0);
+    /// interpreter.release_frame(&frame);
+    /// assert_eq!(interpreter.collect_garbage(&[]), 1);
+    /// ```
+    pub fn collect_garbage(&mut self, frames: &[&Frame]) -> usize {
+        let mut roots: Vec<ObjectId> = frames.iter().flat_map(|frame| gc::roots(frame)).collect();
+        for (address, ids) in &self.frame_roots {
+            if !frames.iter().any(|frame| frame.address == *address) {
+                roots.extend(ids.keys());
+            }
+        }
+        self.objects.collect(roots)
+    }
 
-                    let result = VirtualObject::from(obj_1.get_bool() && obj_2.get_bool());
-                    frame.operand_stack.push(result)
-                }
-                opcode::OR => {
-                    let obj_1 = frame.get_front_in_stack(0).unwrap();
-                    let obj_2 = frame.get_front_in_stack(1).unwrap();
+    /// Stop keeping the objects a frame held alive, see `collect_garbage`
+    pub fn release_frame(&mut self, frame: &Frame) {
+        self.frame_roots.remove(&frame.address);
+        if self.running == Some(frame.address) {
+            self.running = None;
+        }
+    }
 
-                    let result = VirtualObject::from(obj_1.get_bool() || obj_2.get_bool());
-                    frame.operand_stack.push(result);
-                }
-                opcode::LOAD_BOOL => {
-                    let val = frame.get_mut_front_in_stack(0).unwrap();
-                    if val.data_type != HType::Bool {
-                        panic!("trying to load into a non bool object");
-                    }
+    /// Start counting the memory and references a frame holds
+    fn measure(&mut self, frame: &Frame) {
+        self.running = Some(frame.address);
+        self.frame_memory = frame.memory_used();
+        self.frame_roots.remove(&frame.address);
+        self.count_roots(frame.address, gc::roots(frame), Vec::new());
+    }
 
-                    val.set_bool(&(i.arg1 != 0));
-                }
-                opcode::LOAD_U8 => {
-                    let val = frame.get_mut_front_in_stack(0).unwrap();
-                    if val.data_type != HType::U8 {
-                        panic!("trying to load into a non u8 object");
-                    }
+    /// Only keep the objects referenced by the stacks of a finished frame
+    ///
+    /// Its locals and constants are no longer used, the frame is measured
+    /// again if it gets more instructions.
+    fn finish(&mut self, frame: &Frame) {
+        self.running = None;
+        self.frame_roots.remove(&frame.address);
+        let roots = gc::references(&frame.stack).chain(gc::references(&frame.operand_stack)).collect();
+        self.count_roots(frame.address, roots, Vec::new());
+    }
 
-                    val.set_u8(&(i.arg1 as u8));
-                }
-                opcode::LOAD_U16 => {
-                    let val = frame.get_mut_front_in_stack(0).unwrap();
-                    if val.data_type != HType::U16 {
-                        panic!("trying to load into a non u16 object");
-                    }
+    /// Apply the bytes an instruction allocated and freed to the memory of the running frame
+    fn count_memory(&mut self, allocated: u64, freed: u64) {
+        self.frame_memory = self.frame_memory.saturating_add(allocated).saturating_sub(freed);
+    }
 
-                    val.set_u16(&(i.arg1 as u16));
+    /// Apply the references an instruction added and removed to the roots of a frame
+    fn count_roots(&mut self, address: FrameAddress, referenced: Vec<ObjectId>, released: Vec<ObjectId>) {
+        let roots = self.frame_roots.entry(address).or_default();
+        for id in referenced {
+            *roots.entry(id).or_default() += 1;
+        }
+        for id in released {
+            if let Some(count) = roots.get_mut(&id) {
+                *count -= 1;
+                if *count == 0 {
+                    roots.remove(&id);
                 }
-                opcode::LOAD_U32 => {
-                    let val = frame.get_mut_front_in_stack(0).unwrap();
-                    if val.data_type != HType::U32 {
-                        panic!("trying to load into a non u32 object");
-                    }
+            }
+        }
+        if roots.is_empty() {
+            self.frame_roots.remove(&address);
+        }
+    }
 
-                    val.set_u32(&(i.arg1 as u32));
-                }
-                opcode::LOAD_U64 => {
-                    let val = frame.get_mut_front_in_stack(0).unwrap();
-                    if val.data_type != HType::U64 {
-                        panic!("trying to load into a non u64 object");
-                    }
+    /// Step through a frame until it finishes or `predicate` matches
+    ///
+    /// The predicate is checked before every instruction, returning
+    /// `StepResult::Paused` when it matches. The frame keeps its `pc` and
+    /// call stack, so execution resumes where it paused on the next call.
+    ///
+    /// ## Examples
+    /// ```
+    /// use libvirt::constraints::Constraints;
+    /// use libvirt::frame::Frame;
+    /// use libvirt::instruction::Instruction;
+    /// use libvirt::interpreter::{Interpreter, StepResult};
+    /// use lib_heat_spec::opcode;
+    ///
+    /// let mut interpreter = Interpreter::new(Constraints::new_none());
+    /// let mut frame = Frame::default();
+    /// for _ in 0..4 {
+    ///     frame.instructions.push(Instruction { opcode: opcode::NEW_U8, arg1: 0, arg2: 0, arg3: 0 });
+    /// }
+    ///
+    /// // break before the instruction at index 2
+    /// let result = interpreter.run_until(&mut frame, |frame| frame.active_frame().pc == 2);
+    /// assert_eq!(result, Ok(StepResult::Paused));
+    /// assert_eq!(frame.stack.len(), 2);
+    ///
+    /// // resume until the end
+    /// assert_eq!(interpreter.run_until(&mut frame, |_| false), Ok(StepResult::Finished));
+    /// assert_eq!(frame.stack.len(), 4);
+    /// ```
+    pub fn run_until<P>(&mut self, frame: &mut Frame, mut predicate: P) -> Result<StepResult, Trap>
+    where
+        P: FnMut(&Frame) -> bool,
+    {
+        if !frame.is_finished() {
+            self.measure(frame);
+        }
+        let result = loop {
+            if frame.is_finished() {
+                break Ok(StepResult::Finished);
+            }
+            if predicate(frame) {
+                break Ok(StepResult::Paused);
+            }
+            if let Err(trap) = self.step_frame(frame) {
+                break Err(trap);
+            }
+        };
+        if result == Ok(StepResult::Finished) {
+            self.finish(frame);
+        }
+        result
+    }
 
-                    val.set_u64(&(i.arg1 as u64));
-                }
-                opcode::STORE => {
-                    let operand: VirtualObject = frame.get_front_in_op_stack(0).unwrap().clone();
-                    frame.stack.push(operand);
-                }
-                opcode::LOCAL_LOAD => {
-                    let obj = frame.get_front_in_stack(0).unwrap();
-                    let cloned_obj = obj.clone();
-                    let index = i.arg1;
-                    frame.local.insert(index as usize, cloned_obj);
-                }
-                opcode::ADD_U8 => {
-                    let val1 = frame.get_front_in_stack(0).unwrap();
-                    let val2 = frame.get_front_in_stack(1).unwrap();
-                    let mut res_obj = VirtualObject::new_empty(HType::U8);
-                    res_obj.set_u8(&(val1.get_u8() + val2.get_u8()));
-                    frame.operand_stack.push(res_obj);
-                }
-                opcode::ADD_U16 => {
-                    let val1 = frame.get_front_in_stack(0).unwrap();
-                    let val2 = frame.get_front_in_stack(1).unwrap();
-                    let mut res_obj = VirtualObject::new_empty(HType::U16);
-                    res_obj.set_u16(&(val1.get_u16() + val2.get_u16()));
-                    frame.operand_stack.push(res_obj);
-                }
-                opcode::ADD_U32 => {
-                    let val1 = frame.get_front_in_stack(0).unwrap();
-                    let val2 = frame.get_front_in_stack(1).unwrap();
-                    let mut res_obj = VirtualObject::new_empty(HType::U32);
-                    res_obj.set_u32(&(val1.get_u32() + val2.get_u32()));
-                    frame.operand_stack.push(res_obj);
-                }
-                opcode::ADD_U64 => {
-                    let val1 = frame.get_front_in_stack(0).unwrap();
-                    let val2 = frame.get_front_in_stack(1).unwrap();
-                    let mut res_obj = VirtualObject::new_empty(HType::U64);
-                    res_obj.set_u64(&(val1.get_u64() + val2.get_u64()));
-                    frame.operand_stack.push(res_obj);
-                }
-                opcode::SUB_U8 => {
-                    let val1 = frame.get_front_in_stack(0).unwrap();
-                    let val2 = frame.get_front_in_stack(1).unwrap();
-                    let mut res_obj = VirtualObject::new_empty(HType::U8);
-                    res_obj.set_u8(&(val1.get_u8() - val2.get_u8()));
-                    frame.operand_stack.push(res_obj);
-                }
-                opcode::SUB_U16 => {
-                    let val1 = frame.get_front_in_stack(0).unwrap();
-                    let val2 = frame.get_front_in_stack(1).unwrap();
-                    let mut res_obj = VirtualObject::new_empty(HType::U16);
-                    res_obj.set_u16(&(val1.get_u16() - val2.get_u16()));
-                    frame.operand_stack.push(res_obj);
-                }
-                opcode::SUB_U32 => {
-                    let val1 = frame.get_front_in_stack(0).unwrap();
-                    let val2 = frame.get_front_in_stack(1).unwrap();
-                    let mut res_obj = VirtualObject::new_empty(HType::U32);
-                    res_obj.set_u32(&(val1.get_u32() - val2.get_u32()));
-                    frame.operand_stack.push(res_obj);
-                }
-                opcode::SUB_U64 => {
-                    let val1 = frame.get_front_in_stack(0).unwrap();
-                    let val2 = frame.get_front_in_stack(1).unwrap();
-                    let mut res_obj = VirtualObject::new_empty(HType::U64);
-                    res_obj.set_u64(&(val1.get_u64() - val2.get_u64()));
-                    frame.operand_stack.push(res_obj);
-                }
-                opcode::DIV_U8 => {
-                    let val1 = frame.get_front_in_stack(0).unwrap();
-                    let val2 = frame.get_front_in_stack(1).unwrap();
-                    let mut res_obj = VirtualObject::new_empty(HType::U8);
-                    res_obj.set_u8(&(val1.get_u8() / val2.get_u8()));
-                    frame.operand_stack.push(res_obj);
-                }
-                opcode::DIV_U16 => {
-                    let val1 = frame.get_front_in_stack(0).unwrap();
-                    let val2 = frame.get_front_in_stack(1).unwrap();
-                    let mut res_obj = VirtualObject::new_empty(HType::U16);
-                    res_obj.set_u16(&(val1.get_u16() / val2.get_u16()));
-                    frame.operand_stack.push(res_obj);
-                }
-                opcode::DIV_U32 => {
-                    let val1 = frame.get_front_in_stack(0).unwrap();
-                    let val2 = frame.get_front_in_stack(1).unwrap();
-                    let mut res_obj = VirtualObject::new_empty(HType::U32);
-                    res_obj.set_u32(&(val1.get_u32() / val2.get_u32()));
-                    frame.operand_stack.push(res_obj);
+    /// Execute exactly one instruction of the innermost active frame
+    ///
+    /// Stepping past the end of a called function returns from it without
+    /// executing an instruction. Returns `StepResult::Finished` once there
+    /// is nothing left to execute.
+    ///
+    /// The memory and references a frame holds are measured when `run_until`
+    /// starts and when `step` gets another frame than the one the interpreter
+    /// ran last, from then on the interpreter counts what instructions add and
+    /// remove. Objects the host adds between two steps of the same frame are
+    /// not counted until it is measured again.
+    pub fn step(&mut self, frame: &mut Frame) -> Result<StepResult, Trap> {
+        if frame.is_finished() {
+            self.finish(frame);
+            return Ok(StepResult::Finished);
+        }
+        if self.running != Some(frame.address) {
+            self.measure(frame);
+        }
+        let result = self.step_frame(frame);
+        if result == Ok(StepResult::Finished) {
+            self.finish(frame);
+        }
+        result
+    }
+
+    fn step_frame(&mut self, frame: &mut Frame) -> Result<StepResult, Trap> {
+        let mut budget = self.budget(frame);
+        let active = frame.active_frame_mut();
+        let flow = match active.code().get(active.pc as usize).cloned() {
+            Some(i) => {
+                if let Some(fuel) = &mut self.fuel {
+                    if !fuel.burn(i.opcode) {
+                        return Err(Trap::new(TrapKind::OutOfFuel, active.pc, i));
+                    }
                 }
-                opcode::DIV_U64 => {
-                    let val1 = frame.get_front_in_stack(0).unwrap();
-                    let val2 = frame.get_front_in_stack(1).unwrap();
-                    let mut res_obj = VirtualObject::new_empty(HType::U64);
-                    res_obj.set_u64(&(val1.get_u64() / val2.get_u64()));
-                    frame.operand_stack.push(res_obj);
+                let mut result = self.execute_instruction(active, &i, &mut budget);
+                // the trap left the frame untouched, so the instruction can run again after collecting
+                if matches!(result, Err(TrapKind::ConstraintViolation(Constraint::MaxMemory))) && self.collect_garbage(&[frame]) != 0 {
+                    budget = self.budget(frame);
+                    result = self.execute_instruction(frame.active_frame_mut(), &i, &mut budget);
                 }
-                opcode::MUL_U8 => {
-                    let val1 = frame.get_front_in_stack(0).unwrap();
-                    let val2 = frame.get_front_in_stack(1).unwrap();
-                    let mut res_obj = VirtualObject::new_empty(HType::U8);
-                    res_obj.set_u8(&(val1.get_u8() * val2.get_u8()));
-                    frame.operand_stack.push(res_obj);
+                match result {
+                    Ok(flow) => {
+                        self.count_memory(budget.allocated, budget.freed);
+                        self.count_roots(frame.address, budget.referenced, budget.released);
+                        flow
+                    }
+                    Err(kind) => {
+                        if let Some(fuel) = &mut self.fuel {
+                            fuel.refund(i.opcode);
+                        }
+                        return Err(Trap::new(kind, frame.active_frame().pc, i));
+                    }
                 }
-                opcode::MUL_U16 => {
-                    let val1 = frame.get_front_in_stack(0).unwrap();
-                    let val2 = frame.get_front_in_stack(1).unwrap();
-                    let mut res_obj = VirtualObject::new_empty(HType::U16);
-                    res_obj.set_u16(&(val1.get_u16() * val2.get_u16()));
-                    frame.operand_stack.push(res_obj);
+            }
+            // running off the end of a function returns nothing to the caller
+            None if !frame.children.is_empty() => Flow::Return(Vec::new()),
+            None => return Ok(StepResult::Finished),
+        };
+
+        match flow {
+            Flow::Continue => {}
+            Flow::Call(child) => frame.children.push(child),
+            Flow::Return(values) => {
+                match frame.children.pop() {
+                    Some(child) => {
+                        self.count_memory(0, FRAME_HEADER_SIZE + child.memory_used());
+                        self.count_roots(frame.address, Vec::new(), gc::roots(&child));
+                    }
+                    None => frame.pc = frame.code().len() as u64,
                 }
-                opcode::MUL_U32 => {
-                    let val1 = frame.get_front_in_stack(0).unwrap();
-                    let val2 = frame.get_front_in_stack(1).unwrap();
-                    let mut res_obj = VirtualObject::new_empty(HType::U32);
-                    res_obj.set_u32(&(val1.get_u32() * val2.get_u32()));
-                    frame.operand_stack.push(res_obj);
+                frame.active_frame_mut().operand_stack.extend(values);
+            }
+        }
+
+        if frame.is_finished() {
+            return Ok(StepResult::Finished);
+        }
+        Ok(StepResult::Running)
+    }
+
+    /// Get what the next instruction may allocate without exceeding the constraints
+    fn budget(&self, frame: &Frame) -> Budget {
+        let memory = match self.constraints.max_memory {
+            0 => None,
+            max_memory => {
+                let used = self.frame_memory.saturating_add(self.heap.used()).saturating_add(self.objects.used());
+                Some(max_memory.saturating_sub(used))
+            }
+        };
+        let calls = (MAX_CALL_DEPTH as usize).saturating_sub(frame.children.len());
+        Budget {
+            memory,
+            max_stack_allocation: self.constraints.max_stack_allocation,
+            calls,
+            allocated: 0,
+            freed: 0,
+            referenced: Vec::new(),
+            released: Vec::new(),
+        }
+    }
+
+    /// Execute a single instruction and move `frame.pc` to the next one
+    fn execute_instruction(&mut self, frame: &mut Frame, i: &Instruction, budget: &mut Budget) -> Result<Flow, TrapKind> {
+        match i.opcode {
+            opcode::NONE => {}
+            opcode::JMP => {
+                frame.pc = i.arg1;
+                return Ok(Flow::Continue);
+            }
+            opcode::JMP_IF_TRUE | opcode::JMP_IF_FALSE => {
+                let condition = pop_operand(frame, budget, HType::Bool)?.get_bool();
+                if condition == (i.opcode == opcode::JMP_IF_TRUE) {
+                    frame.pc = i.arg1;
+                    return Ok(Flow::Continue);
                 }
-                opcode::MUL_U64 => {
-                    let val1 = frame.get_front_in_stack(0).unwrap();
-                    let val2 = frame.get_front_in_stack(1).unwrap();
-                    let mut res_obj = VirtualObject::new_empty(HType::U64);
-                    res_obj.set_u64(&(val1.get_u64() * val2.get_u64()));
-                    frame.operand_stack.push(res_obj);
+            }
+            opcode::CALL => {
+                let function = self.functions.get(i.arg1 as usize).ok_or(TrapKind::UndefinedFunction)?;
+                if budget.calls == 0 {
+                    return Err(TrapKind::CallStackOverflow);
                 }
-                opcode::PWR_U8 => {
-                    let val1 = frame.get_front_in_stack(0).unwrap();
-                    let val2 = frame.get_front_in_stack(1).unwrap();
-                    let mut res_obj = VirtualObject::new_empty(HType::U8);
-                    res_obj.set_u8(&(val1.get_u8() ^ val2.get_u8()));
-                    frame.operand_stack.push(res_obj);
+                budget.allocate_bytes(FRAME_HEADER_SIZE)?;
+                for constant in &function.constants {
+                    budget.allocate(constant)?;
                 }
-                opcode::PWR_U16 => {
-                    let val1 = frame.get_front_in_stack(0).unwrap();
-                    let val2 = frame.get_front_in_stack(1).unwrap();
-                    let mut res_obj = VirtualObject::new_empty(HType::U16);
-                    res_obj.set_u16(&(val1.get_u16() ^ val2.get_u16()));
-                    frame.operand_stack.push(res_obj);
+                for h_type in &function.locals {
+                    budget.allocate(&VirtualObject::new_empty(*h_type))?;
                 }
-                opcode::PWR_U32 => {
-                    let val1 = frame.get_front_in_stack(0).unwrap();
-                    let val2 = frame.get_front_in_stack(1).unwrap();
-                    let mut res_obj = VirtualObject::new_empty(HType::U32);
-                    res_obj.set_u32(&(val1.get_u32() ^ val2.get_u32()));
-                    frame.operand_stack.push(res_obj);
+                let params = pop_many(frame, function.param_count as usize)?;
+
+                let child = function.frame(params);
+                frame.pc += 1;
+                return Ok(Flow::Call(child));
+            }
+            opcode::RET => {
+                let values = pop_many(frame, i.arg1 as usize)?;
+                return Ok(Flow::Return(values));
+            }
+            opcode::NEW_BOOL => {
+                push_stack(frame, budget, VirtualObject::new_empty(HType::Bool))?;
+            }
+            opcode::NEW_U8 => {
+                push_stack(frame, budget, VirtualObject::new_empty(HType::U8))?;
+            }
+            opcode::NEW_U16 => {
+                push_stack(frame, budget, VirtualObject::new_empty(HType::U16))?;
+            }
+            opcode::NEW_U32 => {
+                push_stack(frame, budget, VirtualObject::new_empty(HType::U32))?;
+            }
+            opcode::NEW_U64 => {
+                push_stack(frame, budget, VirtualObject::new_empty(HType::U64))?;
+            }
+            opcode::NEW_I8 => {
+                push_stack(frame, budget, VirtualObject::new_empty(HType::I8))?;
+            }
+            opcode::NEW_I16 => {
+                push_stack(frame, budget, VirtualObject::new_empty(HType::I16))?;
+            }
+            opcode::NEW_I32 => {
+                push_stack(frame, budget, VirtualObject::new_empty(HType::I32))?;
+            }
+            opcode::NEW_I64 => {
+                push_stack(frame, budget, VirtualObject::new_empty(HType::I64))?;
+            }
+            opcode::NEW_F32 => {
+                push_stack(frame, budget, VirtualObject::new_empty(HType::F32))?;
+            }
+            opcode::NEW_F64 => {
+                push_stack(frame, budget, VirtualObject::new_empty(HType::F64))?;
+            }
+            opcode::EQUAL => {
+                let obj_1 = front(frame, 0)?;
+                let obj_2 = front(frame, 1)?;
+                // NaN is not equal to itself and -0.0 equals 0.0
+                let result = VirtualObject::from(match (obj_1.data_type, obj_2.data_type) {
+                    (HType::F32, HType::F32) => obj_1.get_f32() == obj_2.get_f32(),
+                    (HType::F64, HType::F64) => obj_1.get_f64() == obj_2.get_f64(),
+                    _ => obj_1 == obj_2,
+                });
+                consume(frame, budget, 2, result)?;
+            }
+            opcode::NOT => {
+                let obj = operand(frame, 0, HType::Bool)?;
+                let result = VirtualObject::from(!obj.get_bool());
+                consume(frame, budget, 1, result)?;
+            }
+            opcode::AND => {
+                let obj_1 = operand(frame, 0, HType::Bool)?;
+                let obj_2 = operand(frame, 1, HType::Bool)?;
+
+                let result = VirtualObject::from(obj_1.get_bool() && obj_2.get_bool());
+                consume(frame, budget, 2, result)?;
+            }
+            opcode::OR => {
+                let obj_1 = operand(frame, 0, HType::Bool)?;
+                let obj_2 = operand(frame, 1, HType::Bool)?;
+
+                let result = VirtualObject::from(obj_1.get_bool() || obj_2.get_bool());
+                consume(frame, budget, 2, result)?;
+            }
+            opcode::XOR => {
+                let obj_1 = operand(frame, 0, HType::Bool)?;
+                let obj_2 = operand(frame, 1, HType::Bool)?;
+
+                let result = VirtualObject::from(obj_1.get_bool() ^ obj_2.get_bool());
+                consume(frame, budget, 2, result)?;
+            }
+            opcode::LOAD_BOOL => {
+                let val = operand_mut(frame, 0, HType::Bool)?;
+                val.set_bool(&(i.arg1 != 0));
+            }
+            opcode::LOAD_U8 => {
+                let val = operand_mut(frame, 0, HType::U8)?;
+                val.set_u8(&(i.arg1 as u8));
+            }
+            opcode::LOAD_U16 => {
+                let val = operand_mut(frame, 0, HType::U16)?;
+                val.set_u16(&(i.arg1 as u16));
+            }
+            opcode::LOAD_U32 => {
+                let val = operand_mut(frame, 0, HType::U32)?;
+                val.set_u32(&(i.arg1 as u32));
+            }
+            opcode::LOAD_U64 => {
+                let val = operand_mut(frame, 0, HType::U64)?;
+                val.set_u64(&i.arg1);
+            }
+            opcode::LOAD_I8 => {
+                let val = operand_mut(frame, 0, HType::I8)?;
+                val.set_i8(&(i.arg1 as i8));
+            }
+            opcode::LOAD_I16 => {
+                let val = operand_mut(frame, 0, HType::I16)?;
+                val.set_i16(&(i.arg1 as i16));
+            }
+            opcode::LOAD_I32 => {
+                let val = operand_mut(frame, 0, HType::I32)?;
+                val.set_i32(&(i.arg1 as i32));
+            }
+            opcode::LOAD_I64 => {
+                let val = operand_mut(frame, 0, HType::I64)?;
+                val.set_i64(&(i.arg1 as i64));
+            }
+            opcode::LOAD_F32 => {
+                let val = operand_mut(frame, 0, HType::F32)?;
+                val.set_f32(&f32::from_bits(i.arg1 as u32));
+            }
+            opcode::LOAD_F64 => {
+                let val = operand_mut(frame, 0, HType::F64)?;
+                val.set_f64(&f64::from_bits(i.arg1));
+            }
+            opcode::LOAD_CONST => {
+                let constant = frame.constant_pool.get(i.arg1 as usize).ok_or(TrapKind::ConstantOutOfRange)?.clone();
+                push_stack(frame, budget, constant)?;
+            }
+            opcode::STORE => {
+                frame.get_front_in_op_stack(0).ok_or(TrapKind::StackUnderflow)?;
+                check_stack_allocation(frame, budget)?;
+                let obj = frame.operand_stack.pop().ok_or(TrapKind::StackUnderflow)?;
+                frame.stack.push(obj);
+            }
+            opcode::STORE_OPERAND => {
+                let obj = frame.stack.pop().ok_or(TrapKind::StackUnderflow)?;
+                frame.operand_stack.push(obj);
+            }
+            opcode::LOCAL_GET => {
+                let obj = frame.local.get(i.arg1 as usize).ok_or(TrapKind::LocalOutOfRange)?.clone();
+                push_stack(frame, budget, obj)?;
+            }
+            opcode::LOCAL_SET | opcode::LOCAL_TEE => {
+                let h_type = frame.local.get(i.arg1 as usize).ok_or(TrapKind::LocalOutOfRange)?.data_type;
+                let obj = match i.opcode {
+                    opcode::LOCAL_SET => pop_operand(frame, budget, h_type)?,
+                    _ => operand(frame, 0, h_type)?.clone(),
+                };
+                let old = std::mem::replace(&mut frame.local[i.arg1 as usize], obj);
+                budget.replace(&old, &frame.local[i.arg1 as usize]);
+            }
+            opcode::ALLOC => {
+                // the collector does not trace references stored in the linear heap
+                let h_type = verifier::type_arg(i.arg1).filter(|h_type| *h_type != HType::Ref).ok_or(TrapKind::InvalidType)?;
+                let count = operand(frame, 0, HType::U64)?.get_u64();
+                budget.reserve(Heap::size_of(h_type, count)?)?;
+                budget.allocate(&VirtualObject::new_empty(HType::Ptr))?;
+
+                let pointer = self.heap.alloc(h_type, count)?;
+                drop_many(frame, budget, 1)?;
+                frame.operand_stack.push(VirtualObject::from(pointer));
+            }
+            opcode::FREE => {
+                let pointer = operand(frame, 0, HType::Ptr)?.get_pointer();
+                self.heap.free(pointer)?;
+                drop_many(frame, budget, 1)?;
+            }
+            opcode::LOAD_PTR => {
+                let pointer = operand(frame, 0, HType::Ptr)?.get_pointer();
+                let obj = self.heap.load(pointer)?;
+                consume(frame, budget, 1, obj)?;
+            }
+            opcode::STORE_PTR => {
+                let pointer = operand(frame, 1, HType::Ptr)?.get_pointer();
+                self.heap.store(pointer, front(frame, 0)?)?;
+                drop_many(frame, budget, 2)?;
+            }
+            opcode::PTR_ADD => {
+                let count = operand(frame, 0, HType::U64)?.get_u64();
+                let pointer = operand(frame, 1, HType::Ptr)?.get_pointer();
+                let moved = self.heap.offset(pointer, count)?;
+                consume(frame, budget, 2, VirtualObject::from(moved))?;
+            }
+            opcode::NEW_OBJECT => {
+                let at = usize::try_from(i.arg1).ok()
+                    .and_then(|count| frame.stack.len().checked_sub(count))
+                    .ok_or(TrapKind::StackUnderflow)?;
+                budget.reserve(OBJECT_HEADER_SIZE)?;
+                budget.allocate(&VirtualObject::new_empty(HType::Ref))?;
+
+                let fields = frame.stack.split_off(at);
+                fields.iter().for_each(|field| budget.free(field));
+                let reference = self.objects.alloc(fields);
+                budget.referenced.push(reference.id);
+                frame.operand_stack.push(VirtualObject::from(reference));
+            }
+            opcode::GET_FIELD => {
+                let reference = operand(frame, 0, HType::Ref)?.get_reference();
+                let field = self.objects.field(reference, field_index(i.arg1))?.clone();
+                consume(frame, budget, 1, field)?;
+            }
+            opcode::SET_FIELD => {
+                let reference = operand(frame, 1, HType::Ref)?.get_reference();
+                let obj = front(frame, 0)?.clone();
+                self.objects.set_field(reference, field_index(i.arg1), obj)?;
+                drop_many(frame, budget, 2)?;
+            }
+            opcode::POP | opcode::POP_OPERAND => {
+                let obj = stack_of(frame, i.opcode).pop().ok_or(TrapKind::StackUnderflow)?;
+                budget.free(&obj);
+            }
+            opcode::SWAP | opcode::SWAP_OPERAND => {
+                let stack = stack_of(frame, i.opcode);
+                let at = stack.len().checked_sub(2).ok_or(TrapKind::StackUnderflow)?;
+                stack.swap(at, at + 1);
+            }
+            opcode::ROT | opcode::ROT_OPERAND => {
+                let stack = stack_of(frame, i.opcode);
+                let at = stack.len().checked_sub(3).ok_or(TrapKind::StackUnderflow)?;
+                let obj = stack.remove(at);
+                stack.push(obj);
+            }
+            opcode::DUP | opcode::OVER | opcode::PICK | opcode::DUP_OPERAND | opcode::OVER_OPERAND | opcode::PICK_OPERAND => {
+                let depth = match i.opcode {
+                    opcode::DUP | opcode::DUP_OPERAND => 0,
+                    opcode::OVER | opcode::OVER_OPERAND => 1,
+                    _ => i.arg1,
+                };
+                let stack = stack_of(frame, i.opcode);
+                let obj = usize::try_from(depth).ok()
+                    .and_then(|depth| stack.iter().rev().nth(depth))
+                    .ok_or(TrapKind::StackUnderflow)?
+                    .clone();
+                match i.opcode {
+                    opcode::DUP | opcode::OVER | opcode::PICK => push_stack(frame, budget, obj)?,
+                    _ => push_operand(frame, budget, obj)?,
                 }
-                opcode::PWR_U64 => {
-                    let val1 = frame.get_front_in_stack(0).unwrap();
-                    let val2 = frame.get_front_in_stack(1).unwrap();
-                    let mut res_obj = VirtualObject::new_empty(HType::U64);
-                    res_obj.set_u64(&(val1.get_u64() ^ val2.get_u64()));
-                    frame.operand_stack.push(res_obj);
+            }
+            opcode::CAST_WRAP | opcode::CAST_CHECKED => {
+                let target = verifier::type_arg(i.arg1).ok_or(TrapKind::InvalidType)?;
+                let obj = front(frame, 0)?;
+                // pointers only come from ALLOC and PTR_ADD, references from NEW_OBJECT
+                if h_type::is_handle(target) || h_type::is_handle(obj.data_type) {
+                    return Err(TrapKind::InvalidType);
                 }
-                _ => {}
+                let result = match i.opcode {
+                    opcode::CAST_WRAP => cast::wrap(obj, target),
+                    _ => cast::checked(obj, target).ok_or(TrapKind::LossyCast)?,
+                };
+                consume(frame, budget, 1, result)?;
             }
-            frame.pc += 1;
+            opcode::ADD_U8..=opcode::ADD_I64 | opcode::ADD_CHECKED_U8..=opcode::ADD_CHECKED_I64 => integer_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| val1.checked_add(val2).ok_or(TrapKind::Overflow))
+            })?,
+            opcode::ADD_WRAP_U8..=opcode::ADD_WRAP_I64 => integer_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| Ok(val1.wrapping_add(val2)))
+            })?,
+            opcode::ADD_SAT_U8..=opcode::ADD_SAT_I64 => integer_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| Ok(val1.saturating_add(val2)))
+            })?,
+            opcode::SUB_U8..=opcode::SUB_I64 | opcode::SUB_CHECKED_U8..=opcode::SUB_CHECKED_I64 => integer_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| val1.checked_sub(val2).ok_or(TrapKind::Overflow))
+            })?,
+            opcode::SUB_WRAP_U8..=opcode::SUB_WRAP_I64 => integer_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| Ok(val1.wrapping_sub(val2)))
+            })?,
+            opcode::SUB_SAT_U8..=opcode::SUB_SAT_I64 => integer_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| Ok(val1.saturating_sub(val2)))
+            })?,
+            opcode::MUL_U8..=opcode::MUL_I64 | opcode::MUL_CHECKED_U8..=opcode::MUL_CHECKED_I64 => integer_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| val1.checked_mul(val2).ok_or(TrapKind::Overflow))
+            })?,
+            opcode::MUL_WRAP_U8..=opcode::MUL_WRAP_I64 => integer_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| Ok(val1.wrapping_mul(val2)))
+            })?,
+            opcode::MUL_SAT_U8..=opcode::MUL_SAT_I64 => integer_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| Ok(val1.saturating_mul(val2)))
+            })?,
+            opcode::DIV_U8..=opcode::DIV_I64 | opcode::DIV_CHECKED_U8..=opcode::DIV_CHECKED_I64 => integer_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| {
+                    if val2 == 0 {
+                        return Err(TrapKind::DivisionByZero);
+                    }
+                    // only MIN / -1 overflows
+                    val1.checked_div(val2).ok_or(TrapKind::Overflow)
+                })
+            })?,
+            opcode::DIV_WRAP_U8..=opcode::DIV_WRAP_I64 => integer_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| {
+                    if val2 == 0 {
+                        return Err(TrapKind::DivisionByZero);
+                    }
+                    Ok(val1.wrapping_div(val2))
+                })
+            })?,
+            opcode::DIV_SAT_U8..=opcode::DIV_SAT_I64 => integer_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| {
+                    if val2 == 0 {
+                        return Err(TrapKind::DivisionByZero);
+                    }
+                    Ok(val1.saturating_div(val2))
+                })
+            })?,
+            opcode::REM_U8..=opcode::REM_I64 => integer_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| {
+                    if val2 == 0 {
+                        return Err(TrapKind::DivisionByZero);
+                    }
+                    Ok(val1.wrapping_rem(val2))
+                })
+            })?,
+            opcode::MOD_U8..=opcode::MOD_I64 => integer_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| {
+                    if val2 == 0 {
+                        return Err(TrapKind::DivisionByZero);
+                    }
+                    Ok(val1.wrapping_rem_euclid(val2))
+                })
+            })?,
+            opcode::DIVREM_U8..=opcode::DIVREM_I64 => integer_op!(i.opcode, T => {
+                let val1 = T::get(operand(frame, 0, T::H_TYPE)?);
+                let val2 = T::get(operand(frame, 1, T::H_TYPE)?);
+                if val2 == 0 {
+                    return Err(TrapKind::DivisionByZero);
+                }
+                let quotient = VirtualObject::from(val1.checked_div(val2).ok_or(TrapKind::Overflow)?);
+                let remainder = VirtualObject::from(val1.wrapping_rem(val2));
+
+                // both results fit the budget or neither is pushed
+                budget.allocate(&remainder)?;
+                consume(frame, budget, 2, quotient)?;
+                frame.operand_stack.push(remainder);
+                Ok(())
+            })?,
+            opcode::PWR_U8..=opcode::PWR_I64 | opcode::PWR_CHECKED_U8..=opcode::PWR_CHECKED_I64 => integer_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| {
+                    val1.checked_pow(exponent(val2 as i128)?).ok_or(TrapKind::Overflow)
+                })
+            })?,
+            opcode::PWR_WRAP_U8..=opcode::PWR_WRAP_I64 => integer_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| {
+                    let exponent = u64::try_from(val2 as i128).map_err(|_| TrapKind::NegativeExponent)?;
+                    Ok(wrapping_pow(val1, exponent, 1, T::wrapping_mul))
+                })
+            })?,
+            opcode::PWR_SAT_U8..=opcode::PWR_SAT_I64 => integer_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| Ok(val1.saturating_pow(exponent(val2 as i128)?)))
+            })?,
+            opcode::PWR_F32 | opcode::PWR_F64 => float_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| Ok(val1.powf(val2)))
+            })?,
+            opcode::AND_U8..=opcode::AND_I64 => integer_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| Ok(val1 & val2))
+            })?,
+            opcode::OR_U8..=opcode::OR_I64 => integer_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| Ok(val1 | val2))
+            })?,
+            opcode::XOR_U8..=opcode::XOR_I64 => integer_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| Ok(val1 ^ val2))
+            })?,
+            opcode::NOT_U8..=opcode::NOT_I64 => integer_op!(i.opcode, T => {
+                unary(frame, budget, |val: T| Ok(!val))
+            })?,
+            opcode::SHL_U8..=opcode::SHL_I64 => integer_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| Ok(val1.wrapping_shl(val2 as u32)))
+            })?,
+            opcode::SHR_U8..=opcode::SHR_I64 => integer_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| Ok(val1.wrapping_shr(val2 as u32)))
+            })?,
+            opcode::ROTL_U8..=opcode::ROTL_I64 => integer_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| Ok(val1.rotate_left(val2 as u32)))
+            })?,
+            opcode::ROTR_U8..=opcode::ROTR_I64 => integer_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| Ok(val1.rotate_right(val2 as u32)))
+            })?,
+            opcode::ADD_F32 | opcode::ADD_F64 => float_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| Ok(val1 + val2))
+            })?,
+            opcode::SUB_F32 | opcode::SUB_F64 => float_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| Ok(val1 - val2))
+            })?,
+            opcode::DIV_F32 | opcode::DIV_F64 => float_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| Ok(val1 / val2))
+            })?,
+            opcode::MUL_F32 | opcode::MUL_F64 => float_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| Ok(val1 * val2))
+            })?,
+            opcode::LT_U8..=opcode::LT_F64 => number_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| Ok(val1 < val2))
+            })?,
+            opcode::LE_U8..=opcode::LE_F64 => number_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| Ok(val1 <= val2))
+            })?,
+            opcode::GT_U8..=opcode::GT_F64 => number_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| Ok(val1 > val2))
+            })?,
+            opcode::GE_U8..=opcode::GE_F64 => number_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| Ok(val1 >= val2))
+            })?,
+            opcode::NE_U8..=opcode::NE_F64 => number_op!(i.opcode, T => {
+                binary(frame, budget, |val1: T, val2: T| Ok(val1 != val2))
+            })?,
+            _ => return Err(TrapKind::IllegalOpcode),
         }
+        frame.pc += 1;
+        Ok(Flow::Continue)
     }
 }
 
+/// Pop the two objects at the front of the frame's stack, apply `op` and push
+/// the result onto the operand stack, `op` gets the front object first
+fn binary<T, R, F>(frame: &mut Frame, budget: &mut Budget, op: F) -> Result<(), TrapKind>
+where
+    T: Primitive,
+    R: Primitive,
+    F: FnOnce(T, T) -> Result<R, TrapKind>,
+{
+    let val1 = T::get(operand(frame, 0, T::H_TYPE)?);
+    let val2 = T::get(operand(frame, 1, T::H_TYPE)?);
+    let result = op(val1, val2)?;
+    consume(frame, budget, 2, result.into())
+}
+
+/// Pop the object at the front of the frame's stack, apply `op` and push the
+/// result onto the operand stack
+fn unary<T, R, F>(frame: &mut Frame, budget: &mut Budget, op: F) -> Result<(), TrapKind>
+where
+    T: Primitive,
+    R: Primitive,
+    F: FnOnce(T) -> Result<R, TrapKind>,
+{
+    let val = T::get(operand(frame, 0, T::H_TYPE)?);
+    let result = op(val)?;
+    consume(frame, budget, 1, result.into())
+}
+
+/// Get the exponent of a checked or saturating integer PWR
+///
+/// Exponents above `u32::MAX` only give a result that fits for a base of 0, 1
+/// or -1, which only depends on the exponent being odd or even, so they are
+/// reduced to the largest u32 of the same parity. Any other base overflows or
+/// saturates either way.
+fn exponent(exponent: i128) -> Result<u32, TrapKind> {
+    if exponent < 0 {
+        return Err(TrapKind::NegativeExponent);
+    }
+    Ok(u32::try_from(exponent).unwrap_or(u32::MAX - 1 + (exponent % 2) as u32))
+}
+
+/// Raise `base` to `exponent` by square and multiply, with `mul` wrapping on overflow
+///
+/// Wrapped results depend on every bit of the exponent, so unlike
+/// `wrapping_pow` this takes the whole u64.
+fn wrapping_pow<T: Copy>(base: T, exponent: u64, one: T, mul: impl Fn(T, T) -> T) -> T {
+    let (mut base, mut exponent, mut result) = (base, exponent, one);
+    while exponent != 0 {
+        if exponent & 1 == 1 {
+            result = mul(result, base);
+        }
+        base = mul(base, base);
+        exponent >>= 1;
+    }
+    result
+}
+
+/// Get the stack a stack manipulation opcode works on, the `_OPERAND` variants
+/// work on the operand stack
+fn stack_of(frame: &mut Frame, op: u64) -> &mut Vec<VirtualObject> {
+    match op {
+        opcode::POP_OPERAND..=opcode::PICK_OPERAND => &mut frame.operand_stack,
+        _ => &mut frame.stack,
+    }
+}
+
+/// Get a field index from an argument, indices past `usize::MAX` are out of range anyway
+fn field_index(arg: u64) -> usize {
+    usize::try_from(arg).unwrap_or(usize::MAX)
+}
+
+/// Get the object at `offset` from the front of the frame's stack
+fn front(frame: &Frame, offset: usize) -> Result<&VirtualObject, TrapKind> {
+    frame.get_front_in_stack(offset).ok_or(TrapKind::StackUnderflow)
+}
+
+/// Get the object at `offset` from the front of the frame's stack, checking it holds an `HType`
+fn operand(frame: &Frame, offset: usize, h_type: HType) -> Result<&VirtualObject, TrapKind> {
+    let obj = front(frame, offset)?;
+    expect_type(obj, h_type)?;
+    Ok(obj)
+}
+
+/// Pop the object at the front of the frame's stack, checking it holds an `HType`
+///
+/// The object counts as freed.
+fn pop_operand(frame: &mut Frame, budget: &mut Budget, h_type: HType) -> Result<VirtualObject, TrapKind> {
+    operand(frame, 0, h_type)?;
+    let obj = frame.stack.pop().ok_or(TrapKind::StackUnderflow)?;
+    budget.free(&obj);
+    Ok(obj)
+}
+
+/// Check the frame's stack has room for another object
+fn check_stack_allocation(frame: &Frame, budget: &Budget) -> Result<(), TrapKind> {
+    if budget.max_stack_allocation != 0 && frame.stack.len() as u64 >= budget.max_stack_allocation {
+        return Err(TrapKind::ConstraintViolation(Constraint::MaxStackAllocation));
+    }
+    Ok(())
+}
+
+/// Push an object onto the frame's stack if the constraints allow it
+fn push_stack(frame: &mut Frame, budget: &mut Budget, obj: VirtualObject) -> Result<(), TrapKind> {
+    check_stack_allocation(frame, budget)?;
+    budget.allocate(&obj)?;
+    frame.stack.push(obj);
+    Ok(())
+}
+
+/// Push an object onto the frame's operand stack if the constraints allow it
+fn push_operand(frame: &mut Frame, budget: &mut Budget, obj: VirtualObject) -> Result<(), TrapKind> {
+    budget.allocate(&obj)?;
+    frame.operand_stack.push(obj);
+    Ok(())
+}
+
+/// Replace the `count` objects an instruction read from the front of the
+/// frame's stack with its result on the operand stack
+///
+/// The operands stay on the stack if the result exceeds the constraints.
+fn consume(frame: &mut Frame, budget: &mut Budget, count: usize, result: VirtualObject) -> Result<(), TrapKind> {
+    frame.stack.len().checked_sub(count).ok_or(TrapKind::StackUnderflow)?;
+    push_operand(frame, budget, result)?;
+    drop_many(frame, budget, count)
+}
+
+/// Pop `count` objects from the frame's stack, keeping their order
+fn pop_many(frame: &mut Frame, count: usize) -> Result<Vec<VirtualObject>, TrapKind> {
+    let at = frame.stack.len().checked_sub(count).ok_or(TrapKind::StackUnderflow)?;
+    Ok(frame.stack.split_off(at))
+}
+
+/// Drop `count` objects from the front of the frame's stack
+fn drop_many(frame: &mut Frame, budget: &mut Budget, count: usize) -> Result<(), TrapKind> {
+    for obj in pop_many(frame, count)? {
+        budget.free(&obj);
+    }
+    Ok(())
+}
+
+fn operand_mut(frame: &mut Frame, offset: usize, h_type: HType) -> Result<&mut VirtualObject, TrapKind> {
+    let obj = frame.get_mut_front_in_stack(offset).ok_or(TrapKind::StackUnderflow)?;
+    expect_type(obj, h_type)?;
+    Ok(obj)
+}
+
+fn expect_type(obj: &VirtualObject, expected: HType) -> Result<(), TrapKind> {
+    if obj.data_type != expected {
+        return Err(TrapKind::TypeMismatch { expected, found: obj.data_type });
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use lib_heat_spec::frame::MAX_CALL_DEPTH;
     use lib_heat_spec::h_type::{BOOL_SIZE, HType, U16_SIZE, U32_SIZE, U64_SIZE, U8_SIZE};
     use lib_heat_spec::opcode;
-    use crate::constraints::Constraints;
-    use crate::frame::Frame;
+    use crate::constraints::{Constraint, Constraints};
+    use crate::frame::{Frame, FRAME_HEADER_SIZE};
+    use crate::fuel::Fuel;
+    use crate::function::Function;
+    use crate::gc;
     use crate::instruction::Instruction;
-    use crate::interpreter::Interpreter;
+    use crate::interpreter::{Interpreter, StepResult};
+    use crate::trap::TrapKind;
     use crate::types::VirtualObject;
 
+    /// Build an instruction that only uses its first argument
+    fn instruction(opcode: u64, arg1: u64) -> Instruction {
+        Instruction { opcode, arg1, arg2: 0, arg3: 0 }
+    }
+
     #[test]
     /// Performs stack allocation test on all HTypes using NEW_\[HType] instruction
     fn interpreter_frame_stack_allocation() {
-        let i = Interpreter::new(Constraints::new_none());
+        let mut i = Interpreter::new(Constraints::new_none());
         let mut frame = Frame::default();
         frame.instructions.push(Instruction {
             opcode: lib_heat_spec::opcode::NEW_BOOL,
@@ -304,10 +1102,10 @@ mod tests {
             arg2: 0,
             arg3: 0
         });
-        i.execute_frame(&mut frame);
+        i.execute_frame(&mut frame).unwrap();
 
-        assert_eq!(frame.stack.get(0).unwrap().data_type, HType::Bool);
-        assert_eq!(frame.stack.get(0).unwrap().data.capacity(), BOOL_SIZE);
+        assert_eq!(frame.stack.first().unwrap().data_type, HType::Bool);
+        assert_eq!(frame.stack.first().unwrap().data.capacity(), BOOL_SIZE);
 
         assert_eq!(frame.stack.get(1).unwrap().data_type, HType::U8);
         assert_eq!(frame.stack.get(1).unwrap().data.capacity(), U8_SIZE);
@@ -325,7 +1123,7 @@ mod tests {
     #[test]
     /// Performs LOAD_[HType] on `VirtualObjects` in stack
     fn interpreter_frame_stack_obj_load() {
-        let i = Interpreter::new(Constraints::new_none());
+        let mut i = Interpreter::new(Constraints::new_none());
         let mut frame = Frame::default();
 
         frame.stack.push(VirtualObject::new_empty(HType::Bool));
@@ -335,7 +1133,7 @@ mod tests {
             arg2: 0,
             arg3: 0
         });
-        i.execute_frame(&mut frame);
+        i.execute_frame(&mut frame).unwrap();
 
 
         frame.clear_instructions();
@@ -346,7 +1144,7 @@ mod tests {
             arg2: 0,
             arg3: 0
         });
-        i.execute_frame(&mut frame);
+        i.execute_frame(&mut frame).unwrap();
 
 
         frame.clear_instructions();
@@ -357,7 +1155,7 @@ mod tests {
             arg2: 0,
             arg3: 0
         });
-        i.execute_frame(&mut frame);
+        i.execute_frame(&mut frame).unwrap();
 
 
         frame.clear_instructions();
@@ -368,7 +1166,7 @@ mod tests {
             arg2: 0,
             arg3: 0
         });
-        i.execute_frame(&mut frame);
+        i.execute_frame(&mut frame).unwrap();
 
 
         frame.clear_instructions();
@@ -379,10 +1177,10 @@ mod tests {
             arg2: 0,
             arg3: 0
         });
-        i.execute_frame(&mut frame);
+        i.execute_frame(&mut frame).unwrap();
 
 
-        assert_eq!(frame.stack.get(0).unwrap().get_bool(), true);
+        assert!(frame.stack.first().unwrap().get_bool());
         assert_eq!(frame.stack.get(1).unwrap().get_u8(), u8::MAX);
         assert_eq!(frame.stack.get(2).unwrap().get_u16(), u16::MAX);
         assert_eq!(frame.stack.get(3).unwrap().get_u32(), u32::MAX);
@@ -392,47 +1190,369 @@ mod tests {
     #[test]
     /// Performs STORE on `VirtualObjects` in operand stack
     fn interpreter_frame_stack_operand_store() {
-        let i = Interpreter::new(Constraints::new_none());
+        let mut i = Interpreter::new(Constraints::new_none());
 
         let types = [HType::U8, HType::U16, HType::U32, HType::U64, HType::Bool];
         for h_type in types {
             let mut frame = Frame::default();
-            frame.stack.push(VirtualObject::new_max(h_type));
+            frame.operand_stack.push(VirtualObject::new_max(h_type));
+            frame.stack.push(VirtualObject::new_empty(HType::I8));
             frame.instructions.push(Instruction {
                 opcode: opcode::STORE,
                 arg1: 0,
                 arg2: 0,
                 arg3: 0
             });
-            i.execute_frame(&mut frame);
-            assert_eq!(frame.stack.pop().unwrap().data_type, h_type, "checking both operand and the first stack item are the same type")
+            i.execute_frame(&mut frame).unwrap();
+            assert_eq!(frame.stack.pop().unwrap(), VirtualObject::new_max(h_type), "the result is moved onto the stack");
+            assert_eq!(frame.stack, vec![VirtualObject::new_empty(HType::I8)], "objects below the result are left alone");
+            assert!(frame.operand_stack.is_empty());
         }
     }
 
     #[test]
-    /// Performs LOCAL_LOAD on `VirtualObjects` in stack
-    fn interpreter_frame_local_load() {
-        let interpreter = Interpreter::new(Constraints::new_none());
-        let mut frame = Frame::default();
-        frame.local = Vec::with_capacity(1);
+    /// Allocates an array on the heap, writes and reads an element through a pointer and frees it
+    fn interpreter_frame_heap() {
+        let mut interpreter = Interpreter::new(Constraints::new_none());
+        let mut frame = Frame { instructions: vec![
+            instruction(opcode::NEW_U64, 0),
+            instruction(opcode::LOAD_U64, 3),
+            instruction(opcode::ALLOC, HType::U32 as u64),
+            instruction(opcode::STORE, 0),
+            instruction(opcode::DUP, 0),
+            instruction(opcode::NEW_U64, 0),
+            instruction(opcode::LOAD_U64, 2),
+            instruction(opcode::PTR_ADD, 0),
+            instruction(opcode::STORE, 0),
+            instruction(opcode::DUP, 0),
+            instruction(opcode::NEW_U32, 0),
+            instruction(opcode::LOAD_U32, 7),
+            instruction(opcode::STORE_PTR, 0),
+            instruction(opcode::LOAD_PTR, 0),
+        ], ..Default::default() };
+        interpreter.execute_frame(&mut frame).unwrap();
+        assert_eq!(frame.operand_stack, vec![VirtualObject::from(7u32)]);
+        assert_eq!(frame.stack.len(), 1);
+        assert_eq!(interpreter.heap.used(), 3 * U32_SIZE as u64);
+
+        frame.instructions.push(instruction(opcode::FREE, 0));
+        interpreter.execute_frame(&mut frame).unwrap();
+        assert!(frame.stack.is_empty());
+        assert!(interpreter.heap.is_empty());
+
+        // pointers are checked on every use and a trap leaves the stack alone
+        let cases = [
+            (vec![VirtualObject::new_empty(HType::Ptr)], opcode::LOAD_PTR, TrapKind::InvalidPointer),
+            (vec![VirtualObject::from(1u64)], opcode::LOAD_PTR, TrapKind::TypeMismatch { expected: HType::Ptr, found: HType::U64 }),
+            (vec![VirtualObject::new_empty(HType::Ptr)], opcode::FREE, TrapKind::InvalidPointer),
+            (vec![VirtualObject::new_empty(HType::Ptr), VirtualObject::from(1u8)], opcode::STORE_PTR, TrapKind::InvalidPointer),
+            (vec![VirtualObject::from(1u8)], opcode::CAST_WRAP, TrapKind::InvalidType),
+        ];
+        for (stack, op, kind) in cases {
+            let mut frame = Frame { stack: stack.clone(), ..Default::default() };
+            frame.instructions.push(instruction(op, HType::Ptr as u64));
+            let trap = interpreter.execute_frame(&mut frame).unwrap_err();
+            assert_eq!(trap.kind, kind, "{:#x}", op);
+            assert_eq!(frame.stack, stack);
+        }
 
-        frame.instructions.push(Instruction{
-            opcode: opcode::LOCAL_LOAD,
-            arg1: 0,
-            arg2: 0,
-            arg3: 0
-        });
-        frame.stack.push(VirtualObject::new_max(HType::U8));
+        let pointer = interpreter.heap.alloc(HType::I8, 1).unwrap();
+        let mut frame = Frame { stack: vec![VirtualObject::from(pointer), VirtualObject::from(1u8)], ..Default::default() };
+        frame.instructions.push(instruction(opcode::STORE_PTR, 0));
+        let trap = interpreter.execute_frame(&mut frame).unwrap_err();
+        assert_eq!(trap.kind, TrapKind::TypeMismatch { expected: HType::I8, found: HType::U8 });
+        assert_eq!(frame.stack.len(), 2);
+    }
 
-        interpreter.execute_frame(&mut frame);
+    #[test]
+    /// Counts heap memory against `Constraints::max_memory`
+    fn interpreter_frame_heap_memory() {
+        let alloc = |count| Frame {
+            stack: vec![VirtualObject::from(count)],
+            instructions: vec![instruction(opcode::ALLOC, HType::U8 as u64), instruction(opcode::NEW_U64, 0), instruction(opcode::NEW_U8, 0)],
+            ..Default::default()
+        };
+
+        // the count leaves 12 bytes for the allocation and the pointer
+        let mut interpreter = Interpreter::new(Constraints::new(20, 0));
+        let mut frame = alloc(4u64);
+        let trap = interpreter.execute_frame(&mut frame).unwrap_err();
+        assert_eq!(trap.kind, TrapKind::ConstraintViolation(Constraint::MaxMemory));
+        assert_eq!(trap.pc, 0);
+        assert_eq!(frame.stack, vec![VirtualObject::from(4u64)]);
+        assert!(interpreter.heap.is_empty());
+
+        // the heap keeps counting after the allocation
+        let mut frame = alloc(3u64);
+        let trap = interpreter.execute_frame(&mut frame).unwrap_err();
+        assert_eq!(trap.kind, TrapKind::ConstraintViolation(Constraint::MaxMemory));
+        assert_eq!(trap.pc, 2);
+        assert_eq!(interpreter.heap.used(), 3);
+
+        // allocations beyond the address space trap even without a limit
+        let mut interpreter = Interpreter::new(Constraints::new_none());
+        let mut frame = alloc(u64::MAX);
+        assert_eq!(interpreter.execute_frame(&mut frame).unwrap_err().kind, TrapKind::Overflow);
+
+        // huge allocations trap instead of aborting the host even without a limit
+        let mut frame = alloc(1u64 << 42);
+        let trap = interpreter.execute_frame(&mut frame).unwrap_err();
+        assert_eq!(trap.kind, TrapKind::OutOfMemory);
+        assert_eq!(frame.stack, vec![VirtualObject::from(1u64 << 42)]);
+        assert!(interpreter.heap.is_empty());
+    }
 
-        assert_eq!(frame.local.get(0).unwrap().get_u8(), u8::MAX, "local 1 is not equal to VirtualObject u8 with MAX value");
+    #[test]
+    /// Creates an object, then reads and writes its fields through a reference
+    fn interpreter_frame_objects() {
+        let mut interpreter = Interpreter::new(Constraints::new_none());
+        let mut frame = Frame { instructions: vec![
+            instruction(opcode::NEW_U8, 0),
+            instruction(opcode::LOAD_U8, 5),
+            instruction(opcode::NEW_U16, 0),
+            instruction(opcode::NEW_OBJECT, 2),
+            instruction(opcode::STORE, 0),
+            instruction(opcode::DUP, 0),
+            instruction(opcode::NEW_U16, 0),
+            instruction(opcode::LOAD_U16, 9),
+            instruction(opcode::SET_FIELD, 1),
+            instruction(opcode::DUP, 0),
+            instruction(opcode::GET_FIELD, 1),
+            instruction(opcode::GET_FIELD, 0),
+        ], ..Default::default() };
+        interpreter.execute_frame(&mut frame).unwrap();
+        assert_eq!(frame.operand_stack, vec![VirtualObject::from(9u16), VirtualObject::from(5u8)]);
+        assert!(frame.stack.is_empty());
+        assert_eq!(interpreter.objects.len(), 1);
+
+        // a trap leaves the stack alone
+        let object = VirtualObject::from(interpreter.objects.alloc(vec![VirtualObject::from(1u8)]));
+        let cases = [
+            (vec![VirtualObject::new_empty(HType::Ref)], opcode::GET_FIELD, 0, TrapKind::NullReference),
+            (vec![object.clone()], opcode::GET_FIELD, 1, TrapKind::FieldOutOfRange),
+            (vec![object.clone(), VirtualObject::from(1u16)], opcode::SET_FIELD, 0, TrapKind::TypeMismatch { expected: HType::U8, found: HType::U16 }),
+            (vec![VirtualObject::from(1u8), object.clone()], opcode::SET_FIELD, 0, TrapKind::TypeMismatch { expected: HType::Ref, found: HType::U8 }),
+            (vec![VirtualObject::from(1u8)], opcode::NEW_OBJECT, 2, TrapKind::StackUnderflow),
+            (vec![object], opcode::CAST_WRAP, HType::U64 as u64, TrapKind::InvalidType),
+            (vec![VirtualObject::from(1u64)], opcode::ALLOC, HType::Ref as u64, TrapKind::InvalidType),
+        ];
+        for (stack, op, arg1, kind) in cases {
+            let mut frame = Frame { stack: stack.clone(), ..Default::default() };
+            frame.instructions.push(instruction(op, arg1));
+            let trap = interpreter.execute_frame(&mut frame).unwrap_err();
+            assert_eq!(trap.kind, kind, "{:#x}", op);
+            assert_eq!(frame.stack, stack);
+        }
+    }
+
+    #[test]
+    /// Creates 10000 garbage objects pointing to a live one, collecting whenever memory runs out
+    fn interpreter_frame_gc_stress() {
+        let run = |max_memory| {
+            let mut interpreter = Interpreter::new(Constraints::new(max_memory, 0));
+            let live = interpreter.objects.alloc(vec![VirtualObject::from(42u8)]);
+            let mut frame = Frame {
+                local: vec![VirtualObject::from(0u64), VirtualObject::from(live)],
+                instructions: vec![
+                    instruction(opcode::LOCAL_GET, 0),
+                    instruction(opcode::LOCAL_GET, 1),
+                    instruction(opcode::NEW_OBJECT, 2),
+                    instruction(opcode::POP_OPERAND, 0),
+                    instruction(opcode::LOCAL_GET, 0),
+                    instruction(opcode::NEW_U64, 0),
+                    instruction(opcode::LOAD_U64, 1),
+                    instruction(opcode::ADD_U64, 0),
+                    instruction(opcode::STORE, 0),
+                    instruction(opcode::LOCAL_TEE, 0),
+                    instruction(opcode::NEW_U64, 0),
+                    instruction(opcode::LOAD_U64, 10_000),
+                    instruction(opcode::GT_U64, 0),
+                    instruction(opcode::STORE, 0),
+                    instruction(opcode::JMP_IF_TRUE, 0),
+                    instruction(opcode::LOCAL_GET, 1),
+                    instruction(opcode::GET_FIELD, 0),
+                ],
+                ..Default::default()
+            };
+            interpreter.execute_frame(&mut frame).unwrap();
+            assert_eq!(frame.local[0], VirtualObject::from(10_000u64));
+            assert_eq!(frame.operand_stack, vec![VirtualObject::from(42u8)], "the live object survives");
+            (interpreter, frame)
+        };
+
+        // every object holds 16 bytes in fields and a header
+        let (interpreter, _) = run(200);
+        assert!(interpreter.objects.len() < 10);
+        assert!(interpreter.objects.used() <= 200);
+
+        // without a limit the host collects
+        let (mut interpreter, frame) = run(0);
+        assert_eq!(interpreter.objects.len(), 10_001);
+        assert_eq!(interpreter.collect_garbage(&[&frame]), 10_000);
+        assert_eq!(interpreter.objects.len(), 1);
+    }
+
+    #[test]
+    /// Builds a chain of objects in one frame while another frame sharing the interpreter creates garbage
+    fn interpreter_frame_gc_frames() {
+        // counts local 0 up to `count`, creating an object from the counter and local 1 on every iteration
+        let looping = |count, keep: &[Instruction]| Frame {
+            local: vec![VirtualObject::from(0u64), VirtualObject::new_empty(HType::Ref)],
+            instructions: [
+                &[
+                    instruction(opcode::LOCAL_GET, 0),
+                    instruction(opcode::LOCAL_GET, 1),
+                    instruction(opcode::NEW_OBJECT, 2),
+                ],
+                keep,
+                &[
+                    instruction(opcode::LOCAL_GET, 0),
+                    instruction(opcode::NEW_U64, 0),
+                    instruction(opcode::LOAD_U64, 1),
+                    instruction(opcode::ADD_U64, 0),
+                    instruction(opcode::STORE, 0),
+                    instruction(opcode::LOCAL_TEE, 0),
+                    instruction(opcode::NEW_U64, 0),
+                    instruction(opcode::LOAD_U64, count),
+                    instruction(opcode::GT_U64, 0),
+                    instruction(opcode::STORE, 0),
+                    instruction(opcode::JMP_IF_TRUE, 0),
+                ],
+            ].concat(),
+            ..Default::default()
+        };
+
+        let keep = [instruction(opcode::STORE, 0), instruction(opcode::LOCAL_SET, 1)];
+        let mut interpreter = Interpreter::new(Constraints::new(1_000, 0));
+        let mut chain = looping(30, &keep);
+        // the finished frame hands the head of the chain to the host
+        chain.instructions.push(instruction(opcode::LOCAL_GET, 1));
+        let mut paused = 0u64;
+        loop {
+            let result = interpreter.run_until(&mut chain, |frame| {
+                frame.pc == 0 && frame.local[0] == VirtualObject::from(paused + 5)
+            }).unwrap();
+
+            // the paused chain is only known to the interpreter while the garbage is collected
+            let mut garbage = looping(2_000, &[instruction(opcode::POP_OPERAND, 0)]);
+            interpreter.execute_frame(&mut garbage).unwrap();
+            assert!(interpreter.objects.used() <= 1_000);
+
+            if result == StepResult::Finished {
+                break;
+            }
+            paused += 5;
+        }
+
+        // walking the chain finds every value in order
+        let mut link = chain.local[1].get_reference();
+        for value in (0..30u64).rev() {
+            let fields = interpreter.objects.fields(link).unwrap();
+            assert_eq!(fields[0], VirtualObject::from(value));
+            link = fields[1].get_reference();
+        }
+        assert_eq!(link.id, 0);
+
+        // leftover garbage is freed, the finished chain frame still holds the head on its stack
+        interpreter.collect_garbage(&[]);
+        assert_eq!(interpreter.objects.len(), 30);
+        interpreter.release_frame(&chain);
+        assert_eq!(interpreter.collect_garbage(&[]), 30);
+        assert!(interpreter.objects.is_empty());
+
+        // a finished frame without references on its stacks keeps nothing
+        let mut chain = looping(30, &keep);
+        interpreter.execute_frame(&mut chain).unwrap();
+        assert_eq!(interpreter.collect_garbage(&[]), 30);
+
+        // a frame driven by step keeps its objects between steps
+        let mut chain = looping(30, &keep);
+        while chain.local[0] != VirtualObject::from(10u64) {
+            interpreter.step(&mut chain).unwrap();
+        }
+        let mut garbage = looping(2_000, &[instruction(opcode::POP_OPERAND, 0)]);
+        interpreter.execute_frame(&mut garbage).unwrap();
+        interpreter.collect_garbage(&[]);
+        assert_eq!(interpreter.objects.len(), 10);
+        assert_eq!(interpreter.frame_roots.len(), 1, "only the stepped frame is kept");
+    }
+
+    #[test]
+    /// Moves objects between the stacks with STORE and STORE_OPERAND
+    fn interpreter_frame_store_operand() {
+        let mut interpreter = Interpreter::new(Constraints::new_none());
+
+        let mut frame = Frame { stack: vec![VirtualObject::from(1u8), VirtualObject::from(2u16)], ..Default::default() };
+        frame.instructions.push(instruction(opcode::STORE_OPERAND, 0));
+        frame.instructions.push(instruction(opcode::STORE_OPERAND, 0));
+        frame.instructions.push(instruction(opcode::STORE, 0));
+        interpreter.execute_frame(&mut frame).unwrap();
+        assert_eq!(frame.stack, vec![VirtualObject::from(1u8)]);
+        assert_eq!(frame.operand_stack, vec![VirtualObject::from(2u16)]);
+
+        // moving from an empty stack traps and leaves the other stack alone
+        for (op, stack, operand_stack) in [(opcode::STORE, 1, 0), (opcode::STORE_OPERAND, 0, 1)] {
+            let mut frame = Frame {
+                stack: vec![VirtualObject::from(1u8); stack],
+                operand_stack: vec![VirtualObject::from(1u8); operand_stack],
+                ..Default::default()
+            };
+            frame.instructions.push(instruction(op, 0));
+            let trap = interpreter.execute_frame(&mut frame).unwrap_err();
+            assert_eq!(trap.kind, TrapKind::StackUnderflow);
+            assert_eq!((frame.stack.len(), frame.operand_stack.len()), (stack, operand_stack));
+        }
+
+        // STORE counts against the stack allocation limit, moving needs no memory
+        let mut interpreter = Interpreter::new(Constraints::new(2, 1));
+        let mut frame = Frame {
+            stack: vec![VirtualObject::from(1u8)],
+            operand_stack: vec![VirtualObject::from(2u8)],
+            ..Default::default()
+        };
+        frame.instructions.push(instruction(opcode::STORE_OPERAND, 0));
+        frame.instructions.push(instruction(opcode::STORE, 0));
+        frame.instructions.push(instruction(opcode::STORE, 0));
+        let trap = interpreter.execute_frame(&mut frame).unwrap_err();
+        assert_eq!(trap.kind, TrapKind::ConstraintViolation(Constraint::MaxStackAllocation));
+        assert_eq!(trap.pc, 2);
+    }
+
+    #[test]
+    /// Performs LOCAL_GET, LOCAL_SET and LOCAL_TEE on the local slots
+    fn interpreter_frame_locals() {
+        let mut interpreter = Interpreter::new(Constraints::new_none());
+        let mut frame = Frame {
+            local: vec![VirtualObject::from(1u8), VirtualObject::from(2u16), VirtualObject::from(3u8)],
+            stack: vec![VirtualObject::new_max(HType::U8), VirtualObject::from(9u16)],
+            ..Default::default()
+        };
+        frame.instructions.push(instruction(opcode::LOCAL_SET, 1));
+        frame.instructions.push(instruction(opcode::LOCAL_TEE, 0));
+        frame.instructions.push(instruction(opcode::LOCAL_GET, 2));
+
+        interpreter.execute_frame(&mut frame).unwrap();
+        assert_eq!(frame.local, vec![VirtualObject::new_max(HType::U8), VirtualObject::from(9u16), VirtualObject::from(3u8)],
+            "slots are replaced in place");
+        assert_eq!(frame.stack, vec![VirtualObject::new_max(HType::U8), VirtualObject::from(3u8)]);
+
+        // slots keep their type
+        let mut frame = Frame {
+            local: vec![VirtualObject::from(1u8)],
+            stack: vec![VirtualObject::from(1u16)],
+            ..Default::default()
+        };
+        frame.instructions.push(instruction(opcode::LOCAL_SET, 0));
+        let trap = interpreter.execute_frame(&mut frame).unwrap_err();
+        assert_eq!(trap.kind, TrapKind::TypeMismatch { expected: HType::U8, found: HType::U16 });
+        assert_eq!(frame.stack.len(), 1, "a trapping LOCAL_SET leaves the stack alone");
+        assert_eq!(frame.local, vec![VirtualObject::from(1u8)]);
     }
 
     #[test]
     /// Performs ADD_[HType] on `VirtualObjects` in operand stack
     fn interpreter_frame_arithmetic_add() {
-        let interpreter = Interpreter::new(Constraints::new_none());
+        let mut interpreter = Interpreter::new(Constraints::new_none());
 
         let u8list:[[u8;3];4] = [
             [1, 2, 3],
@@ -473,7 +1593,7 @@ mod tests {
                 arg1: 0, arg2: 0, arg3: 0
             });
 
-            interpreter.execute_frame(&mut frame);
+            interpreter.execute_frame(&mut frame).unwrap();
             assert_eq!(frame.operand_stack.pop().unwrap().get_u8(), i[2]);
         }
 
@@ -487,7 +1607,7 @@ mod tests {
                 arg1: 0, arg2: 0, arg3: 0
             });
 
-            interpreter.execute_frame(&mut frame);
+            interpreter.execute_frame(&mut frame).unwrap();
             assert_eq!(frame.operand_stack.pop().unwrap().get_u16(), i[2]);
         }
 
@@ -501,7 +1621,7 @@ mod tests {
                 arg1: 0, arg2: 0, arg3: 0
             });
 
-            interpreter.execute_frame(&mut frame);
+            interpreter.execute_frame(&mut frame).unwrap();
             assert_eq!(frame.operand_stack.pop().unwrap().get_u32(), i[2]);
         }
 
@@ -515,7 +1635,7 @@ mod tests {
                 arg1: 0, arg2: 0, arg3: 0
             });
 
-            interpreter.execute_frame(&mut frame);
+            interpreter.execute_frame(&mut frame).unwrap();
             assert_eq!(frame.operand_stack.pop().unwrap().get_u64(), i[2]);
         }
     }
@@ -523,7 +1643,7 @@ mod tests {
     #[test]
     /// Performs SUB_[HType] on `VirtualObjects` in operand stack
     fn interpreter_frame_arithmetic_sub() {
-        let interpreter = Interpreter::new(Constraints::new_none());
+        let mut interpreter = Interpreter::new(Constraints::new_none());
 
         let u8list:[[u8;3];4] = [
             [2, 5, 3],
@@ -564,7 +1684,7 @@ mod tests {
                 arg1: 0, arg2: 0, arg3: 0
             });
 
-            interpreter.execute_frame(&mut frame);
+            interpreter.execute_frame(&mut frame).unwrap();
             assert_eq!(frame.operand_stack.pop().unwrap().get_u8(), i[2]);
         }
 
@@ -578,7 +1698,7 @@ mod tests {
                 arg1: 0, arg2: 0, arg3: 0
             });
 
-            interpreter.execute_frame(&mut frame);
+            interpreter.execute_frame(&mut frame).unwrap();
             assert_eq!(frame.operand_stack.pop().unwrap().get_u16(), i[2]);
         }
 
@@ -592,7 +1712,7 @@ mod tests {
                 arg1: 0, arg2: 0, arg3: 0
             });
 
-            interpreter.execute_frame(&mut frame);
+            interpreter.execute_frame(&mut frame).unwrap();
             assert_eq!(frame.operand_stack.pop().unwrap().get_u32(), i[2]);
         }
 
@@ -606,14 +1726,14 @@ mod tests {
                 arg1: 0, arg2: 0, arg3: 0
             });
 
-            interpreter.execute_frame(&mut frame);
+            interpreter.execute_frame(&mut frame).unwrap();
             assert_eq!(frame.operand_stack.pop().unwrap().get_u64(), i[2]);
         }
     }
 
     #[test]
     fn interpreter_frame_equal() {
-        let interpreter = Interpreter::new(Constraints::new_none());
+        let mut interpreter = Interpreter::new(Constraints::new_none());
 
 
         // same types same value
@@ -629,8 +1749,8 @@ mod tests {
         frame.stack.push(VirtualObject::new_max(HType::U8));
         frame.stack.push(VirtualObject::new_max(HType::U8));
 
-        interpreter.execute_frame(&mut frame);
-        assert_eq!(frame.operand_stack.pop().unwrap().get_bool(), true);
+        interpreter.execute_frame(&mut frame).unwrap();
+        assert!(frame.operand_stack.pop().unwrap().get_bool());
 
         // non same types
         let mut frame = Frame::default();
@@ -644,8 +1764,8 @@ mod tests {
         frame.stack.push(VirtualObject::new_max(HType::U8));
         frame.stack.push(VirtualObject::new_max(HType::U32));
 
-        interpreter.execute_frame(&mut frame);
-        assert_eq!(frame.operand_stack.pop().unwrap().get_bool(), false);
+        interpreter.execute_frame(&mut frame).unwrap();
+        assert!(!frame.operand_stack.pop().unwrap().get_bool());
 
         // non same types with same value
         let mut frame = Frame::default();
@@ -659,13 +1779,13 @@ mod tests {
         frame.stack.push(VirtualObject::from(2u8));
         frame.stack.push(VirtualObject::from(2u32));
 
-        interpreter.execute_frame(&mut frame);
-        assert_eq!(frame.operand_stack.pop().unwrap().get_bool(), false);
+        interpreter.execute_frame(&mut frame).unwrap();
+        assert!(!frame.operand_stack.pop().unwrap().get_bool());
     }
 
     #[test]
     fn interpreter_frame_not() {
-        let interpreter = Interpreter::new(Constraints::new_none());
+        let mut interpreter = Interpreter::new(Constraints::new_none());
         let mut frame = Frame::default();
 
         frame.stack.push(VirtualObject::from(true));
@@ -675,7 +1795,7 @@ mod tests {
             arg2: 0,
             arg3: 0
         });
-        interpreter.execute_frame(&mut frame);
+        interpreter.execute_frame(&mut frame).unwrap();
         assert!(!frame.operand_stack.pop().unwrap().get_bool(), "checking if value turned into false");
 
 
@@ -688,7 +1808,866 @@ mod tests {
             arg2: 0,
             arg3: 0
         });
-        interpreter.execute_frame(&mut frame);
+        interpreter.execute_frame(&mut frame).unwrap();
         assert!(frame.operand_stack.pop().unwrap().get_bool(), "checking if value turned into true")
     }
+
+    #[test]
+    /// Performs NEW, LOAD, arithmetic and comparisons on signed `VirtualObjects`
+    fn interpreter_frame_signed() {
+        let mut interpreter = Interpreter::new(Constraints::new_none());
+
+        // immediates are sign extended
+        let mut frame = Frame::default();
+        for (new, load, value) in [
+            (opcode::NEW_I8, opcode::LOAD_I8, -1i64),
+            (opcode::NEW_I16, opcode::LOAD_I16, i16::MIN as i64),
+            (opcode::NEW_I32, opcode::LOAD_I32, -70_000),
+            (opcode::NEW_I64, opcode::LOAD_I64, i64::MIN),
+        ] {
+            frame.instructions.push(instruction(new, 0));
+            frame.instructions.push(instruction(load, value as u64));
+        }
+        interpreter.execute_frame(&mut frame).unwrap();
+        assert_eq!(frame.stack, vec![
+            VirtualObject::from(-1i8),
+            VirtualObject::from(i16::MIN),
+            VirtualObject::from(-70_000i32),
+            VirtualObject::from(i64::MIN),
+        ]);
+
+        // the front object is the left hand side
+        let cases: [(VirtualObject, VirtualObject, u64, VirtualObject); 8] = [
+            ((-7i8).into(), 2i8.into(), opcode::DIV_I8, (-3i8).into()),
+            (7i16.into(), (-2i16).into(), opcode::DIV_I16, (-3i16).into()),
+            ((-7i32).into(), (-2i32).into(), opcode::DIV_I32, 3i32.into()),
+            ((-5i64).into(), 3i64.into(), opcode::ADD_I64, (-2i64).into()),
+            (3i8.into(), 5i8.into(), opcode::SUB_I8, (-2i8).into()),
+            ((-4i16).into(), 3i16.into(), opcode::MUL_I16, (-12i16).into()),
+            ((-1i32).into(), 0i32.into(), opcode::LT_I32, true.into()),
+            ((-1i64).into(), 0i64.into(), opcode::GT_I64, false.into()),
+        ];
+        for (lhs, rhs, op, expected) in cases {
+            let mut frame = Frame { stack: vec![rhs, lhs], ..Default::default() };
+            frame.instructions.push(instruction(op, 0));
+            interpreter.execute_frame(&mut frame).unwrap();
+            assert_eq!(frame.operand_stack.pop().unwrap(), expected, "{}", opcode::to_mnemonic(op).unwrap());
+        }
+
+        let cases: [(VirtualObject, VirtualObject, u64, TrapKind); 4] = [
+            (i8::MIN.into(), (-1i8).into(), opcode::DIV_I8, TrapKind::Overflow),
+            (1i16.into(), 0i16.into(), opcode::DIV_I16, TrapKind::DivisionByZero),
+            (i32::MIN.into(), 1i32.into(), opcode::SUB_I32, TrapKind::Overflow),
+            (1u64.into(), 1u64.into(), opcode::ADD_I64, TrapKind::TypeMismatch { expected: HType::I64, found: HType::U64 }),
+        ];
+        for (lhs, rhs, op, kind) in cases {
+            let mut frame = Frame { stack: vec![rhs, lhs], ..Default::default() };
+            frame.instructions.push(instruction(op, 0));
+            assert_eq!(interpreter.execute_frame(&mut frame).unwrap_err().kind, kind);
+        }
+    }
+
+    #[test]
+    /// Performs NEW, LOAD, arithmetic and comparisons on float `VirtualObjects`
+    fn interpreter_frame_float() {
+        let mut interpreter = Interpreter::new(Constraints::new_none());
+
+        // immediates are IEEE-754 bit patterns
+        let mut frame = Frame::default();
+        frame.instructions.push(instruction(opcode::NEW_F32, 0));
+        frame.instructions.push(instruction(opcode::LOAD_F32, (-1.5f32).to_bits() as u64));
+        frame.instructions.push(instruction(opcode::NEW_F64, 0));
+        frame.instructions.push(instruction(opcode::LOAD_F64, f64::NEG_INFINITY.to_bits()));
+        interpreter.execute_frame(&mut frame).unwrap();
+        assert_eq!(frame.stack, vec![VirtualObject::from(-1.5f32), VirtualObject::from(f64::NEG_INFINITY)]);
+
+        // the front object is the left hand side
+        let cases: [(VirtualObject, VirtualObject, u64, VirtualObject); 16] = [
+            (1.5f32.into(), 0.25f32.into(), opcode::ADD_F32, 1.75f32.into()),
+            (1.0f64.into(), 4.0f64.into(), opcode::SUB_F64, (-3.0f64).into()),
+            (f32::MAX.into(), 2.0f32.into(), opcode::MUL_F32, f32::INFINITY.into()),
+            (1.0f64.into(), 0.0f64.into(), opcode::DIV_F64, f64::INFINITY.into()),
+            (1.0f64.into(), (-0.0f64).into(), opcode::DIV_F64, f64::NEG_INFINITY.into()),
+            (f32::INFINITY.into(), 1.0f32.into(), opcode::SUB_F32, f32::INFINITY.into()),
+            (f64::NEG_INFINITY.into(), f64::MIN.into(), opcode::LT_F64, true.into()),
+            (f32::INFINITY.into(), f32::MAX.into(), opcode::GT_F32, true.into()),
+            (f32::NAN.into(), 1.0f32.into(), opcode::LT_F32, false.into()),
+            (f64::NAN.into(), 1.0f64.into(), opcode::GT_F64, false.into()),
+            (f32::NAN.into(), f32::NAN.into(), opcode::EQUAL, false.into()),
+            (f64::NAN.into(), f64::NAN.into(), opcode::EQUAL, false.into()),
+            ((-0.0f32).into(), 0.0f32.into(), opcode::EQUAL, true.into()),
+            ((-0.0f64).into(), 0.0f64.into(), opcode::EQUAL, true.into()),
+            ((-0.0f64).into(), 0.0f64.into(), opcode::LT_F64, false.into()),
+            (1.0f32.into(), 1.0f64.into(), opcode::EQUAL, false.into()),
+        ];
+        for (lhs, rhs, op, expected) in cases {
+            let mut frame = Frame { stack: vec![rhs, lhs], ..Default::default() };
+            frame.instructions.push(instruction(op, 0));
+            interpreter.execute_frame(&mut frame).unwrap();
+            assert_eq!(frame.operand_stack.pop().unwrap(), expected, "{}", opcode::to_mnemonic(op).unwrap());
+        }
+
+        // 0 / 0 and inf - inf are NaN
+        for (lhs, rhs, op) in [(0.0f64, 0.0f64, opcode::DIV_F64), (f64::INFINITY, f64::INFINITY, opcode::SUB_F64)] {
+            let mut frame = Frame { stack: vec![rhs.into(), lhs.into()], ..Default::default() };
+            frame.instructions.push(instruction(op, 0));
+            interpreter.execute_frame(&mut frame).unwrap();
+            assert!(frame.operand_stack.pop().unwrap().get_f64().is_nan());
+        }
+
+        let mut frame = Frame { stack: vec![1.0f64.into(), 1.0f32.into()], ..Default::default() };
+        frame.instructions.push(instruction(opcode::ADD_F32, 0));
+        let trap = interpreter.execute_frame(&mut frame).unwrap_err();
+        assert_eq!(trap.kind, TrapKind::TypeMismatch { expected: HType::F32, found: HType::F64 });
+    }
+
+    #[test]
+    /// Performs LT, LE, GT, GE and NE on every number type
+    fn interpreter_frame_comparison() {
+        let mut interpreter = Interpreter::new(Constraints::new_none());
+
+        // the front object is the left hand side
+        let cases: [(VirtualObject, VirtualObject, u64, bool); 16] = [
+            (1u8.into(), 2u8.into(), opcode::LT_U8, true),
+            (u64::MAX.into(), 0u64.into(), opcode::LT_U64, false),
+            (2u16.into(), 2u16.into(), opcode::LE_U16, true),
+            ((-1i32).into(), 0i32.into(), opcode::LE_I32, true),
+            (u32::MAX.into(), 0u32.into(), opcode::GT_U32, true),
+            ((-1i8).into(), 0i8.into(), opcode::GT_I8, false),
+            (i64::MIN.into(), i64::MIN.into(), opcode::GE_I64, true),
+            (0u8.into(), 1u8.into(), opcode::GE_U8, false),
+            (3i16.into(), 3i16.into(), opcode::NE_I16, false),
+            (3u64.into(), 4u64.into(), opcode::NE_U64, true),
+            ((-0.0f32).into(), 0.0f32.into(), opcode::LE_F32, true),
+            (f64::INFINITY.into(), f64::MAX.into(), opcode::GE_F64, true),
+            (f32::NAN.into(), f32::NAN.into(), opcode::LE_F32, false),
+            (f64::NAN.into(), 1.0f64.into(), opcode::GE_F64, false),
+            (f32::NAN.into(), f32::NAN.into(), opcode::NE_F32, true),
+            ((-0.0f64).into(), 0.0f64.into(), opcode::NE_F64, false),
+        ];
+        for (lhs, rhs, op, expected) in cases {
+            let mut frame = Frame { stack: vec![rhs, lhs], ..Default::default() };
+            frame.instructions.push(instruction(op, 0));
+            interpreter.execute_frame(&mut frame).unwrap();
+            assert_eq!(frame.operand_stack, vec![VirtualObject::from(expected)], "{}", opcode::to_mnemonic(op).unwrap());
+        }
+
+        let mut frame = Frame { stack: vec![1u16.into(), 1u8.into()], ..Default::default() };
+        frame.instructions.push(instruction(opcode::LT_U8, 0));
+        let trap = interpreter.execute_frame(&mut frame).unwrap_err();
+        assert_eq!(trap.kind, TrapKind::TypeMismatch { expected: HType::U8, found: HType::U16 });
+    }
+
+    #[test]
+    /// Performs POP, DUP, SWAP, OVER, ROT and PICK on both stacks
+    fn interpreter_frame_stack_manipulation() {
+        let mut interpreter = Interpreter::new(Constraints::new_none());
+        let objects = || vec![VirtualObject::from(1u8), VirtualObject::from(2u16), VirtualObject::from(3u32)];
+        let run = |interpreter: &mut Interpreter, op, arg1| {
+            let operand = op >= opcode::POP_OPERAND;
+            let mut frame = Frame::default();
+            if operand {
+                frame.operand_stack = objects();
+            } else {
+                frame.stack = objects();
+            }
+            frame.instructions.push(instruction(op, arg1));
+            interpreter.execute_frame(&mut frame).map_err(|trap| trap.kind)?;
+            Ok(if operand { frame.operand_stack } else { frame.stack })
+        };
+
+        let expected = |order: &[u32]| -> Result<Vec<VirtualObject>, TrapKind> {
+            Ok(order.iter().map(|value| objects()[*value as usize - 1].clone()).collect())
+        };
+        let cases = [
+            (opcode::POP, opcode::POP_OPERAND, 0, expected(&[1, 2])),
+            (opcode::DUP, opcode::DUP_OPERAND, 0, expected(&[1, 2, 3, 3])),
+            (opcode::SWAP, opcode::SWAP_OPERAND, 0, expected(&[1, 3, 2])),
+            (opcode::OVER, opcode::OVER_OPERAND, 0, expected(&[1, 2, 3, 2])),
+            (opcode::ROT, opcode::ROT_OPERAND, 0, expected(&[2, 3, 1])),
+            (opcode::PICK, opcode::PICK_OPERAND, 2, expected(&[1, 2, 3, 1])),
+            (opcode::PICK, opcode::PICK_OPERAND, 3, Err(TrapKind::StackUnderflow)),
+            (opcode::PICK, opcode::PICK_OPERAND, u64::MAX, Err(TrapKind::StackUnderflow)),
+        ];
+        for (op, operand_op, arg1, expected) in cases {
+            assert_eq!(run(&mut interpreter, op, arg1), expected, "{}", opcode::to_mnemonic(op).unwrap());
+            assert_eq!(run(&mut interpreter, operand_op, arg1), expected, "{}", opcode::to_mnemonic(operand_op).unwrap());
+        }
+
+        for op in [opcode::POP, opcode::DUP, opcode::SWAP, opcode::ROT, opcode::OVER_OPERAND, opcode::ROT_OPERAND] {
+            let mut frame = Frame::default();
+            frame.instructions.push(instruction(op, 0));
+            assert_eq!(interpreter.execute_frame(&mut frame).unwrap_err().kind, TrapKind::StackUnderflow);
+        }
+
+        // copies onto the stack count against max_stack_allocation
+        let mut interpreter = Interpreter::new(Constraints::new(0, 3));
+        let trap = run(&mut interpreter, opcode::DUP, 0).unwrap_err();
+        assert_eq!(trap, TrapKind::ConstraintViolation(Constraint::MaxStackAllocation));
+        assert_eq!(run(&mut interpreter, opcode::DUP_OPERAND, 0), expected(&[1, 2, 3, 3]));
+    }
+
+    #[test]
+    /// Checks instructions consume the objects they read from stack
+    fn interpreter_frame_consume_operands() {
+        let mut interpreter = Interpreter::new(Constraints::new_none());
+
+        let mut frame = Frame { stack: vec![true.into(), 1u8.into(), 2u8.into(), 3u8.into()], ..Default::default() };
+        frame.instructions.push(instruction(opcode::ADD_U8, 0));
+        frame.instructions.push(instruction(opcode::NOT_U8, 0));
+        frame.instructions.push(instruction(opcode::NOT, 0));
+        interpreter.execute_frame(&mut frame).unwrap();
+        assert!(frame.stack.is_empty());
+        assert_eq!(frame.operand_stack, vec![5u8.into(), (!1u8).into(), false.into()]);
+
+        // a faulting instruction leaves its operands on stack
+        let mut frame = Frame { stack: vec![0u8.into(), 1u8.into()], ..Default::default() };
+        frame.instructions.push(instruction(opcode::DIV_U8, 0));
+        assert_eq!(interpreter.execute_frame(&mut frame).unwrap_err().kind, TrapKind::DivisionByZero);
+        assert_eq!(frame.stack, vec![0u8.into(), 1u8.into()]);
+    }
+
+    #[test]
+    /// Performs CAST_WRAP and CAST_CHECKED on the object at the front of stack
+    fn interpreter_frame_cast() {
+        let mut interpreter = Interpreter::new(Constraints::new_none());
+
+        // each cast consumes the object it converts
+        let mut frame = Frame { stack: vec![VirtualObject::from(300u16); 4], ..Default::default() };
+        frame.instructions.push(instruction(opcode::CAST_CHECKED, HType::U64 as u64));
+        frame.instructions.push(instruction(opcode::CAST_CHECKED, HType::F32 as u64));
+        frame.instructions.push(instruction(opcode::CAST_WRAP, HType::U8 as u64));
+        interpreter.execute_frame(&mut frame).unwrap();
+        assert_eq!(frame.operand_stack, vec![
+            VirtualObject::from(300u64),
+            VirtualObject::from(300.0f32),
+            VirtualObject::from(44u8),
+        ]);
+
+        frame.clear_instructions();
+        frame.instructions.push(instruction(opcode::CAST_CHECKED, HType::I8 as u64));
+        let trap = interpreter.execute_frame(&mut frame).unwrap_err();
+        assert_eq!(trap.kind, TrapKind::LossyCast);
+        assert_eq!(frame.operand_stack.len(), 3);
+        assert_eq!(frame.stack, vec![VirtualObject::from(300u16)], "the trap leaves the operand on stack");
+
+        let mut frame = Frame { stack: vec![VirtualObject::from(1u8)], ..Default::default() };
+        frame.instructions.push(instruction(opcode::CAST_WRAP, 99));
+        assert_eq!(interpreter.execute_frame(&mut frame).unwrap_err().kind, TrapKind::InvalidType);
+    }
+
+    #[test]
+    /// Checks the overflow behaviour of the default, WRAP, CHECKED and SAT variants
+    ///
+    /// The expected values use Rust's explicit wrapping and saturating
+    /// methods, so this passes with and without `debug_assertions`.
+    fn interpreter_frame_overflow_variants() {
+        let mut interpreter = Interpreter::new(Constraints::new_none());
+        let run = |interpreter: &mut Interpreter, lhs: VirtualObject, rhs: VirtualObject, op| {
+            let mut frame = Frame { stack: vec![rhs, lhs], ..Default::default() };
+            frame.instructions.push(instruction(op, 0));
+            interpreter.execute_frame(&mut frame).map(|_| frame.operand_stack.pop().unwrap()).map_err(|trap| trap.kind)
+        };
+
+        type Expected = Result<VirtualObject, TrapKind>;
+        let cases: [(VirtualObject, VirtualObject, [u64; 4], [Expected; 4]); 6] = [
+            (u8::MAX.into(), 1u8.into(),
+             [opcode::ADD_U8, opcode::ADD_CHECKED_U8, opcode::ADD_WRAP_U8, opcode::ADD_SAT_U8],
+             [Err(TrapKind::Overflow), Err(TrapKind::Overflow), Ok(0u8.into()), Ok(u8::MAX.into())]),
+            (0u32.into(), 1u32.into(),
+             [opcode::SUB_U32, opcode::SUB_CHECKED_U32, opcode::SUB_WRAP_U32, opcode::SUB_SAT_U32],
+             [Err(TrapKind::Overflow), Err(TrapKind::Overflow), Ok(u32::MAX.into()), Ok(0u32.into())]),
+            (i16::MIN.into(), 1i16.into(),
+             [opcode::SUB_I16, opcode::SUB_CHECKED_I16, opcode::SUB_WRAP_I16, opcode::SUB_SAT_I16],
+             [Err(TrapKind::Overflow), Err(TrapKind::Overflow), Ok(i16::MAX.into()), Ok(i16::MIN.into())]),
+            (i64::MAX.into(), 2i64.into(),
+             [opcode::MUL_I64, opcode::MUL_CHECKED_I64, opcode::MUL_WRAP_I64, opcode::MUL_SAT_I64],
+             [Err(TrapKind::Overflow), Err(TrapKind::Overflow), Ok(i64::MAX.wrapping_mul(2).into()), Ok(i64::MAX.into())]),
+            (i8::MIN.into(), (-1i8).into(),
+             [opcode::DIV_I8, opcode::DIV_CHECKED_I8, opcode::DIV_WRAP_I8, opcode::DIV_SAT_I8],
+             [Err(TrapKind::Overflow), Err(TrapKind::Overflow), Ok(i8::MIN.into()), Ok(i8::MAX.into())]),
+            (1u16.into(), 0u16.into(),
+             [opcode::DIV_U16, opcode::DIV_CHECKED_U16, opcode::DIV_WRAP_U16, opcode::DIV_SAT_U16],
+             [Err(TrapKind::DivisionByZero), Err(TrapKind::DivisionByZero), Err(TrapKind::DivisionByZero), Err(TrapKind::DivisionByZero)]),
+        ];
+        for (lhs, rhs, ops, expected) in cases {
+            for (op, expected) in ops.into_iter().zip(expected) {
+                let result = run(&mut interpreter, lhs.clone(), rhs.clone(), op);
+                assert_eq!(result, expected, "{}", opcode::to_mnemonic(op).unwrap());
+            }
+        }
+
+        // results that fit are the same in every variant
+        for op in [opcode::ADD_I32, opcode::ADD_CHECKED_I32, opcode::ADD_WRAP_I32, opcode::ADD_SAT_I32] {
+            assert_eq!(run(&mut interpreter, (-3i32).into(), 5i32.into(), op), Ok(2i32.into()));
+        }
+    }
+
+    #[test]
+    /// Performs PWR_[HType] and its overflow variants
+    fn interpreter_frame_pwr() {
+        let mut interpreter = Interpreter::new(Constraints::new_none());
+
+        // the front object is the base
+        let cases: [(VirtualObject, VirtualObject, u64, Result<VirtualObject, TrapKind>); 17] = [
+            (2u8.into(), 7u8.into(), opcode::PWR_U8, Ok(128u8.into())),
+            (2u8.into(), 8u8.into(), opcode::PWR_U8, Err(TrapKind::Overflow)),
+            (2u8.into(), 8u8.into(), opcode::PWR_CHECKED_U8, Err(TrapKind::Overflow)),
+            (2u8.into(), 8u8.into(), opcode::PWR_WRAP_U8, Ok(0u8.into())),
+            (2u8.into(), 8u8.into(), opcode::PWR_SAT_U8, Ok(u8::MAX.into())),
+            (3u64.into(), 0u64.into(), opcode::PWR_U64, Ok(1u64.into())),
+            ((-2i32).into(), 3i32.into(), opcode::PWR_I32, Ok((-8i32).into())),
+            ((-2i8).into(), 7i8.into(), opcode::PWR_SAT_I8, Ok(i8::MIN.into())),
+            (2i16.into(), (-1i16).into(), opcode::PWR_I16, Err(TrapKind::NegativeExponent)),
+            ((-1i64).into(), (u32::MAX as i64 + 2).into(), opcode::PWR_I64, Ok((-1i64).into())),
+            ((-1i64).into(), (u32::MAX as i64 + 1).into(), opcode::PWR_I64, Ok(1i64.into())),
+            (2i64.into(), (u32::MAX as i64 + 1).into(), opcode::PWR_WRAP_I64, Ok(0i64.into())),
+            // 3^(2^32) = (3^(2^16))^(2^16)
+            (3u64.into(), (1u64 << 32).into(), opcode::PWR_WRAP_U64, Ok(3u64.wrapping_pow(1 << 16).wrapping_pow(1 << 16).into())),
+            ((-3i64).into(), ((1i64 << 32) + 1).into(), opcode::PWR_WRAP_I64, Ok((-3i64).wrapping_pow(1 << 16).wrapping_pow(1 << 16).wrapping_mul(-3).into())),
+            (3u8.into(), 7u8.into(), opcode::PWR_WRAP_U8, Ok(3u8.wrapping_pow(7).into())),
+            (2i32.into(), (-1i32).into(), opcode::PWR_WRAP_I32, Err(TrapKind::NegativeExponent)),
+            (2.0f64.into(), (-1.0f64).into(), opcode::PWR_F64, Ok(0.5f64.into())),
+        ];
+        for (base, exponent, op, expected) in cases {
+            let mut frame = Frame { stack: vec![exponent, base], ..Default::default() };
+            frame.instructions.push(instruction(op, 0));
+            let result = interpreter.execute_frame(&mut frame).map(|_| frame.operand_stack.pop().unwrap());
+            assert_eq!(result.map_err(|trap| trap.kind), expected, "{}", opcode::to_mnemonic(op).unwrap());
+        }
+    }
+
+    #[test]
+    /// Performs the bitwise families on integers and XOR on bools
+    fn interpreter_frame_bitwise() {
+        let mut interpreter = Interpreter::new(Constraints::new_none());
+
+        // the front object is the left hand side
+        let cases: [(VirtualObject, VirtualObject, u64, VirtualObject); 14] = [
+            (0b1100u8.into(), 0b1010u8.into(), opcode::AND_U8, 0b1000u8.into()),
+            (0b1100u16.into(), 0b1010u16.into(), opcode::OR_U16, 0b1110u16.into()),
+            (0b1100u32.into(), 0b1010u32.into(), opcode::XOR_U32, 0b0110u32.into()),
+            ((-1i64).into(), 0x0Fi64.into(), opcode::XOR_I64, (-16i64).into()),
+            (1u8.into(), 7u8.into(), opcode::SHL_U8, 0x80u8.into()),
+            (1u8.into(), 9u8.into(), opcode::SHL_U8, 2u8.into()),
+            (0x80u8.into(), 7u8.into(), opcode::SHR_U8, 1u8.into()),
+            ((-128i8).into(), 7i8.into(), opcode::SHR_I8, (-1i8).into()),
+            ((-8i32).into(), 1i32.into(), opcode::SHR_I32, (-4i32).into()),
+            (0x8001u16.into(), 1u16.into(), opcode::ROTL_U16, 0x0003u16.into()),
+            (0x8001u16.into(), 1u16.into(), opcode::ROTR_U16, 0xC000u16.into()),
+            (1u64.into(), 65u64.into(), opcode::ROTR_U64, (1u64 << 63).into()),
+            (true.into(), true.into(), opcode::XOR, false.into()),
+            (true.into(), false.into(), opcode::XOR, true.into()),
+        ];
+        for (lhs, rhs, op, expected) in cases {
+            let mut frame = Frame { stack: vec![rhs, lhs], ..Default::default() };
+            frame.instructions.push(instruction(op, 0));
+            interpreter.execute_frame(&mut frame).unwrap();
+            assert_eq!(frame.operand_stack.pop().unwrap(), expected, "{}", opcode::to_mnemonic(op).unwrap());
+        }
+
+        for (obj, op, expected) in [
+            (VirtualObject::from(0x0Fu8), opcode::NOT_U8, VirtualObject::from(0xF0u8)),
+            (VirtualObject::from(0i16), opcode::NOT_I16, VirtualObject::from(-1i16)),
+        ] {
+            let mut frame = Frame { stack: vec![obj], ..Default::default() };
+            frame.instructions.push(instruction(op, 0));
+            interpreter.execute_frame(&mut frame).unwrap();
+            assert_eq!(frame.operand_stack.pop().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    /// Performs REM_[HType], MOD_[HType] and DIVREM_[HType]
+    fn interpreter_frame_remainder() {
+        let mut interpreter = Interpreter::new(Constraints::new_none());
+        let run = |interpreter: &mut Interpreter, lhs: VirtualObject, rhs: VirtualObject, op| {
+            let mut frame = Frame { stack: vec![rhs, lhs], ..Default::default() };
+            frame.instructions.push(instruction(op, 0));
+            interpreter.execute_frame(&mut frame).map(|_| frame.operand_stack).map_err(|trap| trap.kind)
+        };
+
+        // the front object is the dividend
+        let cases: [(VirtualObject, VirtualObject, u64, VirtualObject); 10] = [
+            (7u8.into(), 3u8.into(), opcode::REM_U8, 1u8.into()),
+            (7u64.into(), 3u64.into(), opcode::MOD_U64, 1u64.into()),
+            ((-7i32).into(), 3i32.into(), opcode::REM_I32, (-1i32).into()),
+            ((-7i32).into(), 3i32.into(), opcode::MOD_I32, 2i32.into()),
+            (7i16.into(), (-3i16).into(), opcode::REM_I16, 1i16.into()),
+            (7i16.into(), (-3i16).into(), opcode::MOD_I16, 1i16.into()),
+            ((-7i8).into(), (-3i8).into(), opcode::REM_I8, (-1i8).into()),
+            ((-7i8).into(), (-3i8).into(), opcode::MOD_I8, 2i8.into()),
+            (i64::MIN.into(), (-1i64).into(), opcode::REM_I64, 0i64.into()),
+            (i64::MIN.into(), (-1i64).into(), opcode::MOD_I64, 0i64.into()),
+        ];
+        for (lhs, rhs, op, expected) in cases {
+            assert_eq!(run(&mut interpreter, lhs, rhs, op), Ok(vec![expected]), "{}", opcode::to_mnemonic(op).unwrap());
+        }
+
+        assert_eq!(run(&mut interpreter, (-7i32).into(), 2i32.into(), opcode::DIVREM_I32), Ok(vec![(-3i32).into(), (-1i32).into()]));
+        assert_eq!(run(&mut interpreter, 9u16.into(), 4u16.into(), opcode::DIVREM_U16), Ok(vec![2u16.into(), 1u16.into()]));
+        assert_eq!(run(&mut interpreter, i8::MIN.into(), (-1i8).into(), opcode::DIVREM_I8), Err(TrapKind::Overflow));
+
+        for op in [opcode::REM_U32, opcode::MOD_U32, opcode::DIVREM_U32] {
+            assert_eq!(run(&mut interpreter, 1u32.into(), 0u32.into(), op), Err(TrapKind::DivisionByZero));
+        }
+
+        // DIVREM pushes both results or neither
+        let mut interpreter = Interpreter::new(Constraints::new(2 * 8 + 8, 0));
+        assert_eq!(run(&mut interpreter, 9u64.into(), 4u64.into(), opcode::DIVREM_U64), Err(TrapKind::ConstraintViolation(Constraint::MaxMemory)));
+    }
+
+    #[test]
+    /// Checks that faulting instructions return a `Trap` instead of panicking
+    fn interpreter_frame_traps() {
+        let mut interpreter = Interpreter::new(Constraints::new_none());
+
+        let cases: [(Vec<VirtualObject>, u64, u64, TrapKind); 7] = [
+            (vec![], opcode::ADD_U8, 0, TrapKind::StackUnderflow),
+            (vec![VirtualObject::from(1u8)], opcode::NOT, 0, TrapKind::TypeMismatch { expected: HType::Bool, found: HType::U8 }),
+            (vec![VirtualObject::from(1u16)], opcode::LOAD_U8, 1, TrapKind::TypeMismatch { expected: HType::U8, found: HType::U16 }),
+            (vec![VirtualObject::from(0u32), VirtualObject::from(4u32)], opcode::DIV_U32, 0, TrapKind::DivisionByZero),
+            (vec![VirtualObject::from(1u8), VirtualObject::from(u8::MAX)], opcode::ADD_U8, 0, TrapKind::Overflow),
+            (vec![], opcode::ILLEGAL, 0, TrapKind::IllegalOpcode),
+            // the retired LOCAL_LOAD
+            (vec![VirtualObject::from(1u8)], 0x41, 0, TrapKind::IllegalOpcode),
+        ];
+
+        for (stack, op, arg1, kind) in cases {
+            let mut frame = Frame { stack, ..Default::default() };
+            frame.instructions.push(instruction(opcode::NONE, 0));
+            frame.instructions.push(instruction(op, arg1));
+
+            let trap = interpreter.execute_frame(&mut frame).unwrap_err();
+            assert_eq!(trap.kind, kind);
+            assert_eq!(trap.pc, 1);
+            assert_eq!(trap.instruction.opcode, op);
+            assert_eq!(frame.pc, 1, "pc stays on the faulting instruction");
+            assert!(frame.operand_stack.is_empty());
+        }
+    }
+
+    #[test]
+    /// Checks local opcodes past the end of the local slots trap
+    fn interpreter_frame_local_load_out_of_range() {
+        let mut interpreter = Interpreter::new(Constraints::new_none());
+        let mut frame = Frame::default();
+        frame.stack.push(VirtualObject::from(1u8));
+        frame.local.push(VirtualObject::from(1u8));
+
+        for op in [opcode::LOCAL_GET, opcode::LOCAL_SET, opcode::LOCAL_TEE] {
+            frame.pc = 0;
+            frame.instructions = vec![instruction(op, 1)];
+            let trap = interpreter.execute_frame(&mut frame).unwrap_err();
+            assert_eq!(trap.kind, TrapKind::LocalOutOfRange);
+        }
+        assert_eq!(frame.local.len(), 1);
+        assert_eq!(frame.stack.len(), 1);
+    }
+
+    #[test]
+    /// Performs JMP, JMP_IF_TRUE and JMP_IF_FALSE
+    fn interpreter_frame_jump() {
+        let mut interpreter = Interpreter::new(Constraints::new_none());
+
+        // forward and backward unconditional jumps
+        let mut frame = Frame::default();
+        frame.instructions.push(instruction(opcode::JMP, 2));
+        frame.instructions.push(instruction(opcode::JMP, 3));
+        frame.instructions.push(instruction(opcode::JMP, 1));
+        frame.instructions.push(instruction(opcode::NEW_U8, 0));
+        interpreter.execute_frame(&mut frame).unwrap();
+        assert_eq!(frame.stack.len(), 1);
+        assert_eq!(frame.pc, 4);
+
+        // conditional jumps consume the bool and only jump on a match
+        let cases = [
+            (opcode::JMP_IF_TRUE, true, vec![HType::U16]),
+            (opcode::JMP_IF_TRUE, false, vec![HType::U8, HType::U16]),
+            (opcode::JMP_IF_FALSE, false, vec![HType::U16]),
+            (opcode::JMP_IF_FALSE, true, vec![HType::U8, HType::U16]),
+        ];
+        for (op, condition, expected) in cases {
+            let mut frame = Frame::default();
+            frame.stack.push(VirtualObject::from(condition));
+            frame.instructions.push(instruction(op, 2));
+            frame.instructions.push(instruction(opcode::NEW_U8, 0));
+            frame.instructions.push(instruction(opcode::NEW_U16, 0));
+            interpreter.execute_frame(&mut frame).unwrap();
+
+            let types: Vec<HType> = frame.stack.iter().map(|obj| obj.data_type).collect();
+            assert_eq!(types, expected);
+        }
+
+        // conditional jumps on a non bool trap without consuming it
+        let mut frame = Frame::default();
+        frame.stack.push(VirtualObject::from(1u8));
+        frame.instructions.push(instruction(opcode::JMP_IF_TRUE, 0));
+        let trap = interpreter.execute_frame(&mut frame).unwrap_err();
+        assert_eq!(trap.kind, TrapKind::TypeMismatch { expected: HType::Bool, found: HType::U8 });
+        assert_eq!(frame.stack.len(), 1);
+    }
+
+    #[test]
+    /// Performs CALL and RET between frames
+    fn interpreter_frame_call() {
+        let mut interpreter = Interpreter::new(Constraints::new_none());
+        interpreter.functions.push(Function::new("seven", 0, vec![
+            instruction(opcode::NEW_U8, 0),
+            instruction(opcode::LOAD_U8, 7),
+            instruction(opcode::RET, 1),
+        ]));
+        interpreter.functions.push(Function::new("nested", 0, vec![
+            instruction(opcode::CALL, 0),
+        ]));
+
+        // return values end up on the caller's operand stack
+        let mut frame = Frame::default();
+        frame.instructions.push(instruction(opcode::CALL, 0));
+        frame.instructions.push(instruction(opcode::NEW_BOOL, 0));
+        interpreter.execute_frame(&mut frame).unwrap();
+        assert_eq!(frame.operand_stack.pop().unwrap(), VirtualObject::from(7u8));
+        assert_eq!(frame.stack.pop().unwrap().data_type, HType::Bool, "caller continues after the call");
+        assert!(frame.children.is_empty());
+
+        // running off the end of a function returns nothing
+        let mut frame = Frame::default();
+        frame.instructions.push(instruction(opcode::CALL, 1));
+        interpreter.execute_frame(&mut frame).unwrap();
+        assert!(frame.operand_stack.is_empty());
+        assert!(frame.children.is_empty());
+
+        // RET in the frame itself stops execution and hands the objects to the host
+        let mut frame = Frame::default();
+        frame.instructions.push(instruction(opcode::NEW_U8, 0));
+        frame.instructions.push(instruction(opcode::RET, 1));
+        frame.instructions.push(instruction(opcode::NEW_U16, 0));
+        interpreter.execute_frame(&mut frame).unwrap();
+        assert_eq!(frame.operand_stack.len(), 1);
+        assert!(frame.stack.is_empty());
+    }
+
+    #[test]
+    /// Checks CALL copies parameters into the callee's locals and keeps the call stack on a trap
+    fn interpreter_frame_call_params() {
+        let mut interpreter = Interpreter::new(Constraints::new_none());
+        interpreter.functions.push(Function::new("trap", 2, vec![instruction(opcode::ILLEGAL, 0)]));
+
+        let mut frame = Frame {
+            stack: vec![VirtualObject::from(1u8), VirtualObject::from(2u16), VirtualObject::from(3u32)],
+            ..Default::default()
+        };
+        frame.instructions.push(instruction(opcode::CALL, 0));
+
+        let trap = interpreter.execute_frame(&mut frame).unwrap_err();
+        assert_eq!(trap.kind, TrapKind::IllegalOpcode);
+        assert_eq!(trap.pc, 0);
+        assert_eq!(frame.children.len(), 1);
+        assert_eq!(frame.children[0].local, vec![VirtualObject::from(2u16), VirtualObject::from(3u32)]);
+        assert_eq!(frame.stack, vec![VirtualObject::from(1u8)]);
+
+        // declared locals follow the parameters, zeroed
+        interpreter.functions.push(Function {
+            locals: vec![HType::I16, HType::Bool],
+            ..Function::new("locals", 1, vec![instruction(opcode::ILLEGAL, 0)])
+        });
+        let mut frame = Frame { stack: vec![VirtualObject::from(1u8)], ..Default::default() };
+        frame.instructions.push(instruction(opcode::CALL, 1));
+        interpreter.execute_frame(&mut frame).unwrap_err();
+        assert_eq!(frame.children[0].local, vec![VirtualObject::from(1u8), VirtualObject::from(0i16), VirtualObject::from(false)]);
+
+        // calling an unknown function or without enough parameters traps in the caller
+        let mut frame = Frame::default();
+        frame.instructions.push(instruction(opcode::CALL, 5));
+        assert_eq!(interpreter.execute_frame(&mut frame).unwrap_err().kind, TrapKind::UndefinedFunction);
+
+        let mut frame = Frame::default();
+        frame.instructions.push(instruction(opcode::CALL, 0));
+        assert_eq!(interpreter.execute_frame(&mut frame).unwrap_err().kind, TrapKind::StackUnderflow);
+        assert!(frame.children.is_empty());
+    }
+
+    #[test]
+    /// Traps on unbounded recursion, every call shares the instructions of its function
+    fn interpreter_frame_call_depth() {
+        let mut interpreter = Interpreter::new(Constraints::new_none());
+        interpreter.functions.push(Function::new("recurse", 0, vec![instruction(opcode::CALL, 0)]));
+
+        let mut frame = interpreter.functions[0].frame(Vec::new());
+        let trap = interpreter.execute_frame(&mut frame).unwrap_err();
+        assert_eq!(trap.kind, TrapKind::CallStackOverflow);
+        assert_eq!(frame.children.len(), MAX_CALL_DEPTH as usize);
+        assert_eq!(frame.active_frame().pc, 0, "the faulting CALL is not executed");
+
+        let shared = &interpreter.functions[0].instructions;
+        for frame in std::iter::once(&frame).chain(&frame.children) {
+            assert!(frame.instructions.is_empty());
+            assert!(Arc::ptr_eq(frame.shared_instructions.as_ref().unwrap(), shared));
+        }
+    }
+
+    #[test]
+    /// Performs LOAD_CONST in frames and the functions they call
+    fn interpreter_frame_load_const() {
+        let mut interpreter = Interpreter::new(Constraints::new_none());
+        let function = Function {
+            constants: vec![VirtualObject::from(u64::MAX), VirtualObject::from(-0.5f64)],
+            ..Function::new("constants", 0, vec![instruction(opcode::LOAD_CONST, 1), instruction(opcode::RET, 1)])
+        };
+        interpreter.functions.push(function);
+
+        let mut frame = Frame { constant_pool: vec![VirtualObject::from(7i8)], ..Default::default() };
+        frame.instructions.push(instruction(opcode::LOAD_CONST, 0));
+        frame.instructions.push(instruction(opcode::LOAD_CONST, 0));
+        frame.instructions.push(instruction(opcode::CALL, 0));
+        interpreter.execute_frame(&mut frame).unwrap();
+        assert_eq!(frame.stack, vec![VirtualObject::from(7i8), VirtualObject::from(7i8)]);
+        assert_eq!(frame.operand_stack, vec![VirtualObject::from(-0.5f64)]);
+        assert_eq!(frame.constant_pool, vec![VirtualObject::from(7i8)], "constants are copied");
+
+        let mut frame = Frame::default();
+        frame.instructions.push(instruction(opcode::LOAD_CONST, 0));
+        assert_eq!(interpreter.execute_frame(&mut frame).unwrap_err().kind, TrapKind::ConstantOutOfRange);
+
+        // the constant pool of a called function counts against max_memory
+        let mut interpreter = Interpreter { constraints: Constraints::new(15, 0), ..interpreter };
+        let mut frame = Frame::default();
+        frame.instructions.push(instruction(opcode::CALL, 0));
+        let trap = interpreter.execute_frame(&mut frame).unwrap_err();
+        assert_eq!(trap.kind, TrapKind::ConstraintViolation(Constraint::MaxMemory));
+        assert!(frame.children.is_empty());
+    }
+
+    #[test]
+    /// Checks allocations trap once they would exceed `Constraints::max_memory`
+    fn interpreter_frame_max_memory() {
+        let mut interpreter = Interpreter::new(Constraints::new(3, 0));
+
+        let mut frame = Frame::default();
+        frame.instructions.push(instruction(opcode::NEW_U16, 0));
+        frame.instructions.push(instruction(opcode::NEW_U8, 0));
+        frame.instructions.push(instruction(opcode::NEW_U8, 0));
+        let trap = interpreter.execute_frame(&mut frame).unwrap_err();
+        assert_eq!(trap.kind, TrapKind::ConstraintViolation(Constraint::MaxMemory));
+        assert_eq!(trap.pc, 2);
+        assert_eq!(frame.memory_used(), 3);
+
+        // results on the operand stack count too
+        let mut frame = Frame { stack: vec![VirtualObject::from(1u8); 3], ..Default::default() };
+        frame.instructions.push(instruction(opcode::ADD_U8, 0));
+        let trap = interpreter.execute_frame(&mut frame).unwrap_err();
+        assert_eq!(trap.kind, TrapKind::ConstraintViolation(Constraint::MaxMemory));
+        assert!(frame.operand_stack.is_empty());
+
+        // as do called frames and their locals
+        let mut interpreter = Interpreter::new(Constraints::new(FRAME_HEADER_SIZE + 8, 0));
+        interpreter.functions.push(Function::new("alloc", 1, vec![instruction(opcode::NEW_U8, 0)]));
+        let mut frame = Frame::default();
+        frame.stack.push(VirtualObject::from(1u64));
+        frame.instructions.push(instruction(opcode::CALL, 0));
+        let trap = interpreter.execute_frame(&mut frame).unwrap_err();
+        assert_eq!(trap.kind, TrapKind::ConstraintViolation(Constraint::MaxMemory));
+        assert_eq!(frame.children.len(), 1);
+
+        // 0 is unlimited
+        let mut interpreter = Interpreter::new(Constraints::new_none());
+        let mut frame = Frame::default();
+        for _ in 0..64 {
+            frame.instructions.push(instruction(opcode::NEW_U64, 0));
+        }
+        interpreter.execute_frame(&mut frame).unwrap();
+        assert_eq!(frame.memory_used(), 64 * 8);
+    }
+
+    #[test]
+    /// Bounds recursion by `Constraints::max_memory` before the call depth, every call costs a header
+    fn interpreter_frame_max_memory_calls() {
+        let mut interpreter = Interpreter::new(Constraints::new(10 * FRAME_HEADER_SIZE, 0));
+        interpreter.functions.push(Function::new("recurse", 0, vec![instruction(opcode::CALL, 0)]));
+
+        let mut frame = interpreter.functions[0].frame(Vec::new());
+        let trap = interpreter.execute_frame(&mut frame).unwrap_err();
+        assert_eq!(trap.kind, TrapKind::ConstraintViolation(Constraint::MaxMemory));
+        assert_eq!(frame.children.len(), 10);
+        assert!(FRAME_HEADER_SIZE >= std::mem::size_of::<Frame>() as u64);
+    }
+
+    #[test]
+    /// Keeps count of the memory and references a frame holds instead of measuring them on every instruction
+    fn interpreter_frame_memory_count() {
+        let mut interpreter = Interpreter::new(Constraints::new(1_000_000, 0));
+        interpreter.functions.push(Function::new("add", 2, vec![
+            instruction(opcode::LOCAL_GET, 0),
+            instruction(opcode::LOCAL_GET, 1),
+            instruction(opcode::ADD_U64, 0),
+            instruction(opcode::STORE, 0),
+            instruction(opcode::RET, 1),
+        ]));
+        interpreter.functions.push(Function::new("replace", 1, vec![
+            instruction(opcode::NEW_OBJECT, 0),
+            instruction(opcode::STORE, 0),
+            instruction(opcode::LOCAL_SET, 0),
+            instruction(opcode::LOCAL_GET, 0),
+            instruction(opcode::RET, 1),
+        ]));
+        let mut frame = Frame { instructions: vec![
+            instruction(opcode::NEW_U64, 0),
+            instruction(opcode::LOAD_U64, 3),
+            instruction(opcode::ALLOC, HType::U32 as u64),
+            instruction(opcode::STORE, 0),
+            instruction(opcode::DUP, 0),
+            instruction(opcode::NEW_U32, 0),
+            instruction(opcode::LOAD_U32, 7),
+            instruction(opcode::STORE_PTR, 0),
+            instruction(opcode::FREE, 0),
+            instruction(opcode::NEW_U8, 0),
+            instruction(opcode::NEW_U16, 0),
+            instruction(opcode::NEW_OBJECT, 2),
+            instruction(opcode::STORE, 0),
+            instruction(opcode::DUP, 0),
+            instruction(opcode::NEW_U16, 0),
+            instruction(opcode::SET_FIELD, 1),
+            instruction(opcode::POP, 0),
+            instruction(opcode::NEW_U64, 0),
+            instruction(opcode::LOAD_U64, 4),
+            instruction(opcode::NEW_U64, 0),
+            instruction(opcode::LOAD_U64, 5),
+            instruction(opcode::CALL, 0),
+            instruction(opcode::STORE, 0),
+            instruction(opcode::NEW_BOOL, 0),
+            instruction(opcode::JMP_IF_FALSE, 25),
+            instruction(opcode::POP, 0),
+            instruction(opcode::NEW_OBJECT, 0),
+            instruction(opcode::STORE, 0),
+            instruction(opcode::CALL, 1),
+            instruction(opcode::STORE, 0),
+            instruction(opcode::POP, 0),
+        ], ..Default::default() };
+
+        while interpreter.step(&mut frame).unwrap() != StepResult::Finished {
+            assert_eq!(interpreter.frame_memory, frame.memory_used());
+            let mut roots = HashMap::new();
+            for id in gc::roots(&frame) {
+                *roots.entry(id).or_default() += 1;
+            }
+            assert_eq!(interpreter.frame_roots.get(&frame.address).cloned().unwrap_or_default(), roots);
+        }
+        assert_eq!(frame.memory_used(), 0);
+        assert!(interpreter.frame_roots.is_empty());
+    }
+
+    #[test]
+    /// Checks allocations trap once they would exceed `Constraints::max_stack_allocation`
+    fn interpreter_frame_max_stack_allocation() {
+        let mut interpreter = Interpreter::new(Constraints::new(0, 2));
+        let mut frame = Frame::default();
+        for _ in 0..3 {
+            frame.instructions.push(instruction(opcode::NEW_BOOL, 0));
+        }
+
+        let trap = interpreter.execute_frame(&mut frame).unwrap_err();
+        assert_eq!(trap.kind, TrapKind::ConstraintViolation(Constraint::MaxStackAllocation));
+        assert_eq!(trap.pc, 2);
+        assert_eq!(frame.stack.len(), 2);
+    }
+
+    #[test]
+    /// Checks fuel bounds execution and execution resumes after topping it up
+    fn interpreter_frame_fuel() {
+
+        // an endless loop runs out of fuel
+        let mut interpreter = Interpreter::new(Constraints::new_none());
+        interpreter.add_fuel(10);
+        let mut frame = Frame::default();
+        frame.instructions.push(instruction(opcode::JMP, 0));
+        let trap = interpreter.execute_frame(&mut frame).unwrap_err();
+        assert_eq!(trap.kind, TrapKind::OutOfFuel);
+        assert_eq!(interpreter.remaining_fuel(), Some(0));
+
+        // per opcode costs, resuming inside a call
+        let mut fuel = Fuel::new(4);
+        fuel.set_cost(opcode::NEW_U8, 2);
+        let mut interpreter = Interpreter::new(Constraints::new_none());
+        interpreter.fuel = Some(fuel);
+        interpreter.functions.push(Function::new("alloc", 0, vec![
+            instruction(opcode::NEW_U8, 0),
+            instruction(opcode::NEW_U8, 0),
+            instruction(opcode::RET, 2),
+        ]));
+        let mut frame = Frame::default();
+        frame.instructions.push(instruction(opcode::CALL, 0));
+
+        let trap = interpreter.execute_frame(&mut frame).unwrap_err();
+        assert_eq!(trap.kind, TrapKind::OutOfFuel);
+        assert_eq!(trap.pc, 1);
+        assert_eq!(interpreter.remaining_fuel(), Some(1));
+        assert_eq!(frame.children[0].stack.len(), 1, "the faulting instruction was not executed");
+
+        interpreter.add_fuel(2);
+        interpreter.execute_frame(&mut frame).unwrap();
+        assert_eq!(frame.operand_stack.len(), 2);
+        assert_eq!(interpreter.remaining_fuel(), Some(0));
+        // an instruction that traps for another reason burns nothing, resuming burns its cost once
+        let mut interpreter = Interpreter::new(Constraints::new_none());
+        interpreter.add_fuel(10);
+        let mut frame = Frame::default();
+        frame.instructions.push(instruction(opcode::NEW_U8, 0));
+        frame.instructions.push(instruction(opcode::ADD_U8, 0));
+        let trap = interpreter.execute_frame(&mut frame).unwrap_err();
+        assert_eq!(trap.kind, TrapKind::StackUnderflow);
+        assert_eq!(interpreter.remaining_fuel(), Some(9));
+
+        frame.stack.push(VirtualObject::from(1u8));
+        interpreter.execute_frame(&mut frame).unwrap();
+        assert_eq!(frame.operand_stack, vec![VirtualObject::from(1u8)]);
+        assert_eq!(interpreter.remaining_fuel(), Some(8));
+    }
+
+    #[test]
+    /// Steps through a frame one instruction at a time, including a call
+    fn interpreter_frame_step() {
+        let mut interpreter = Interpreter::new(Constraints::new_none());
+        interpreter.functions.push(Function::new("alloc", 0, vec![instruction(opcode::NEW_U16, 0)]));
+
+        let mut frame = Frame::default();
+        frame.instructions.push(instruction(opcode::CALL, 0));
+        frame.instructions.push(instruction(opcode::NEW_U8, 0));
+
+        assert_eq!(interpreter.step(&mut frame), Ok(StepResult::Running));
+        assert_eq!(frame.children.len(), 1);
+        assert_eq!(interpreter.step(&mut frame), Ok(StepResult::Running));
+        assert_eq!(frame.children[0].stack.len(), 1);
+        assert_eq!(interpreter.step(&mut frame), Ok(StepResult::Running), "returning from the end of a function is a step");
+        assert!(frame.children.is_empty());
+        assert_eq!(interpreter.step(&mut frame), Ok(StepResult::Finished));
+        assert_eq!(frame.stack.len(), 1);
+        assert_eq!(interpreter.step(&mut frame), Ok(StepResult::Finished), "stepping a finished frame does nothing");
+        assert_eq!(frame.stack.len(), 1);
+    }
+
+    #[test]
+    /// Pauses inside a call and resumes from the saved pc
+    fn interpreter_frame_run_until() {
+        let mut interpreter = Interpreter::new(Constraints::new_none());
+        interpreter.functions.push(Function::new("alloc", 0, vec![
+            instruction(opcode::NEW_U8, 0),
+            instruction(opcode::NEW_U8, 0),
+            instruction(opcode::RET, 2),
+        ]));
+
+        let mut frame = Frame::default();
+        frame.instructions.push(instruction(opcode::CALL, 0));
+        frame.instructions.push(instruction(opcode::NEW_BOOL, 0));
+
+        let at_ret = |frame: &Frame| frame.active_frame().code()[frame.active_frame().pc as usize].opcode == opcode::RET;
+        assert_eq!(interpreter.run_until(&mut frame, at_ret), Ok(StepResult::Paused));
+        assert_eq!(frame.children[0].pc, 2);
+        assert_eq!(frame.children[0].stack.len(), 2);
+
+        assert_eq!(interpreter.run_until(&mut frame, at_ret), Ok(StepResult::Paused), "pausing again before stepping does nothing");
+        interpreter.execute_frame(&mut frame).unwrap();
+        assert!(frame.is_finished());
+        assert_eq!(frame.operand_stack.len(), 2);
+        assert_eq!(frame.stack.len(), 1);
+    }
 }
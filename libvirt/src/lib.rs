@@ -1,5 +1,13 @@
+pub mod cast;
 pub mod constraints;
 pub mod instruction;
 pub mod interpreter;
+pub mod loader;
 pub mod frame;
+pub mod fuel;
+pub mod function;
+pub mod gc;
+pub mod heap;
+pub mod trap;
 pub mod types;
+pub mod verifier;
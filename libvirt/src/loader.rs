@@ -0,0 +1,262 @@
+use std::fmt;
+use std::io::Read;
+use lib_heat_spec::instruction::SIZE;
+use lib_heat_spec::module::{Module, ModuleError};
+use crate::function::Function;
+use crate::instruction::Instruction;
+use crate::types::VirtualObject;
+use crate::verifier::{verify_function, VerifyError};
+
+/// An error found while loading byte code
+#[derive(Debug)]
+pub enum LoadError {
+    Io(std::io::Error),
+    Module(ModuleError),
+    /// the byte code ends with an incomplete instruction starting at `offset`
+    TrailingBytes { offset: usize, len: usize },
+    /// the instruction at byte `offset` of the code section failed verification
+    Malformed { function: String, offset: usize, error: VerifyError },
+    /// the heat archive does not contain a `bin` file
+    MissingModule,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(err) => write!(f, "{}", err),
+            LoadError::Module(err) => write!(f, "{}", err),
+            LoadError::TrailingBytes { offset, len } => {
+                write!(f, "{} trailing bytes at byte {} do not form an instruction", len, offset)
+            }
+            LoadError::Malformed { function, offset, error } => {
+                write!(f, "{} at byte {:#x}: {}", function, offset, error.kind)
+            }
+            LoadError::MissingModule => write!(f, "archive does not contain a bin file"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<std::io::Error> for LoadError {
+    fn from(err: std::io::Error) -> LoadError {
+        LoadError::Io(err)
+    }
+}
+
+impl From<ModuleError> for LoadError {
+    fn from(err: ModuleError) -> LoadError {
+        LoadError::Module(err)
+    }
+}
+
+/// Decode a sequence of encoded instructions
+///
+/// ## Examples
+/// ```
+/// use libvirt::loader::load_instructions;
+///
+/// let instructions = load_instructions(&[0u8; 64]).unwrap();
+/// assert_eq!(instructions.len(), 2);
+/// assert!(load_instructions(&[0u8; 40]).is_err());
+/// ```
+pub fn load_instructions(bytes: &[u8]) -> Result<Vec<Instruction>, LoadError> {
+    let chunks = bytes.chunks_exact(SIZE as usize);
+    let remainder = chunks.remainder();
+    if !remainder.is_empty() {
+        return Err(LoadError::TrailingBytes { offset: bytes.len() - remainder.len(), len: remainder.len() });
+    }
+    Ok(chunks.map(Instruction::from).collect())
+}
+
+/// Read and decode a sequence of encoded instructions
+pub fn read_instructions<R: Read>(mut reader: R) -> Result<Vec<Instruction>, LoadError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    load_instructions(&bytes)
+}
+
+/// Validate a module, then decode and verify every function in the order of its function table
+pub fn load_module(module: &Module) -> Result<Vec<Function>, LoadError> {
+    module.validate()?;
+    let mut functions = Vec::with_capacity(module.functions.len());
+    for entry in &module.functions {
+        let instructions = load_instructions(module.function_code(entry))?;
+        let constants = module.function_constants(entry).iter()
+            .map(|constant| VirtualObject::new(constant.data.clone(), constant.h_type))
+            .collect();
+        let function = Function {
+            constants,
+            locals: entry.locals.clone(),
+            ..Function::new(&entry.name, entry.param_count, instructions)
+        };
+
+        if let Err(error) = verify_function(&function) {
+            let offset = (entry.code_start + error.pc) as usize * SIZE as usize;
+            return Err(LoadError::Malformed { function: entry.name.clone(), offset, error });
+        }
+        functions.push(function);
+    }
+    Ok(functions)
+}
+
+/// Read a module, then decode and verify its functions
+pub fn read_module<R: Read>(mut reader: R) -> Result<Vec<Function>, LoadError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    load_module(&Module::from_bytes(&bytes)?)
+}
+
+/// Read the module packed as `bin` in a heat archive, without unpacking the archive
+pub fn read_archive<R: Read>(reader: R) -> Result<Module, LoadError> {
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.path()?.as_os_str() == "bin" {
+            let mut bytes = Vec::new();
+            entry.read_to_end(&mut bytes)?;
+            return Ok(Module::from_bytes(&bytes)?);
+        }
+    }
+    Err(LoadError::MissingModule)
+}
+
+#[cfg(test)]
+mod tests {
+    use lib_heat_spec::h_type::HType;
+    use lib_heat_spec::module::{Constant, FunctionEntry, Module, ModuleError};
+    use lib_heat_spec::opcode;
+    use crate::instruction::Instruction;
+    use crate::loader::{load_instructions, load_module, read_archive, read_instructions, LoadError};
+    use crate::types::VirtualObject;
+
+    #[test]
+    fn loader_round_trip() {
+        let instructions = vec![
+            Instruction { opcode: opcode::NEW_U64, arg1: 0, arg2: 0, arg3: 0 },
+            Instruction { opcode: opcode::LOAD_U64, arg1: u64::MAX, arg2: 1, arg3: 2 },
+        ];
+        let bytes: Vec<u8> = instructions.iter().flat_map(Instruction::to_bytes).collect();
+
+        assert_eq!(load_instructions(&bytes).unwrap(), instructions);
+        assert_eq!(read_instructions(bytes.as_slice()).unwrap(), instructions);
+    }
+
+    #[test]
+    fn loader_trailing_bytes() {
+        match load_instructions(&[0u8; 70]) {
+            Err(LoadError::TrailingBytes { offset, len }) => assert_eq!((offset, len), (64, 6)),
+            other => panic!("expected trailing bytes, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn loader_module_offsets() {
+        let code: Vec<u8> = [
+            Instruction { opcode: opcode::NONE, arg1: 0, arg2: 0, arg3: 0 },
+            Instruction { opcode: opcode::NONE, arg1: 0, arg2: 0, arg3: 0 },
+            Instruction { opcode: opcode::JMP, arg1: 7, arg2: 0, arg3: 0 },
+        ].iter().flat_map(Instruction::to_bytes).collect();
+
+        let module = Module {
+            code,
+            functions: vec![
+                FunctionEntry { name: "main".to_string(), param_count: 0, code_start: 0, code_length: 1, constant_start: 0, constant_length: 0, locals: Vec::new() },
+                FunctionEntry { name: "jump".to_string(), param_count: 1, code_start: 1, code_length: 2, constant_start: 0, constant_length: 0, locals: Vec::new() },
+            ],
+            ..Default::default()
+        };
+
+        match load_module(&module) {
+            Err(LoadError::Malformed { function, offset, .. }) => {
+                assert_eq!(function, "jump");
+                assert_eq!(offset, 64);
+            }
+            other => panic!("expected a malformed instruction, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn loader_module_constants() {
+        let code: Vec<u8> = [
+            Instruction { opcode: opcode::LOAD_CONST, arg1: 0, arg2: 0, arg3: 0 },
+            Instruction { opcode: opcode::LOAD_CONST, arg1: 1, arg2: 0, arg3: 0 },
+        ].iter().flat_map(Instruction::to_bytes).collect();
+
+        let mut module = Module {
+            code,
+            constants: vec![
+                Constant::from_immediate(HType::I16, -2i64 as u64),
+                Constant::from_immediate(HType::U8, 9),
+            ],
+            functions: vec![
+                FunctionEntry { name: "first".to_string(), param_count: 0, code_start: 0, code_length: 1, constant_start: 1, constant_length: 1, locals: Vec::new() },
+                FunctionEntry { name: "both".to_string(), param_count: 0, code_start: 0, code_length: 2, constant_start: 0, constant_length: 2, locals: Vec::new() },
+            ],
+            ..Default::default()
+        };
+
+        let functions = load_module(&module).unwrap();
+        assert_eq!(functions[0].constants, vec![VirtualObject::from(9u8)]);
+        assert_eq!(functions[1].constants, vec![VirtualObject::from(-2i16), VirtualObject::from(9u8)]);
+
+        module.functions[1].constant_length = 1;
+        match load_module(&module) {
+            Err(LoadError::Malformed { function, offset, .. }) => assert_eq!((function.as_str(), offset), ("both", 32)),
+            other => panic!("expected a malformed instruction, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn loader_module_layout() {
+        let entry = FunctionEntry { name: "main".to_string(), param_count: 0, code_start: 0, code_length: 1, constant_start: 0, constant_length: 0, locals: Vec::new() };
+        let mut module = Module { code: vec![0u8; 32], functions: vec![entry], ..Default::default() };
+        assert!(load_module(&module).is_ok());
+
+        // hand built modules are validated instead of panicking
+        module.functions[0].code_start = u64::MAX;
+        assert!(matches!(load_module(&module), Err(LoadError::Module(ModuleError::Malformed { .. }))));
+        module.functions[0].code_start = 0;
+        module.functions[0].constant_start = 3;
+        assert!(matches!(load_module(&module), Err(LoadError::Module(ModuleError::Malformed { .. }))));
+        module.functions[0].constant_start = 0;
+        module.code.push(0);
+        assert!(matches!(load_module(&module), Err(LoadError::Module(ModuleError::Malformed { .. }))));
+    }
+
+    /// Pack files into a heat archive
+    fn archive(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, bytes) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(bytes.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, *bytes).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn loader_archive() {
+        let module = Module { code: vec![0u8; 32], ..Default::default() };
+        let bytes = module.to_bytes().unwrap();
+
+        let packed = archive(&[("readme", b"heat"), ("bin", &bytes)]);
+        assert_eq!(read_archive(packed.as_slice()).unwrap(), module);
+
+        match read_archive(archive(&[("readme", b"heat")]).as_slice()) {
+            Err(LoadError::MissingModule) => {}
+            other => panic!("expected a missing module, got {:?}", other),
+        }
+        match read_archive(archive(&[("bin", &bytes[..20])]).as_slice()) {
+            Err(LoadError::Module(ModuleError::Truncated { .. })) => {}
+            other => panic!("expected a truncated module, got {:?}", other),
+        }
+
+        // an archive cut off inside the module is an error, not an empty archive
+        let start = packed.windows(bytes.len()).position(|window| window == bytes).unwrap();
+        assert!(read_archive(&packed[..start + 10]).is_err());
+        assert!(read_archive(&b"not an archive"[..]).is_err());
+    }
+}
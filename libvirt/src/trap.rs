@@ -0,0 +1,123 @@
+use std::fmt;
+use lib_heat_spec::h_type::HType;
+use crate::constraints::Constraint;
+use crate::instruction::Instruction;
+
+/// The reason an instruction faulted
+#[derive(Clone, Debug, PartialEq)]
+pub enum TrapKind {
+    /// an instruction needed more objects than the stack holds
+    StackUnderflow,
+
+    /// an object had a different `HType` than the instruction expected
+    TypeMismatch { expected: HType, found: HType },
+
+    /// the divisor of a division was zero
+    DivisionByZero,
+
+    /// the result of an arithmetic instruction does not fit its `HType`
+    Overflow,
+
+    /// a PWR on signed integers got a negative exponent
+    NegativeExponent,
+
+    /// an instruction referenced a local outside of the frame's locals
+    LocalOutOfRange,
+
+    /// a LOAD_CONST referenced a constant outside of the frame's constant pool
+    ConstantOutOfRange,
+
+    /// a CALL referenced a function the interpreter does not have
+    UndefinedFunction,
+
+    /// a CALL would nest more calls than `lib_heat_spec::frame::MAX_CALL_DEPTH`
+    CallStackOverflow,
+
+    /// the interpreter's fuel does not cover the cost of the instruction
+    OutOfFuel,
+
+    /// the opcode is not defined in `lib_heat_spec::opcode`
+    IllegalOpcode,
+
+    /// an argument is not a valid `HType` id
+    InvalidType,
+
+    /// a CAST_CHECKED would change the value
+    LossyCast,
+
+    /// a pointer does not point into a live heap allocation, or a FREE does
+    /// not point to the start of one
+    InvalidPointer,
+
+    /// a pointer points past the objects of its heap allocation
+    OutOfBounds,
+
+    /// an ALLOC is larger than `heap::MAX_ALLOCATION` or the host could not provide the memory
+    OutOfMemory,
+
+    /// a reference to a garbage collected object was null
+    NullReference,
+
+    /// an instruction referenced a field outside of a garbage collected object's fields
+    FieldOutOfRange,
+
+    /// the instruction would exceed one of the interpreter's `Constraints`
+    ConstraintViolation(Constraint),
+}
+
+/// A fault raised while executing a frame
+///
+/// The frame is left as it was before the faulting instruction, `pc` included,
+/// so the host can inspect it after reporting the fault.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trap {
+    pub kind: TrapKind,
+
+    /// program counter of the faulting instruction
+    pub pc: u64,
+
+    /// the faulting instruction
+    pub instruction: Instruction,
+}
+
+impl Trap {
+    pub fn new(kind: TrapKind, pc: u64, instruction: Instruction) -> Trap {
+        Trap { kind, pc, instruction }
+    }
+}
+
+impl fmt::Display for TrapKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrapKind::StackUnderflow => write!(f, "stack underflow"),
+            TrapKind::TypeMismatch { expected, found } => {
+                write!(f, "type mismatch: expected {:?}, found {:?}", expected, found)
+            }
+            TrapKind::DivisionByZero => write!(f, "division by zero"),
+            TrapKind::Overflow => write!(f, "arithmetic overflow"),
+            TrapKind::NegativeExponent => write!(f, "negative exponent"),
+            TrapKind::LocalOutOfRange => write!(f, "local index out of range"),
+            TrapKind::ConstantOutOfRange => write!(f, "constant index out of range"),
+            TrapKind::UndefinedFunction => write!(f, "undefined function"),
+            TrapKind::CallStackOverflow => write!(f, "call stack overflow"),
+            TrapKind::OutOfFuel => write!(f, "out of fuel"),
+            TrapKind::IllegalOpcode => write!(f, "illegal opcode"),
+            TrapKind::InvalidType => write!(f, "invalid type id"),
+            TrapKind::LossyCast => write!(f, "cast loses information"),
+            TrapKind::InvalidPointer => write!(f, "invalid pointer"),
+            TrapKind::OutOfBounds => write!(f, "pointer out of bounds"),
+            TrapKind::OutOfMemory => write!(f, "out of memory"),
+            TrapKind::NullReference => write!(f, "null reference"),
+            TrapKind::FieldOutOfRange => write!(f, "field index out of range"),
+            TrapKind::ConstraintViolation(constraint) => write!(f, "constraint violation: {:?} exceeded", constraint),
+        }
+    }
+}
+
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "trap at pc {} (opcode {:#x}): {}", self.pc, self.instruction.opcode, self.kind)
+    }
+}
+
+impl std::error::Error for Trap {}
@@ -4,20 +4,58 @@ use lib_heat_spec::h_type::HType;
 
 pub type VirtualAddress = u64;
 
+/// The null pointer, no allocation lives at it
+pub const NULL: VirtualAddress = 0;
+
+/// A typed pointer into a `Heap`, held by `VirtualObject`s of `HType::Ptr`
+///
+/// [`Heap`]: crate::heap::Heap
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct VirtualPointer {
     pub location: VirtualAddress,
     pub data_type: HType,
 }
 
+/// Id of a garbage collected object
+pub type ObjectId = u64;
+
+/// A reference to an object in an `ObjectHeap`, held by `VirtualObject`s of `HType::Ref`
+///
+/// Id 0 is the null reference, which zeroed references hold.
+///
+/// [`ObjectHeap`]: crate::gc::ObjectHeap
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VirtualReference {
+    pub id: ObjectId,
+}
+
+impl From<VirtualReference> for VirtualObject {
+    fn from(reference: VirtualReference) -> VirtualObject {
+        let mut obj = VirtualObject::new_empty(HType::Ref);
+        obj.set_reference(&reference);
+        obj
+    }
+}
+
+impl From<VirtualPointer> for VirtualObject {
+    fn from(pointer: VirtualPointer) -> VirtualObject {
+        let mut obj = VirtualObject::new_empty(HType::Ptr);
+        obj.set_pointer(&pointer);
+        obj
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct VirtualObject {
     pub data: Vec<u8>,
     pub data_type: HType,
 }
 
+/// Objects are equal if they hold the same bytes, so a NaN equals itself here,
+/// unlike the EQUAL opcode
 impl PartialEq for VirtualObject {
     fn eq(&self, other: &Self) -> bool {
-        return self.data_type == other.data_type && self.data == other.data;
+        self.data_type == other.data_type && self.data == other.data
     }
 }
 
@@ -25,35 +63,77 @@ impl From<bool> for VirtualObject {
     fn from(boolean: bool) -> VirtualObject {
         let mut obj = VirtualObject::new_empty(HType::Bool);
         obj.set_bool(&boolean);
-        return obj;
+        obj
     }
 }
 impl From<u8> for VirtualObject {
     fn from(u8: u8) -> VirtualObject {
         let mut obj = VirtualObject::new_empty(HType::U8);
         obj.set_u8(&u8);
-        return obj;
+        obj
     }
 }
 impl From<u16> for VirtualObject {
     fn from(u16: u16) -> VirtualObject {
         let mut obj = VirtualObject::new_empty(HType::U16);
         obj.set_u16(&u16);
-        return obj;
+        obj
     }
 }
 impl From<u32> for VirtualObject {
     fn from(u32: u32) -> VirtualObject {
         let mut obj = VirtualObject::new_empty(HType::U32);
         obj.set_u32(&u32);
-        return obj;
+        obj
     }
 }
 impl From<u64> for VirtualObject {
     fn from(u64: u64) -> VirtualObject {
         let mut obj = VirtualObject::new_empty(HType::U64);
         obj.set_u64(&u64);
-        return obj;
+        obj
+    }
+}
+impl From<i8> for VirtualObject {
+    fn from(i8: i8) -> VirtualObject {
+        let mut obj = VirtualObject::new_empty(HType::I8);
+        obj.set_i8(&i8);
+        obj
+    }
+}
+impl From<i16> for VirtualObject {
+    fn from(i16: i16) -> VirtualObject {
+        let mut obj = VirtualObject::new_empty(HType::I16);
+        obj.set_i16(&i16);
+        obj
+    }
+}
+impl From<i32> for VirtualObject {
+    fn from(i32: i32) -> VirtualObject {
+        let mut obj = VirtualObject::new_empty(HType::I32);
+        obj.set_i32(&i32);
+        obj
+    }
+}
+impl From<i64> for VirtualObject {
+    fn from(i64: i64) -> VirtualObject {
+        let mut obj = VirtualObject::new_empty(HType::I64);
+        obj.set_i64(&i64);
+        obj
+    }
+}
+impl From<f32> for VirtualObject {
+    fn from(f32: f32) -> VirtualObject {
+        let mut obj = VirtualObject::new_empty(HType::F32);
+        obj.set_f32(&f32);
+        obj
+    }
+}
+impl From<f64> for VirtualObject {
+    fn from(f64: f64) -> VirtualObject {
+        let mut obj = VirtualObject::new_empty(HType::F64);
+        obj.set_f64(&f64);
+        obj
     }
 }
 
@@ -68,25 +148,16 @@ impl VirtualObject {
     
     /// Create an VirtualObject with an empty `Vec<u8>` with the capacity required to hold the `HType`
     pub fn new_empty(data_type: HType) -> VirtualObject {
-        let mut vec = Vec::<u8>::with_capacity(h_type::get_size(data_type));
-        for _ in 0..h_type::get_size(data_type){
-            vec.push(0u8);
-        }
-
         VirtualObject {
-            data: vec,
+            data: vec![0u8; h_type::get_size(data_type)],
             data_type,
         }
     }
 
     /// Create an VirtualObject with max filled `Vec<u8>` for the given `HType`
     pub fn new_max(data_type: HType) -> VirtualObject {
-        let mut vec = Vec::<u8>::with_capacity(h_type::get_size(data_type));
-        for _ in 0..h_type::get_size(data_type){
-            vec.push(u8::MAX);
-        }
         VirtualObject {
-            data: vec,
+            data: vec![u8::MAX; h_type::get_size(data_type)],
             data_type,
         }
     }
@@ -119,32 +190,149 @@ impl VirtualObject {
         self.data.append(&mut Vec::from(value));
     }
 
+    pub fn set_i8(&mut self, value:&i8) {
+        let value = value.to_be_bytes();
+        self.data.clear();
+        self.data.append(&mut Vec::from(value));
+    }
+
+    pub fn set_i16(&mut self, value:&i16) {
+        let value = value.to_be_bytes();
+        self.data.clear();
+        self.data.append(&mut Vec::from(value));
+    }
+
+    pub fn set_i32(&mut self, value:&i32) {
+        let value = value.to_be_bytes();
+        self.data.clear();
+        self.data.append(&mut Vec::from(value));
+    }
+
+    pub fn set_i64(&mut self, value:&i64) {
+        let value = value.to_be_bytes();
+        self.data.clear();
+        self.data.append(&mut Vec::from(value));
+    }
+
+    pub fn set_f32(&mut self, value:&f32) {
+        let value = value.to_be_bytes();
+        self.data.clear();
+        self.data.append(&mut Vec::from(value));
+    }
+
+    pub fn set_f64(&mut self, value:&f64) {
+        let value = value.to_be_bytes();
+        self.data.clear();
+        self.data.append(&mut Vec::from(value));
+    }
+
+
+    pub fn set_pointer(&mut self, value: &VirtualPointer) {
+        self.data.clear();
+        self.data.extend_from_slice(&value.location.to_be_bytes());
+        self.data.push(value.data_type as u8);
+    }
+
+    pub fn set_reference(&mut self, value: &VirtualReference) {
+        self.data.clear();
+        self.data.extend_from_slice(&value.id.to_be_bytes());
+    }
 
     pub fn get_bool(&self) -> bool {
-        return self.data[0] != 0;
+        self.data[0] != 0
     }
 
     pub fn get_u8(&self) -> u8 {
-        return self.data[0];
+        self.data[0]
     }
 
     pub fn get_u16(&self) -> u16 {
-        return BigEndian::read_u16(&self.data);
+        BigEndian::read_u16(&self.data)
     }
 
     pub fn get_u32(&self) -> u32 {
-        return BigEndian::read_u32(&self.data);
+        BigEndian::read_u32(&self.data)
     }
 
     pub fn get_u64(&self) -> u64 {
-        return BigEndian::read_u64(&self.data);
+        BigEndian::read_u64(&self.data)
+    }
+
+    pub fn get_i8(&self) -> i8 {
+        self.data[0] as i8
+    }
+
+    pub fn get_i16(&self) -> i16 {
+        BigEndian::read_i16(&self.data)
+    }
+
+    pub fn get_i32(&self) -> i32 {
+        BigEndian::read_i32(&self.data)
+    }
+
+    pub fn get_i64(&self) -> i64 {
+        BigEndian::read_i64(&self.data)
     }
+
+    pub fn get_f32(&self) -> f32 {
+        BigEndian::read_f32(&self.data)
+    }
+
+    pub fn get_f64(&self) -> f64 {
+        BigEndian::read_f64(&self.data)
+    }
+
+    pub fn get_reference(&self) -> VirtualReference {
+        VirtualReference { id: BigEndian::read_u64(&self.data) }
+    }
+
+    /// Read a pointer, an unknown type id reads as `HType::Bool` like the one of a zeroed pointer
+    pub fn get_pointer(&self) -> VirtualPointer {
+        VirtualPointer {
+            location: BigEndian::read_u64(&self.data),
+            data_type: h_type::from_id(self.data[8]).unwrap_or(HType::Bool),
+        }
+    }
+}
+
+/// A Rust type stored in `VirtualObject`s of an `HType`
+pub trait Primitive: Copy + Into<VirtualObject> {
+    const H_TYPE: HType;
+
+    /// Read the value of an object, which must hold `Self::H_TYPE`
+    fn get(obj: &VirtualObject) -> Self;
+}
+
+macro_rules! primitive {
+    ($($t:ty => $h_type:ident, $get:ident;)*) => {
+        $(impl Primitive for $t {
+            const H_TYPE: HType = HType::$h_type;
+
+            fn get(obj: &VirtualObject) -> Self {
+                obj.$get()
+            }
+        })*
+    };
+}
+
+primitive! {
+    bool => Bool, get_bool;
+    u8 => U8, get_u8;
+    u16 => U16, get_u16;
+    u32 => U32, get_u32;
+    u64 => U64, get_u64;
+    i8 => I8, get_i8;
+    i16 => I16, get_i16;
+    i32 => I32, get_i32;
+    i64 => I64, get_i64;
+    f32 => F32, get_f32;
+    f64 => F64, get_f64;
 }
 
 #[cfg(test)]
 mod tests {
     use lib_heat_spec::h_type::HType;
-    use crate::types::VirtualObject;
+    use crate::types::{VirtualObject, VirtualPointer, VirtualReference, NULL};
 
     #[test]
     fn virtual_object_set_get_bool() {
@@ -152,7 +340,7 @@ mod tests {
 
         vobj.set_bool(&true);
 
-        assert_eq!(vobj.get_bool(), true);
+        assert!(vobj.get_bool());
     }
 
 
@@ -192,6 +380,49 @@ mod tests {
         assert_eq!(vobj.get_u64(), u64::MAX);
     }
 
+    #[test]
+    fn virtual_object_set_get_signed() {
+        let mut vobj = VirtualObject::new_empty(HType::I8);
+        vobj.set_i8(&i8::MIN);
+        assert_eq!(vobj.get_i8(), i8::MIN);
+        assert_eq!(vobj.data, vec![0x80]);
+
+        assert_eq!(VirtualObject::from(-2i16).get_i16(), -2);
+        assert_eq!(VirtualObject::from(i32::MIN).get_i32(), i32::MIN);
+        assert_eq!(VirtualObject::from(-1i64).data, vec![0xFF; 8]);
+        assert_eq!(VirtualObject::from(-1i64).data_type, HType::I64);
+    }
+
+    #[test]
+    fn virtual_object_set_get_float() {
+        let mut vobj = VirtualObject::new_empty(HType::F32);
+        vobj.set_f32(&-0.0);
+        assert_eq!(vobj.data, vec![0x80, 0, 0, 0]);
+        assert!(vobj.get_f32().is_sign_negative());
+
+        assert_eq!(VirtualObject::from(f32::INFINITY).get_f32(), f32::INFINITY);
+        assert_eq!(VirtualObject::from(1.5f64).get_f64(), 1.5);
+        assert!(VirtualObject::from(f64::NAN).get_f64().is_nan());
+        assert_eq!(VirtualObject::from(f64::NAN), VirtualObject::from(f64::NAN), "objects compare bytes");
+    }
+
+    #[test]
+    fn virtual_object_set_get_handles() {
+        let pointer = VirtualPointer { location: 0x0102, data_type: HType::I16 };
+        let vobj = VirtualObject::from(pointer);
+        assert_eq!(vobj.data_type, HType::Ptr);
+        assert_eq!(vobj.data, vec![0, 0, 0, 0, 0, 0, 1, 2, HType::I16 as u8]);
+        assert_eq!(vobj.get_pointer(), pointer);
+
+        let null = VirtualObject::new_empty(HType::Ptr).get_pointer();
+        assert_eq!(null.location, NULL);
+
+        let reference = VirtualObject::from(VirtualReference { id: 0x0304 });
+        assert_eq!(reference.data, vec![0, 0, 0, 0, 0, 0, 3, 4]);
+        assert_eq!(reference.get_reference().id, 0x0304);
+        assert_eq!(VirtualObject::new_empty(HType::Ref).get_reference().id, 0);
+    }
+
     #[test]
     fn virtual_object_partial_equality() {
         // comparing two same type & same value objects (true)
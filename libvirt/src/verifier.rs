@@ -0,0 +1,507 @@
+use std::collections::HashSet;
+use std::fmt;
+use lib_heat_spec::h_type::{self, immediate_value, immediate_range, HType};
+use lib_heat_spec::opcode;
+use crate::function::Function;
+use crate::instruction::Instruction;
+
+/// The reason a sequence of instructions was rejected
+#[derive(Clone, Debug, PartialEq)]
+pub enum VerifyErrorKind {
+    /// a jump points past the end of the instructions
+    InvalidJumpTarget { target: u64 },
+
+    /// an immediate value does not fit the type the opcode loads
+    ImmediateOutOfRange { value: u64, h_type: HType },
+
+    /// an argument that should hold an `HType` id does not
+    InvalidType { id: u64 },
+
+    /// an instruction reads an object of a different `HType` than it expects
+    TypeMismatch { expected: HType, found: HType },
+
+    /// a LOAD_CONST references a constant outside of the function's constant pool
+    ConstantOutOfRange { index: u64 },
+
+    /// a LOCAL_GET, LOCAL_SET or LOCAL_TEE references a slot past the function's parameters and locals
+    LocalOutOfRange { index: u64 },
+}
+
+/// An error found while verifying instructions at load time
+#[derive(Clone, Debug, PartialEq)]
+pub struct VerifyError {
+    pub kind: VerifyErrorKind,
+
+    /// index of the rejected instruction
+    pub pc: u64,
+
+    /// the rejected instruction
+    pub instruction: Instruction,
+}
+
+impl fmt::Display for VerifyErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyErrorKind::InvalidJumpTarget { target } => write!(f, "invalid jump target {}", target),
+            VerifyErrorKind::ImmediateOutOfRange { value, h_type } => {
+                write!(f, "immediate {} does not fit {:?}", value, h_type)
+            }
+            VerifyErrorKind::InvalidType { id } => write!(f, "invalid type id {}", id),
+            VerifyErrorKind::TypeMismatch { expected, found } => {
+                write!(f, "type mismatch: expected {:?}, found {:?}", expected, found)
+            }
+            VerifyErrorKind::ConstantOutOfRange { index } => write!(f, "constant index {} out of range", index),
+            VerifyErrorKind::LocalOutOfRange { index } => write!(f, "local index {} out of range", index),
+        }
+    }
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid instruction at pc {} (opcode {:#x}): {}", self.pc, self.instruction.opcode, self.kind)
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// Check instructions before they are executed
+///
+/// Jump targets may point at any instruction or at the end of the
+/// instructions, which stops execution of the frame. Immediate values must
+/// fit the type of `opcode::immediate_type` and type arguments must be valid
+/// `HType` ids.
+///
+/// The types of the objects pushed by the instructions are tracked through
+/// straight-line code, so an instruction reading an object of the wrong type,
+/// like an LT_U8 comparing a u8 to a u16, is rejected. Nothing is known about
+/// the stacks at jump targets and after a CALL, where the check starts over.
+///
+/// ## Examples
+/// ```
+/// use libvirt::instruction::Instruction;
+/// use libvirt::verifier::verify;
+/// use lib_heat_spec::opcode;
+///
+/// let instructions = vec![Instruction { opcode: opcode::JMP, arg1: 2, arg2: 0, arg3: 0 }];
+/// assert!(verify(&instructions).is_err());
+/// ```
+pub fn verify(instructions: &[Instruction]) -> Result<(), VerifyError> {
+    check(instructions, None)
+}
+
+/// Check the instructions of a function against its constant pool and local slots
+///
+/// Unlike `verify`, which knows nothing about the constants and locals frames
+/// will hold, LOAD_CONST must reference one of the function's constants and
+/// LOCAL_GET, LOCAL_SET and LOCAL_TEE one of its parameters or declared locals.
+pub fn verify_function(function: &Function) -> Result<(), VerifyError> {
+    check(&function.instructions, Some(function))
+}
+
+fn check(instructions: &[Instruction], function: Option<&Function>) -> Result<(), VerifyError> {
+    let len = instructions.len() as u64;
+    let targets: HashSet<u64> = instructions.iter()
+        .filter(|i| matches!(i.opcode, opcode::JMP | opcode::JMP_IF_TRUE | opcode::JMP_IF_FALSE))
+        .map(|i| i.arg1)
+        .collect();
+    let mut types = Types::default();
+
+    for (pc, i) in instructions.iter().enumerate() {
+        if targets.contains(&(pc as u64)) {
+            types = Types::default();
+        }
+
+        let kind = match i.opcode {
+            opcode::JMP | opcode::JMP_IF_TRUE | opcode::JMP_IF_FALSE if i.arg1 > len => {
+                Some(VerifyErrorKind::InvalidJumpTarget { target: i.arg1 })
+            }
+            op if opcode::takes_type(op) && type_arg(i.arg1).is_none() => {
+                Some(VerifyErrorKind::InvalidType { id: i.arg1 })
+            }
+            // pointers only come from ALLOC and PTR_ADD, references from NEW_OBJECT
+            opcode::CAST_WRAP | opcode::CAST_CHECKED if type_arg(i.arg1).is_some_and(h_type::is_handle) => {
+                Some(VerifyErrorKind::InvalidType { id: i.arg1 })
+            }
+            // the collector does not trace references stored in the linear heap
+            opcode::ALLOC if type_arg(i.arg1) == Some(HType::Ref) => Some(VerifyErrorKind::InvalidType { id: i.arg1 }),
+            opcode::LOAD_CONST if function.is_some_and(|function| i.arg1 >= function.constants.len() as u64) => {
+                Some(VerifyErrorKind::ConstantOutOfRange { index: i.arg1 })
+            }
+            opcode::LOCAL_GET | opcode::LOCAL_SET | opcode::LOCAL_TEE
+                if function.is_some_and(|function| i.arg1 >= function.local_count() as u64) => {
+                Some(VerifyErrorKind::LocalOutOfRange { index: i.arg1 })
+            }
+            _ => match opcode::immediate_type(i.opcode) {
+                Some(h_type) if !immediate_range(h_type).contains(&immediate_value(h_type, i.arg1)) => {
+                    Some(VerifyErrorKind::ImmediateOutOfRange { value: i.arg1, h_type })
+                }
+                _ => types.apply(i, function).err(),
+            },
+        };
+
+        if let Some(kind) = kind {
+            return Err(VerifyError { kind, pc: pc as u64, instruction: i.clone() });
+        }
+    }
+    Ok(())
+}
+
+/// Types of the objects at the front of a frame's stacks, as far as they are
+/// known while verifying
+///
+/// Objects below the known ones and `None` entries may hold any type.
+#[derive(Default)]
+struct Types {
+    stack: Vec<Option<HType>>,
+    operand_stack: Vec<Option<HType>>,
+}
+
+impl Types {
+    /// Check the types an instruction reads and record the objects it pushes
+    fn apply(&mut self, i: &Instruction, function: Option<&Function>) -> Result<(), VerifyErrorKind> {
+        let h_type = opcode::operand_type(i.opcode);
+        match i.opcode {
+            // undefined opcodes trap, so nothing after them depends on what they do
+            op if opcode::to_mnemonic(op).is_none() => {}
+            opcode::NONE => {}
+            opcode::NEW_BOOL..=opcode::NEW_F64 => {
+                self.stack.push(h_type::from_id((i.opcode - opcode::NEW_BOOL) as u8));
+            }
+            opcode::LOAD_CONST => {
+                let constant = function.and_then(|function| function.constants.get(i.arg1 as usize));
+                self.stack.push(constant.map(|constant| constant.data_type));
+            }
+            opcode::LOCAL_GET => self.stack.push(local_type(function, i.arg1)),
+            opcode::LOCAL_SET => {
+                self.expect(0, local_type(function, i.arg1))?;
+                self.stack.pop();
+            }
+            opcode::LOCAL_TEE => self.expect(0, local_type(function, i.arg1))?,
+            opcode::JMP_IF_TRUE | opcode::JMP_IF_FALSE => {
+                self.expect(0, Some(HType::Bool))?;
+                self.stack.pop();
+            }
+            opcode::EQUAL => {
+                self.pop(2);
+                self.operand_stack.push(Some(HType::Bool));
+            }
+            opcode::NOT => self.unary(Some(HType::Bool), Some(HType::Bool))?,
+            opcode::AND | opcode::OR | opcode::XOR => self.binary(Some(HType::Bool), Some(HType::Bool))?,
+            opcode::STORE => {
+                let h_type = self.operand_stack.pop().flatten();
+                self.stack.push(h_type);
+            }
+            opcode::STORE_OPERAND => {
+                let h_type = self.stack.pop().flatten();
+                self.operand_stack.push(h_type);
+            }
+            opcode::ALLOC => {
+                self.expect(0, Some(HType::U64))?;
+                self.pop(1);
+                self.operand_stack.push(Some(HType::Ptr));
+            }
+            opcode::FREE => {
+                self.expect(0, Some(HType::Ptr))?;
+                self.pop(1);
+            }
+            opcode::LOAD_PTR => self.unary(Some(HType::Ptr), None)?,
+            opcode::STORE_PTR => {
+                self.expect(1, Some(HType::Ptr))?;
+                self.pop(2);
+            }
+            opcode::PTR_ADD => {
+                self.expect(0, Some(HType::U64))?;
+                self.expect(1, Some(HType::Ptr))?;
+                self.pop(2);
+                self.operand_stack.push(Some(HType::Ptr));
+            }
+            opcode::NEW_OBJECT => {
+                self.pop(usize::try_from(i.arg1).unwrap_or(usize::MAX));
+                self.operand_stack.push(Some(HType::Ref));
+            }
+            opcode::GET_FIELD => self.unary(Some(HType::Ref), None)?,
+            opcode::SET_FIELD => {
+                self.expect(1, Some(HType::Ref))?;
+                self.pop(2);
+            }
+            opcode::POP..=opcode::PICK => manipulate(&mut self.stack, i),
+            opcode::POP_OPERAND..=opcode::PICK_OPERAND => manipulate(&mut self.operand_stack, i),
+            opcode::CAST_WRAP | opcode::CAST_CHECKED => {
+                self.pop(1);
+                self.operand_stack.push(type_arg(i.arg1));
+            }
+            op if opcode::immediate_type(op).is_some() => self.expect(0, opcode::immediate_type(op))?,
+            opcode::ADD_U8..=opcode::PWR_F64
+            | opcode::ADD_WRAP_U8..=opcode::PWR_SAT_I64
+            | opcode::REM_U8..=opcode::MOD_I64
+            | opcode::AND_U8..=opcode::XOR_I64
+            | opcode::SHL_U8..=opcode::ROTR_I64 => self.binary(h_type, h_type)?,
+            opcode::LT_U8..=opcode::GE_F64 | opcode::NE_U8..=opcode::NE_F64 => self.binary(h_type, Some(HType::Bool))?,
+            opcode::NOT_U8..=opcode::NOT_I64 => self.unary(h_type, h_type)?,
+            opcode::DIVREM_U8..=opcode::DIVREM_I64 => {
+                self.binary(h_type, h_type)?;
+                self.operand_stack.push(h_type);
+            }
+            // JMP, CALL and RET leave the stacks unknown to the next instruction
+            _ => *self = Types::default(),
+        }
+        Ok(())
+    }
+
+    /// Check the object at the front of the stack holds `h_type`, pop it and push a `result`
+    fn unary(&mut self, h_type: Option<HType>, result: Option<HType>) -> Result<(), VerifyErrorKind> {
+        self.expect(0, h_type)?;
+        self.pop(1);
+        self.operand_stack.push(result);
+        Ok(())
+    }
+
+    /// Check the two objects at the front of the stack hold `h_type`, pop them and push a `result`
+    fn binary(&mut self, h_type: Option<HType>, result: Option<HType>) -> Result<(), VerifyErrorKind> {
+        self.expect(0, h_type)?;
+        self.expect(1, h_type)?;
+        self.pop(2);
+        self.operand_stack.push(result);
+        Ok(())
+    }
+
+    fn pop(&mut self, count: usize) {
+        self.stack.truncate(self.stack.len().saturating_sub(count));
+    }
+
+    /// Check the object at `offset` from the front of the stack holds `expected`, if both are known
+    fn expect(&self, offset: usize, expected: Option<HType>) -> Result<(), VerifyErrorKind> {
+        let found = self.stack.iter().rev().nth(offset).copied().flatten();
+        match (expected, found) {
+            (Some(expected), Some(found)) if expected != found => Err(VerifyErrorKind::TypeMismatch { expected, found }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Apply a stack manipulation opcode to the known types of a stack
+fn manipulate(stack: &mut Vec<Option<HType>>, i: &Instruction) {
+    // objects moved up from below the known ones may hold any type
+    let reach = |stack: &mut Vec<Option<HType>>, count: usize| {
+        let missing = count.saturating_sub(stack.len());
+        stack.splice(0..0, std::iter::repeat_n(None, missing));
+    };
+    let copy = |stack: &mut Vec<Option<HType>>, depth: usize| {
+        let h_type = stack.iter().rev().nth(depth).copied().flatten();
+        stack.push(h_type);
+    };
+
+    match i.opcode {
+        opcode::POP | opcode::POP_OPERAND => {
+            stack.pop();
+        }
+        opcode::SWAP | opcode::SWAP_OPERAND => {
+            reach(stack, 2);
+            let at = stack.len() - 2;
+            stack.swap(at, at + 1);
+        }
+        opcode::ROT | opcode::ROT_OPERAND => {
+            reach(stack, 3);
+            let h_type = stack.remove(stack.len() - 3);
+            stack.push(h_type);
+        }
+        opcode::DUP | opcode::DUP_OPERAND => copy(stack, 0),
+        opcode::OVER | opcode::OVER_OPERAND => copy(stack, 1),
+        _ => copy(stack, usize::try_from(i.arg1).unwrap_or(usize::MAX)),
+    }
+}
+
+/// Get the type of a declared local slot, parameters hold whatever the caller passes
+fn local_type(function: Option<&Function>, index: u64) -> Option<HType> {
+    let function = function?;
+    let slot = (index as usize).checked_sub(function.param_count as usize)?;
+    function.locals.get(slot).copied()
+}
+
+/// Get the `HType` of an argument holding an `HType` id
+pub fn type_arg(arg: u64) -> Option<HType> {
+    u8::try_from(arg).ok().and_then(h_type::from_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use lib_heat_spec::h_type::HType;
+    use lib_heat_spec::opcode;
+    use crate::function::Function;
+    use crate::instruction::Instruction;
+    use crate::types::VirtualObject;
+    use crate::verifier::{verify, verify_function, VerifyErrorKind};
+
+    /// Build an instruction that only uses its first argument
+    fn instruction(opcode: u64, arg1: u64) -> Instruction {
+        Instruction { opcode, arg1, arg2: 0, arg3: 0 }
+    }
+
+    #[test]
+    fn verify_jump_targets() {
+
+        assert!(verify(&[instruction(opcode::JMP_IF_FALSE, 0), instruction(opcode::NONE, 0)]).is_ok(), "jumping to an instruction is valid");
+        assert!(verify(&[instruction(opcode::JMP_IF_FALSE, 2), instruction(opcode::NONE, 0)]).is_ok(), "jumping to the end is valid");
+
+        let err = verify(&[instruction(opcode::NONE, 0), instruction(opcode::JMP_IF_FALSE, 3)]).unwrap_err();
+        assert_eq!(err.kind, VerifyErrorKind::InvalidJumpTarget { target: 3 });
+        assert_eq!(err.pc, 1);
+    }
+
+    #[test]
+    fn verify_immediates() {
+        assert!(verify(&[instruction(opcode::LOAD_U8, 255), instruction(opcode::LOAD_BOOL, 1)]).is_ok());
+        assert!(verify(&[instruction(opcode::LOAD_U64, u64::MAX)]).is_ok());
+
+        let err = verify(&[instruction(opcode::LOAD_U8, 300)]).unwrap_err();
+        assert_eq!(err.kind, VerifyErrorKind::ImmediateOutOfRange { value: 300, h_type: HType::U8 });
+        assert!(verify(&[instruction(opcode::LOAD_BOOL, 2)]).is_err());
+
+        // signed immediates are sign extended
+        assert!(verify(&[instruction(opcode::LOAD_I8, -128i64 as u64), instruction(opcode::LOAD_I16, 32767)]).is_ok());
+        assert!(verify(&[instruction(opcode::LOAD_I8, 128)]).is_err());
+        assert!(verify(&[instruction(opcode::LOAD_I8, -129i64 as u64)]).is_err());
+
+        // float immediates are bit patterns
+        assert!(verify(&[instruction(opcode::LOAD_F32, f32::NAN.to_bits() as u64), instruction(opcode::LOAD_F64, u64::MAX)]).is_ok());
+        assert!(verify(&[instruction(opcode::LOAD_F32, 1.0f64.to_bits())]).is_err());
+    }
+
+    #[test]
+    fn verify_type_args() {
+        assert!(verify(&[instruction(opcode::CAST_CHECKED, HType::F64 as u64), instruction(opcode::CAST_CHECKED, HType::Bool as u64)]).is_ok());
+        assert_eq!(verify(&[instruction(opcode::CAST_CHECKED, 200)]).unwrap_err().kind, VerifyErrorKind::InvalidType { id: 200 });
+        assert!(verify(&[instruction(opcode::CAST_CHECKED, 0x100 + HType::U8 as u64)]).is_err(), "ids are not truncated to u8");
+    }
+
+    #[test]
+    fn verify_operand_types() {
+        let compare = [instruction(opcode::NEW_U8, 0), instruction(opcode::NEW_U8, 0), instruction(opcode::LT_U8, 0), instruction(opcode::STORE, 0), instruction(opcode::JMP_IF_TRUE, 0)];
+        assert!(verify(&compare).is_ok());
+
+        let err = verify(&[instruction(opcode::NEW_U16, 0), instruction(opcode::NEW_U8, 0), instruction(opcode::GE_U8, 0)]).unwrap_err();
+        assert_eq!(err.kind, VerifyErrorKind::TypeMismatch { expected: HType::U8, found: HType::U16 });
+        assert_eq!(err.pc, 2);
+
+        // results of casts and arithmetic are pushed onto the operand stack
+        let cast = [instruction(opcode::NEW_U8, 0), instruction(opcode::NEW_U8, 0), instruction(opcode::CAST_WRAP, HType::F32 as u64), instruction(opcode::STORE, 0), instruction(opcode::NE_F32, 0)];
+        assert_eq!(verify(&cast).unwrap_err().kind, VerifyErrorKind::TypeMismatch { expected: HType::F32, found: HType::U8 });
+        let sum = [instruction(opcode::NEW_I8, 0), instruction(opcode::NEW_I8, 0), instruction(opcode::ADD_I8, 0), instruction(opcode::STORE, 0), instruction(opcode::LOAD_I8, 1)];
+        assert!(verify(&sum).is_ok());
+        let lt = [instruction(opcode::NEW_I8, 0), instruction(opcode::NEW_I8, 0), instruction(opcode::LT_I8, 0), instruction(opcode::STORE, 0), instruction(opcode::LOAD_I8, 1)];
+        assert_eq!(verify(&lt).unwrap_err().kind, VerifyErrorKind::TypeMismatch { expected: HType::I8, found: HType::Bool });
+        assert!(verify(&[instruction(opcode::NEW_U32, 0), instruction(opcode::JMP_IF_FALSE, 2)]).is_err(), "conditions are bools");
+
+        // objects pushed by the host and at jump targets may hold any type
+        assert!(verify(&[instruction(opcode::NEW_U16, 0), instruction(opcode::LT_U16, 0)]).is_ok());
+        let branch = |target| [instruction(opcode::NEW_U8, 0), instruction(opcode::NEW_BOOL, 0), instruction(opcode::JMP_IF_TRUE, target), instruction(opcode::LT_U16, 0)];
+        assert!(verify(&branch(3)).is_ok());
+        assert!(verify(&branch(4)).is_err());
+    }
+
+    #[test]
+    fn verify_stack_manipulation() {
+
+        // U8 U16 -> U16 U8
+        assert!(verify(&[instruction(opcode::NEW_U8, 0), instruction(opcode::NEW_U16, 0), instruction(opcode::SWAP, 0), instruction(opcode::LOAD_U8, 0)]).is_ok());
+        assert!(verify(&[instruction(opcode::NEW_U8, 0), instruction(opcode::NEW_U16, 0), instruction(opcode::LOAD_U8, 0)]).is_err());
+
+        // U8 U16 BOOL -> U16 BOOL U8
+        let rot = [instruction(opcode::NEW_U8, 0), instruction(opcode::NEW_U16, 0), instruction(opcode::NEW_BOOL, 0), instruction(opcode::ROT, 0)];
+        assert!(verify(&[&rot[..], &[instruction(opcode::POP, 0), instruction(opcode::JMP_IF_TRUE, 0)]].concat()).is_ok());
+        let err = verify(&[&rot[..], &[instruction(opcode::PICK, 2), instruction(opcode::LT_U8, 0)]].concat()).unwrap_err();
+        assert_eq!(err.kind, VerifyErrorKind::TypeMismatch { expected: HType::U8, found: HType::U16 });
+
+        // the operand stack is tracked separately
+        let store = [instruction(opcode::NEW_U8, 0), instruction(opcode::NEW_U8, 0), instruction(opcode::EQUAL, 0), instruction(opcode::DUP_OPERAND, 0), instruction(opcode::STORE, 0)];
+        assert!(verify(&[&store[..], &[instruction(opcode::JMP_IF_TRUE, 0)]].concat()).is_ok());
+        assert!(verify(&[&store[..], &[instruction(opcode::LOAD_U8, 0)]].concat()).is_err());
+        // STORE moves the result, STORE_OPERAND moves it back
+        let moved = [instruction(opcode::NEW_U8, 0), instruction(opcode::NEW_U8, 0), instruction(opcode::EQUAL, 0), instruction(opcode::NEW_U16, 0), instruction(opcode::STORE, 0)];
+        assert!(verify(&[&moved[..], &[instruction(opcode::JMP_IF_TRUE, 0), instruction(opcode::LOAD_U16, 0)]].concat()).is_ok());
+        let err = verify(&[&moved[..], &[instruction(opcode::STORE_OPERAND, 0), instruction(opcode::STORE, 0), instruction(opcode::LOAD_U16, 0)]].concat()).unwrap_err();
+        assert_eq!(err.kind, VerifyErrorKind::TypeMismatch { expected: HType::U16, found: HType::Bool });
+
+        // objects swapped up from below the known ones may hold any type
+        assert!(verify(&[instruction(opcode::NEW_U8, 0), instruction(opcode::SWAP, 0), instruction(opcode::LOAD_U16, 0)]).is_ok());
+        assert!(verify(&[instruction(opcode::NEW_U8, 0), instruction(opcode::PICK, u64::MAX), instruction(opcode::LOAD_U16, 0)]).is_ok());
+    }
+
+    #[test]
+    fn verify_pointers() {
+        let alloc = [instruction(opcode::NEW_U64, 0), instruction(opcode::ALLOC, HType::U8 as u64), instruction(opcode::STORE, 0)];
+
+        let add = [instruction(opcode::NEW_U64, 0), instruction(opcode::PTR_ADD, 0), instruction(opcode::STORE, 0), instruction(opcode::DUP, 0), instruction(opcode::LOAD_PTR, 0)];
+        assert!(verify(&[&alloc[..], &add[..], &[instruction(opcode::POP, 0), instruction(opcode::FREE, 0)]].concat()).is_ok());
+        assert!(verify(&[&alloc[..], &[instruction(opcode::NEW_U8, 0), instruction(opcode::STORE_PTR, 0)]].concat()).is_ok());
+
+        let err = verify(&[&alloc[..], &[instruction(opcode::LOAD_U64, 0)]].concat()).unwrap_err();
+        assert_eq!(err.kind, VerifyErrorKind::TypeMismatch { expected: HType::U64, found: HType::Ptr });
+        let err = verify(&[instruction(opcode::NEW_U32, 0), instruction(opcode::ALLOC, 0)]).unwrap_err();
+        assert_eq!(err.kind, VerifyErrorKind::TypeMismatch { expected: HType::U64, found: HType::U32 });
+        let err = verify(&[instruction(opcode::NEW_U64, 0), instruction(opcode::NEW_U8, 0), instruction(opcode::STORE_PTR, 0)]).unwrap_err();
+        assert_eq!(err.kind, VerifyErrorKind::TypeMismatch { expected: HType::Ptr, found: HType::U64 });
+
+        // pointers can not be forged
+        let err = verify(&[instruction(opcode::NEW_U64, 0), instruction(opcode::CAST_WRAP, HType::Ptr as u64)]).unwrap_err();
+        assert_eq!(err.kind, VerifyErrorKind::InvalidType { id: HType::Ptr as u64 });
+    }
+
+    #[test]
+    fn verify_objects() {
+        let object = [instruction(opcode::NEW_U8, 0), instruction(opcode::NEW_U16, 0), instruction(opcode::NEW_OBJECT, 2), instruction(opcode::STORE, 0)];
+
+        assert!(verify(&[&object[..], &[instruction(opcode::DUP, 0), instruction(opcode::GET_FIELD, 1), instruction(opcode::NEW_U8, 0), instruction(opcode::SET_FIELD, 0)]].concat()).is_ok());
+        let err = verify(&[&object[..], &[instruction(opcode::LOAD_U8, 1)]].concat()).unwrap_err();
+        assert_eq!(err.kind, VerifyErrorKind::TypeMismatch { expected: HType::U8, found: HType::Ref });
+        let err = verify(&[instruction(opcode::NEW_U8, 0), instruction(opcode::NEW_OBJECT, 1), instruction(opcode::NEW_U8, 0), instruction(opcode::GET_FIELD, 0)]).unwrap_err();
+        assert_eq!(err.kind, VerifyErrorKind::TypeMismatch { expected: HType::Ref, found: HType::U8 });
+
+        // references can not be forged or hidden from the collector
+        let err = verify(&[instruction(opcode::NEW_U64, 0), instruction(opcode::CAST_CHECKED, HType::Ref as u64)]).unwrap_err();
+        assert_eq!(err.kind, VerifyErrorKind::InvalidType { id: HType::Ref as u64 });
+        let err = verify(&[instruction(opcode::NEW_U64, 0), instruction(opcode::ALLOC, HType::Ref as u64)]).unwrap_err();
+        assert_eq!(err.kind, VerifyErrorKind::InvalidType { id: HType::Ref as u64 });
+    }
+
+    #[test]
+    fn verify_function_constants() {
+        let function = |instructions| Function {
+            constants: vec![VirtualObject::from(1u8), VirtualObject::from(1u16)],
+            ..Function::new("constants", 0, instructions)
+        };
+
+        assert!(verify_function(&function(vec![instruction(opcode::LOAD_CONST, 0), instruction(opcode::LOAD_CONST, 0), instruction(opcode::LT_U8, 0)])).is_ok());
+        let err = verify_function(&function(vec![instruction(opcode::LOAD_CONST, 2)])).unwrap_err();
+        assert_eq!(err.kind, VerifyErrorKind::ConstantOutOfRange { index: 2 });
+        let err = verify_function(&function(vec![instruction(opcode::LOAD_CONST, 1), instruction(opcode::LOAD_CONST, 0), instruction(opcode::LT_U8, 0)])).unwrap_err();
+        assert_eq!(err.kind, VerifyErrorKind::TypeMismatch { expected: HType::U8, found: HType::U16 });
+
+        // without a function the constants are unknown
+        assert!(verify(&[instruction(opcode::LOAD_CONST, 1), instruction(opcode::LOAD_CONST, 0), instruction(opcode::LT_U8, 0)]).is_ok());
+    }
+
+    #[test]
+    fn verify_function_locals() {
+        let function = |instructions| Function {
+            locals: vec![HType::U8, HType::U16],
+            ..Function::new("locals", 1, instructions)
+        };
+
+        assert!(verify_function(&function(vec![instruction(opcode::LOCAL_GET, 1), instruction(opcode::LOCAL_TEE, 1), instruction(opcode::LOCAL_SET, 1)])).is_ok());
+        for opcode in [opcode::LOCAL_GET, opcode::LOCAL_SET, opcode::LOCAL_TEE] {
+            let err = verify_function(&function(vec![instruction(opcode, 3)])).unwrap_err();
+            assert_eq!(err.kind, VerifyErrorKind::LocalOutOfRange { index: 3 });
+        }
+
+        let err = verify_function(&function(vec![instruction(opcode::LOCAL_GET, 2), instruction(opcode::LOCAL_SET, 1)])).unwrap_err();
+        assert_eq!(err.kind, VerifyErrorKind::TypeMismatch { expected: HType::U8, found: HType::U16 });
+        let err = verify_function(&function(vec![instruction(opcode::LOCAL_GET, 1), instruction(opcode::LOAD_U16, 0)])).unwrap_err();
+        assert_eq!(err.kind, VerifyErrorKind::TypeMismatch { expected: HType::U16, found: HType::U8 });
+
+        // parameters hold any type
+        assert!(verify_function(&function(vec![instruction(opcode::LOCAL_GET, 0), instruction(opcode::LOAD_U16, 0), instruction(opcode::LOCAL_SET, 0)])).is_ok());
+        // without a function the slots are unknown
+        assert!(verify(&[instruction(opcode::LOCAL_GET, 9), instruction(opcode::LOAD_U16, 0)]).is_ok());
+    }
+}